@@ -0,0 +1,439 @@
+//! Derive macros for the `ToBencode` and `FromBencode` traits of the `bencode` crate.
+//!
+//! Structs with named fields are encoded as dictionaries keyed by the field names. Fields
+//! accept these attributes:
+//!
+//! - `#[bencode(rename = "creation date")]` uses a different dictionary key.
+//! - `#[bencode(skip)]` never encodes the field and decodes it as `Default::default()`.
+//! - `#[bencode(default)]` decodes a missing entry as `Default::default()`.
+//!
+//! Dictionary keys that match no field are ignored by default. On the struct itself:
+//!
+//! - `#[bencode(deny_unknown_fields)]` rejects them instead.
+//! - `#[bencode(extra = "field_name")]` collects them into the named
+//!   `BTreeMap<Vec<u8>, Bencode>` field, and writes them back out when encoding. A
+//!   `BTreeMap<String, Bencode>` works too, but rejects keys that are not valid UTF-8.
+//!
+//! Enums are encoded the way the serde serializer encodes them by default: a unit variant
+//! as a string holding its name, and a variant holding one unnamed field as a dictionary
+//! with a single entry from its name to that field. `#[bencode(rename = "...")]` on a
+//! variant uses a different name.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, parse_quote, Data, DataEnum, DeriveInput, Error, Fields, Generics, Ident,
+    LitStr, Type,
+};
+
+#[proc_macro_derive(ToBencode, attributes(bencode))]
+pub fn derive_to_bencode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_to_bencode(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(FromBencode, attributes(bencode))]
+pub fn derive_from_bencode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_bencode(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// A struct field together with its `#[bencode(...)]` attributes.
+struct Field<'a> {
+    ident: &'a Ident,
+    ty: &'a Type,
+    key: String,
+    skip: bool,
+    default: bool,
+}
+
+impl<'a> Field<'a> {
+    fn parse(field: &'a syn::Field) -> syn::Result<Self> {
+        let ident = field.ident.as_ref().unwrap();
+        let mut parsed = Field {
+            ident,
+            ty: &field.ty,
+            key: ident.to_string(),
+            skip: false,
+            default: false,
+        };
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("bencode"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    parsed.key = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("skip") {
+                    parsed.skip = true;
+                } else if meta.path.is_ident("default") {
+                    parsed.default = true;
+                } else {
+                    return Err(meta.error("unknown bencode field attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+/// An enum variant together with its `#[bencode(...)]` attributes.
+struct Variant<'a> {
+    ident: &'a Ident,
+    name: String,
+    /// The type of the variant's one unnamed field, or `None` for a unit variant.
+    ty: Option<&'a Type>,
+}
+
+impl<'a> Variant<'a> {
+    fn parse(variant: &'a syn::Variant) -> syn::Result<Self> {
+        let ty = match &variant.fields {
+            Fields::Unit => None,
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Some(&fields.unnamed[0].ty),
+            _ => return Err(Error::new(
+                variant.ident.span(),
+                "bencode can only be derived for unit variants and variants with one unnamed field",
+            )),
+        };
+        let mut parsed = Variant {
+            ident: &variant.ident,
+            name: variant.ident.to_string(),
+            ty,
+        };
+        for attr in variant
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("bencode"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    parsed.name = meta.value()?.parse::<LitStr>()?.value();
+                } else {
+                    return Err(meta.error("unknown bencode variant attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+/// The variants of an enum, which takes no container attributes.
+fn parse_variants<'a>(input: &'a DeriveInput, data: &'a DataEnum) -> syn::Result<Vec<Variant<'a>>> {
+    if let Some(attr) = input
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("bencode"))
+    {
+        return Err(Error::new(
+            attr.span(),
+            "bencode container attributes are only supported on structs",
+        ));
+    }
+    data.variants.iter().map(Variant::parse).collect()
+}
+
+/// A struct together with its `#[bencode(...)]` attributes.
+struct Container<'a> {
+    fields: Vec<Field<'a>>,
+    deny_unknown_fields: bool,
+    extra: Option<&'a Ident>,
+}
+
+impl<'a> Container<'a> {
+    fn parse(input: &'a DeriveInput) -> syn::Result<Self> {
+        let fields = match &input.data {
+            Data::Struct(data) => match &data.fields {
+                Fields::Named(fields) => fields.named.iter().map(Field::parse).collect(),
+                _ => Err(Error::new(
+                    input.ident.span(),
+                    "bencode can only be derived for structs with named fields",
+                )),
+            },
+            _ => Err(Error::new(
+                input.ident.span(),
+                "bencode can only be derived for structs and enums",
+            )),
+        }?;
+        let mut container = Container {
+            fields,
+            deny_unknown_fields: false,
+            extra: None,
+        };
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("bencode"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("deny_unknown_fields") {
+                    container.deny_unknown_fields = true;
+                } else if meta.path.is_ident("extra") {
+                    let name = meta.value()?.parse::<LitStr>()?;
+                    let field = container
+                        .fields
+                        .iter()
+                        .find(|field| *field.ident == name.value())
+                        .ok_or_else(|| Error::new(name.span(), "no field with this name"))?;
+                    container.extra = Some(field.ident);
+                } else {
+                    return Err(meta.error("unknown bencode container attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if container.deny_unknown_fields && container.extra.is_some() {
+            return Err(Error::new(
+                input.ident.span(),
+                "`deny_unknown_fields` and `extra` cannot be used together",
+            ));
+        }
+        Ok(container)
+    }
+
+    /// The fields that are stored under their own dictionary key.
+    fn keyed_fields(&self) -> impl Iterator<Item = &Field<'a>> {
+        self.fields
+            .iter()
+            .filter(move |field| !field.skip && Some(field.ident) != self.extra)
+    }
+}
+
+/// Requires every type parameter to implement `bound`.
+fn add_bounds(mut generics: Generics, bound: TokenStream2) -> Generics {
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+fn expand_to_bencode(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if let Data::Enum(data) = &input.data {
+        return expand_enum_to_bencode(&input, data);
+    }
+    let container = Container::parse(&input)?;
+    let name = &input.ident;
+    let generics = add_bounds(input.generics.clone(), quote!(::bencode::ToBencode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let entries = container.keyed_fields().map(|field| {
+        let Field { ident, key, .. } = field;
+        // Spanned at the field so an unsupported type is reported where it is written.
+        quote_spanned! {field.ty.span()=>
+            if !::bencode::ToBencode::is_absent(&self.#ident) {
+                dictionary.insert(
+                    ::std::vec::Vec::<u8>::from(#key),
+                    ::bencode::ToBencode::to_bencode(&self.#ident)?,
+                );
+            }
+        }
+    });
+
+    // Entries from the extra field never override the struct's own fields.
+    let extra = container.extra.map(|extra| {
+        quote! {
+            for (key, value) in &self.#extra {
+                if let ::std::collections::btree_map::Entry::Vacant(entry) = dictionary.entry(
+                    ::std::convert::Into::<::std::vec::Vec<u8>>::into(
+                        ::std::clone::Clone::clone(key),
+                    ),
+                ) {
+                    entry.insert(::bencode::ToBencode::to_bencode(value)?);
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::bencode::ToBencode for #name #ty_generics #where_clause {
+            fn to_bencode(
+                &self,
+            ) -> ::std::result::Result<::bencode::Bencode, ::bencode::ConversionError> {
+                let mut dictionary = ::std::collections::BTreeMap::new();
+                #(#entries)*
+                #extra
+                ::std::result::Result::Ok(::bencode::Bencode::Dictionary(dictionary))
+            }
+        }
+    })
+}
+
+fn expand_from_bencode(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if let Data::Enum(data) = &input.data {
+        return expand_enum_from_bencode(&input, data);
+    }
+    let container = Container::parse(&input)?;
+    let name = &input.ident;
+    let generics = add_bounds(input.generics.clone(), quote!(::bencode::FromBencode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let initializers = container.keyed_fields().map(|field| {
+        let Field { ident, ty, key, .. } = field;
+        let convert =
+            quote_spanned!(ty.span()=> <#ty as ::bencode::FromBencode>::from_bencode(value)?);
+        let absent = if field.default {
+            quote_spanned!(ty.span()=> ::std::default::Default::default())
+        } else {
+            quote_spanned! {ty.span()=>
+                <#ty as ::bencode::FromBencode>::from_absent()
+                    .ok_or(::bencode::ConversionError::MissingField(#key))?
+            }
+        };
+        quote! {
+            #ident: match dictionary.remove(#key.as_bytes()) {
+                ::std::option::Option::Some(value) => #convert,
+                ::std::option::Option::None => #absent,
+            }
+        }
+    });
+    let skipped = container
+        .fields
+        .iter()
+        .filter(|field| field.skip)
+        .map(|field| {
+            let Field { ident, ty, .. } = field;
+            quote_spanned!(ty.span()=> #ident: ::std::default::Default::default())
+        });
+    // Whatever is left in the dictionary once every field has taken its entry is unknown.
+    let extra = container.extra.map(|extra| {
+        quote! {
+            #extra: ::bencode::FromBencode::from_bencode(::bencode::Bencode::Dictionary(
+                ::std::mem::take(&mut dictionary),
+            ))?,
+        }
+    });
+    let deny_unknown_fields = container.deny_unknown_fields.then(|| {
+        quote! {
+            if let ::std::option::Option::Some(key) = dictionary.into_keys().next() {
+                return ::std::result::Result::Err(::bencode::ConversionError::UnknownField(
+                    ::std::string::String::from_utf8_lossy(&key).into_owned(),
+                ));
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::bencode::FromBencode for #name #ty_generics #where_clause {
+            fn from_bencode(
+                value: ::bencode::Bencode,
+            ) -> ::std::result::Result<Self, ::bencode::ConversionError> {
+                let mut dictionary = <::std::collections::BTreeMap<
+                    ::std::vec::Vec<u8>,
+                    ::bencode::Bencode,
+                > as ::bencode::FromBencode>::from_bencode(value)?;
+                let value = #name {
+                    #(#initializers,)*
+                    #(#skipped,)*
+                    #extra
+                };
+                #deny_unknown_fields
+                ::std::result::Result::Ok(value)
+            }
+        }
+    })
+}
+
+fn expand_enum_to_bencode(input: &DeriveInput, data: &DataEnum) -> syn::Result<TokenStream2> {
+    let variants = parse_variants(input, data)?;
+    let name = &input.ident;
+    let generics = add_bounds(input.generics.clone(), quote!(::bencode::ToBencode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let arms = variants.iter().map(|variant| {
+        let Variant {
+            ident, name: key, ..
+        } = variant;
+        match variant.ty {
+            None => quote! {
+                #name::#ident => ::bencode::Bencode::String(::std::vec::Vec::from(#key))
+            },
+            Some(ty) => {
+                let value = quote_spanned!(ty.span()=> ::bencode::ToBencode::to_bencode(value)?);
+                quote! {
+                    #name::#ident(value) => ::bencode::Bencode::Dictionary(
+                        ::std::collections::BTreeMap::from([(::std::vec::Vec::from(#key), #value)]),
+                    )
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::bencode::ToBencode for #name #ty_generics #where_clause {
+            fn to_bencode(
+                &self,
+            ) -> ::std::result::Result<::bencode::Bencode, ::bencode::ConversionError> {
+                ::std::result::Result::Ok(match self {
+                    #(#arms,)*
+                })
+            }
+        }
+    })
+}
+
+fn expand_enum_from_bencode(input: &DeriveInput, data: &DataEnum) -> syn::Result<TokenStream2> {
+    let variants = parse_variants(input, data)?;
+    let name = &input.ident;
+    let generics = add_bounds(input.generics.clone(), quote!(::bencode::FromBencode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let units = variants.iter().filter(|variant| variant.ty.is_none()).map(
+        |Variant {
+             ident, name: key, ..
+         }| { quote!(key if key == #key.as_bytes() => #name::#ident) },
+    );
+    let newtypes = variants.iter().filter_map(|variant| {
+        let Variant {
+            ident, name: key, ..
+        } = variant;
+        let ty = variant.ty?;
+        let convert =
+            quote_spanned!(ty.span()=> <#ty as ::bencode::FromBencode>::from_bencode(value)?);
+        Some(quote!(key if key == #key.as_bytes() => #name::#ident(#convert)))
+    });
+    let unknown = quote! {
+        key => {
+            return ::std::result::Result::Err(::bencode::ConversionError::UnknownVariant(
+                ::std::string::String::from_utf8_lossy(key).into_owned(),
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::bencode::FromBencode for #name #ty_generics #where_clause {
+            fn from_bencode(
+                value: ::bencode::Bencode,
+            ) -> ::std::result::Result<Self, ::bencode::ConversionError> {
+                if let ::bencode::Bencode::String(key) = &value {
+                    return ::std::result::Result::Ok(match key.as_slice() {
+                        #(#units,)*
+                        #unknown
+                    });
+                }
+                let dictionary = <::std::collections::BTreeMap<
+                    ::std::vec::Vec<u8>,
+                    ::bencode::Bencode,
+                > as ::bencode::FromBencode>::from_bencode(value)?;
+                if dictionary.len() != 1 {
+                    return ::std::result::Result::Err(::bencode::ConversionError::UnexpectedType {
+                        expected: "dictionary with one entry",
+                        found: "dictionary",
+                    });
+                }
+                let (key, value) = dictionary.into_iter().next().unwrap();
+                ::std::result::Result::Ok(match key.as_slice() {
+                    #(#newtypes,)*
+                    #unknown
+                })
+            }
+        }
+    })
+}
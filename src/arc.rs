@@ -0,0 +1,172 @@
+//! An immutable [`Bencode`] counterpart whose nodes are shared through [`Arc`].
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::value::Bencode;
+
+/// Any bencode value, immutable and cheap to clone.
+///
+/// Strings, lists and dictionaries are held behind an [`Arc`], so cloning a value or any
+/// part of it never copies the underlying data, and clones can be sent to other threads.
+///
+/// ```
+/// use bencode::{bencode, ArcBencode};
+///
+/// let torrent = ArcBencode::from(bencode!({ "info": { "pieces": "..." } }));
+/// let info = torrent.get("info").unwrap().clone();
+/// std::thread::spawn(move || assert!(info.get("pieces").is_some()))
+///     .join()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArcBencode {
+    Integer(i128),
+    #[cfg(feature = "num-bigint")]
+    BigInteger(num_bigint::BigInt),
+    String(Arc<[u8]>),
+    List(Arc<[ArcBencode]>),
+    Dictionary(Arc<BTreeMap<Vec<u8>, ArcBencode>>),
+}
+
+impl ArcBencode {
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            ArcBencode::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&[u8]> {
+        match self {
+            ArcBencode::String(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[ArcBencode]> {
+        match self {
+            ArcBencode::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&BTreeMap<Vec<u8>, ArcBencode>> {
+        match self {
+            ArcBencode::Dictionary(dictionary) => Some(dictionary),
+            _ => None,
+        }
+    }
+
+    /// Looks up a dictionary entry, returning `None` if this is not a dictionary.
+    pub fn get(&self, key: &str) -> Option<&ArcBencode> {
+        self.as_dictionary()?.get(key.as_bytes())
+    }
+}
+
+impl From<Bencode> for ArcBencode {
+    fn from(value: Bencode) -> Self {
+        match value {
+            Bencode::Integer(integer) => ArcBencode::Integer(integer),
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(integer) => ArcBencode::BigInteger(integer),
+            Bencode::String(bytes) => ArcBencode::String(bytes.into()),
+            Bencode::List(list) => ArcBencode::List(list.into_iter().map(Into::into).collect()),
+            Bencode::Dictionary(dictionary) => ArcBencode::Dictionary(Arc::new(
+                dictionary
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// Copies the shared data into a mutable tree.
+impl From<&ArcBencode> for Bencode {
+    fn from(value: &ArcBencode) -> Self {
+        match value {
+            ArcBencode::Integer(integer) => Bencode::Integer(*integer),
+            #[cfg(feature = "num-bigint")]
+            ArcBencode::BigInteger(integer) => Bencode::BigInteger(integer.clone()),
+            ArcBencode::String(bytes) => Bencode::String(bytes.to_vec()),
+            ArcBencode::List(list) => Bencode::List(list.iter().map(Into::into).collect()),
+            ArcBencode::Dictionary(dictionary) => Bencode::Dictionary(
+                dictionary
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ArcBencode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ArcBencode::Integer(integer) => crate::value::serialize_integer(*integer, serializer),
+            #[cfg(feature = "num-bigint")]
+            ArcBencode::BigInteger(integer) => {
+                crate::value::serialize_big_integer(integer, serializer)
+            }
+            ArcBencode::String(bytes) => crate::value::ByteString(bytes).serialize(serializer),
+            ArcBencode::List(list) => serializer.collect_seq(list.iter()),
+            ArcBencode::Dictionary(dictionary) => serializer.collect_map(
+                dictionary
+                    .iter()
+                    .map(|(key, value)| (crate::value::ByteString(key), value)),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversions_keep_every_value() {
+        let value = bencode!({ "a": [1, -2, "x"], "b": { "c": b"\xff".as_slice() } });
+        let shared = ArcBencode::from(value.clone());
+        assert_eq!(
+            shared.get("a").unwrap().as_list().unwrap()[1].as_integer(),
+            Some(-2)
+        );
+        assert_eq!(
+            shared.get("b").unwrap().get("c").unwrap().as_string(),
+            Some(b"\xff".as_slice())
+        );
+        assert_eq!(Bencode::from(&shared), value);
+    }
+
+    #[test]
+    fn clones_share_their_contents() {
+        let value = ArcBencode::from(bencode!({ "info": { "pieces": "abcd" } }));
+        let info = value.get("info").unwrap().clone();
+        let (ArcBencode::Dictionary(original), ArcBencode::Dictionary(clone)) =
+            (value.get("info").unwrap(), &info)
+        else {
+            panic!("info is a dictionary");
+        };
+        assert!(Arc::ptr_eq(original, clone));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializing_writes_the_canonical_encoding() {
+        let value = ArcBencode::from(bencode!({ "b": [1, "xy"], "a": "" }));
+        assert_eq!(crate::to_vec(&value).unwrap(), b"d1:a0:1:bli1e2:xyee");
+    }
+
+    #[cfg(all(feature = "serde", feature = "num-bigint"))]
+    #[test]
+    fn big_integers_are_serialized_in_full() {
+        let big = num_bigint::BigInt::from(u128::MAX) * 10u8;
+        let value = ArcBencode::from(Bencode::List(vec![Bencode::BigInteger(big.clone())]));
+        assert_eq!(
+            crate::to_vec(&value).unwrap(),
+            format!("li{big}ee").into_bytes()
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! The buffer behind the encoders for `futures-io` and Tokio writers.
+
+use std::future;
+use std::io;
+use std::task::{Context, Poll};
+
+use crate::value::Bencode;
+
+/// The encoding of the last value, kept until all of it has been handed to the writer.
+///
+/// The two writer traits differ only in name, so each encoder passes its writer's
+/// `poll_write` in as a closure.
+#[derive(Default)]
+pub(crate) struct WriteBuffer {
+    bytes: Vec<u8>,
+    /// How much of `bytes` has been written.
+    written: usize,
+}
+
+impl WriteBuffer {
+    /// Replaces what is buffered with the canonical encoding of `value`, so whatever was
+    /// left of the last value must have been written first.
+    pub(crate) fn fill(&mut self, value: &Bencode) -> io::Result<()> {
+        self.bytes.clear();
+        self.written = 0;
+        value.write_canonical(&mut self.bytes)
+    }
+
+    /// The encoded bytes not yet handed to the writer.
+    pub(crate) fn buffered(&self) -> &[u8] {
+        &self.bytes[self.written..]
+    }
+
+    /// Hands what is buffered to `poll_write` until all of it has been taken.
+    pub(crate) async fn write_all(
+        &mut self,
+        mut poll_write: impl FnMut(&mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+    ) -> io::Result<()> {
+        future::poll_fn(|cx| {
+            while self.written < self.bytes.len() {
+                match poll_write(cx, &self.bytes[self.written..]) {
+                    Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                    Poll::Ready(Ok(written)) => self.written += written,
+                    Poll::Ready(Err(error)) if error.kind() == io::ErrorKind::Interrupted => {}
+                    Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            Poll::Ready(Ok(()))
+        })
+        .await
+    }
+}
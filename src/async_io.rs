@@ -0,0 +1,242 @@
+//! Decoding from asynchronous readers and encoding to asynchronous writers, behind the
+//! `futures` feature.
+
+use std::future;
+use std::io;
+use std::pin::Pin;
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use crate::async_buffer::WriteBuffer;
+use crate::decoder::{DecoderError, DecoderOptions, DecoderResult};
+use crate::path::Path;
+use crate::push::{Progress, PushDecoder};
+use crate::value::Bencode;
+
+/// Decodes [`Bencode`] values from a [`futures_io::AsyncRead`], such as an async-std
+/// or smol socket.
+///
+/// Input is read into a buffer of [`DecoderOptions::buffer_size`] bytes and handed to a
+/// [`PushDecoder`], so waiting for more of a value never blocks a thread, and bytes read
+/// past one value are kept for the next.
+///
+/// ```
+/// use bencode::{bencode, AsyncDecoder};
+/// use futures::executor::block_on;
+/// use futures::io::Cursor;
+///
+/// let mut decoder = AsyncDecoder::new(Cursor::new(b"d1:y1:qed1:y1:re"));
+/// block_on(async {
+///     assert_eq!(decoder.decode().await.unwrap(), bencode!({ "y": "q" }));
+///     assert_eq!(decoder.decode().await.unwrap(), bencode!({ "y": "r" }));
+///     assert!(decoder.decode().await.is_err());
+/// });
+/// ```
+pub struct AsyncDecoder<R> {
+    reader: R,
+    push: PushDecoder,
+    buffer: Box<[u8]>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        AsyncDecoder::with_options(reader, DecoderOptions::default())
+    }
+
+    /// Creates a decoder with the given options, which like a [`PushDecoder`] never
+    /// decodes leniently.
+    pub fn with_options(reader: R, options: DecoderOptions) -> Self {
+        AsyncDecoder {
+            reader,
+            push: PushDecoder::with_options(options),
+            buffer: vec![0; options.buffer_size.max(1)].into_boxed_slice(),
+        }
+    }
+
+    /// Decodes the next value, reading until all of it has arrived.
+    ///
+    /// Input that ends before a value starts is reported as
+    /// [`DecoderError::UnexpectedEof`], as it is by [`Decoder`](crate::Decoder).
+    pub async fn decode(&mut self) -> DecoderResult<Bencode> {
+        let mut progress = self.push.poll()?;
+        loop {
+            if let Progress::Value(value) = progress {
+                return Ok(value);
+            }
+            let read =
+                future::poll_fn(|cx| Pin::new(&mut self.reader).poll_read(cx, &mut self.buffer))
+                    .await
+                    .map_err(|source| DecoderError::IO {
+                        offset: self.push.bytes_fed(),
+                        path: Path::root(),
+                        source,
+                    })?;
+            if read == 0 {
+                return Err(self.push.end_of_input());
+            }
+            progress = self.push.feed(&self.buffer[..read])?;
+        }
+    }
+
+    /// The bytes read from the reader but not yet returned as part of a value.
+    pub fn buffered(&self) -> &[u8] {
+        self.push.buffered()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the reader, which carries on from where [`buffered`](AsyncDecoder::buffered)
+    /// ends.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Encodes [`Bencode`] values to a [`futures_io::AsyncWrite`], such as an async-std or
+/// smol socket.
+///
+/// Each value is encoded in canonical form into a buffer and then written out, however
+/// few bytes the writer takes at a time. If an [`encode`](AsyncEncoder::encode) is
+/// cancelled partway through, what is left of the value is written ahead of the next
+/// one, or by [`flush`](AsyncEncoder::flush), so the output is never cut short.
+///
+/// ```
+/// use bencode::{bencode, AsyncEncoder};
+/// use futures::executor::block_on;
+///
+/// let mut encoder = AsyncEncoder::new(Vec::new());
+/// block_on(async {
+///     encoder.encode(&bencode!({ "y": "q" })).await.unwrap();
+///     encoder.encode(&bencode!({ "y": "r" })).await.unwrap();
+/// });
+/// assert_eq!(encoder.into_inner(), b"d1:y1:qed1:y1:re");
+/// ```
+pub struct AsyncEncoder<W> {
+    writer: W,
+    buffer: WriteBuffer,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        AsyncEncoder {
+            writer,
+            buffer: WriteBuffer::default(),
+        }
+    }
+
+    /// Writes `value`, returning once all of it has been handed to the writer.
+    pub async fn encode(&mut self, value: &Bencode) -> io::Result<()> {
+        self.write_buffered().await?;
+        self.buffer.fill(value)?;
+        self.write_buffered().await
+    }
+
+    /// Writes whatever is left of the last value and flushes the writer.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.write_buffered().await?;
+        future::poll_fn(|cx| Pin::new(&mut self.writer).poll_flush(cx)).await
+    }
+
+    async fn write_buffered(&mut self) -> io::Result<()> {
+        let writer = &mut self.writer;
+        self.buffer
+            .write_all(|cx, bytes| Pin::new(&mut *writer).poll_write(cx, bytes))
+            .await
+    }
+
+    /// The encoded bytes not yet handed to the writer.
+    pub fn buffered(&self) -> &[u8] {
+        self.buffer.buffered()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the writer, which has not been sent what is still
+    /// [`buffered`](AsyncEncoder::buffered).
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn values_split_across_reads() {
+        let options = DecoderOptions {
+            buffer_size: 3,
+            ..DecoderOptions::default()
+        };
+        let mut decoder = AsyncDecoder::with_options(Cursor::new(b"li1ei22eei3ed1:y"), options);
+        block_on(async {
+            assert_eq!(decoder.decode().await.unwrap(), bencode!([1, 22]));
+            assert_eq!(decoder.decode().await.unwrap(), bencode!(3));
+            assert!(matches!(
+                decoder.decode().await,
+                Err(DecoderError::UnexpectedEof { offset: 16, .. })
+            ));
+        });
+    }
+
+    /// Takes at most two bytes per write, and turns every other write away.
+    #[derive(Default)]
+    struct Trickle {
+        written: Vec<u8>,
+        ready: bool,
+    }
+
+    impl AsyncWrite for Trickle {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bytes: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let length = bytes.len().min(2);
+            self.written.extend_from_slice(&bytes[..length]);
+            Poll::Ready(Ok(length))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn values_written_in_pieces() {
+        let mut encoder = AsyncEncoder::new(Trickle::default());
+        block_on(async {
+            encoder.encode(&bencode!({ "y": "q" })).await.unwrap();
+            encoder.encode(&bencode!([1, "abc"])).await.unwrap();
+            encoder.flush().await.unwrap();
+        });
+        assert!(encoder.buffered().is_empty());
+        assert_eq!(encoder.into_inner().written, b"d1:y1:qeli1e3:abce");
+    }
+}
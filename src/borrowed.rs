@@ -0,0 +1,264 @@
+//! A [`Bencode`] counterpart that borrows its strings from the input it was decoded from.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::{self, Deserialize, MapAccess, SeqAccess, Unexpected, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::de::Deserializer;
+use crate::error::Result;
+use crate::value::{serialize_integer, Bencode, ByteString};
+
+/// Any bencode value, with strings and dictionary keys borrowed from the input.
+///
+/// Decoding one with [`decode`](BencodeRef::decode) allocates only for the lists and
+/// dictionaries themselves, never for a string, which suits documents already held in
+/// memory such as DHT packets.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BencodeRef<'a> {
+    Integer(i128),
+    String(&'a [u8]),
+    List(Vec<BencodeRef<'a>>),
+    Dictionary(BTreeMap<&'a [u8], BencodeRef<'a>>),
+}
+
+impl<'a> BencodeRef<'a> {
+    /// Decodes a single value from `input`, borrowing every string from it.
+    ///
+    /// Lists and dictionaries may nest no more deeply than [`DecoderOptions::max_depth`]
+    /// allows by default, so untrusted input cannot exhaust the stack.
+    ///
+    /// [`DecoderOptions::max_depth`]: crate::DecoderOptions::max_depth
+    ///
+    /// ```
+    /// use bencode::BencodeRef;
+    ///
+    /// let input = b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe";
+    /// let query = BencodeRef::decode(input).unwrap();
+    /// assert_eq!(query.get("q").and_then(BencodeRef::as_str), Some("ping"));
+    /// let id = query.get("a").and_then(|a| a.get("id")).unwrap();
+    /// assert_eq!(id.as_string().unwrap().as_ptr(), input[12..].as_ptr());
+    /// ```
+    pub fn decode(input: &'a [u8]) -> Result<Self> {
+        let mut deserializer = Deserializer::from_slice(input);
+        let value = BencodeRef::deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(value)
+    }
+
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            BencodeRef::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&'a [u8]> {
+        match self {
+            BencodeRef::String(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// The contents of a string, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.as_string()?).ok()
+    }
+
+    pub fn as_list(&self) -> Option<&[BencodeRef<'a>]> {
+        match self {
+            BencodeRef::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&BTreeMap<&'a [u8], BencodeRef<'a>>> {
+        match self {
+            BencodeRef::Dictionary(dictionary) => Some(dictionary),
+            _ => None,
+        }
+    }
+
+    /// Looks up a dictionary entry, returning `None` if this is not a dictionary.
+    pub fn get(&self, key: &str) -> Option<&BencodeRef<'a>> {
+        self.as_dictionary()?.get(key.as_bytes())
+    }
+}
+
+/// Copies the borrowed strings into an owned tree.
+impl From<&BencodeRef<'_>> for Bencode {
+    fn from(value: &BencodeRef<'_>) -> Self {
+        match value {
+            BencodeRef::Integer(integer) => Bencode::Integer(*integer),
+            BencodeRef::String(bytes) => Bencode::String(bytes.to_vec()),
+            BencodeRef::List(list) => Bencode::List(list.iter().map(Into::into).collect()),
+            BencodeRef::Dictionary(dictionary) => Bencode::Dictionary(
+                dictionary
+                    .iter()
+                    .map(|(key, value)| (key.to_vec(), value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for BencodeRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            BencodeRef::Integer(integer) => serialize_integer(*integer, serializer),
+            BencodeRef::String(bytes) => serializer.serialize_bytes(bytes),
+            BencodeRef::List(list) => serializer.collect_seq(list),
+            BencodeRef::Dictionary(dictionary) => serializer.collect_map(
+                dictionary
+                    .iter()
+                    .map(|(key, value)| (ByteString(key), value)),
+            ),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BencodeRef<'de> {
+    fn deserialize<D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(BencodeRefVisitor)
+    }
+}
+
+struct BencodeRefVisitor;
+
+impl<'de> Visitor<'de> for BencodeRefVisitor {
+    type Value = BencodeRef<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an integer, borrowed string, list or dictionary")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<BencodeRef<'de>, E> {
+        Ok(BencodeRef::Integer(v.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<BencodeRef<'de>, E> {
+        Ok(BencodeRef::Integer(v.into()))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> std::result::Result<BencodeRef<'de>, E> {
+        Ok(BencodeRef::Integer(v))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> std::result::Result<BencodeRef<'de>, E> {
+        i128::try_from(v)
+            .map(BencodeRef::Integer)
+            .map_err(|_| E::custom(format_args!("integer {v} is out of range")))
+    }
+
+    fn visit_borrowed_str<E: de::Error>(
+        self,
+        v: &'de str,
+    ) -> std::result::Result<BencodeRef<'de>, E> {
+        Ok(BencodeRef::String(v.as_bytes()))
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(
+        self,
+        v: &'de [u8],
+    ) -> std::result::Result<BencodeRef<'de>, E> {
+        Ok(BencodeRef::String(v))
+    }
+
+    // A string that only lives in the deserializer's scratch space cannot be borrowed.
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<BencodeRef<'de>, E> {
+        Err(E::invalid_type(Unexpected::Str(v), &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<BencodeRef<'de>, E> {
+        Err(E::invalid_type(Unexpected::Bytes(v), &self))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<BencodeRef<'de>, A::Error> {
+        let mut list = Vec::new();
+        while let Some(element) = seq.next_element()? {
+            list.push(element);
+        }
+        Ok(BencodeRef::List(list))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(
+        self,
+        mut map: A,
+    ) -> std::result::Result<BencodeRef<'de>, A::Error> {
+        let mut dictionary = BTreeMap::new();
+        while let Some(key) = map.next_key()? {
+            let BencodeRef::String(key) = key else {
+                return Err(de::Error::invalid_type(
+                    Unexpected::Other("non-string key"),
+                    &"a string",
+                ));
+            };
+            dictionary.insert(key, map.next_value()?);
+        }
+        Ok(BencodeRef::Dictionary(dictionary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::value::{Error as ValueError, MapDeserializer};
+
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn strings_and_keys_are_borrowed_from_the_input() {
+        let input = b"d3:keyl5:valuei-3ee4:spam4:eggse";
+        let value = BencodeRef::decode(input).unwrap();
+        let dictionary = value.as_dictionary().unwrap();
+        let range = input.as_ptr_range();
+        for key in dictionary.keys() {
+            assert!(range.contains(&key.as_ptr()));
+        }
+        let list = value.get("key").and_then(BencodeRef::as_list).unwrap();
+        assert!(range.contains(&list[0].as_string().unwrap().as_ptr()));
+        assert_eq!(list[1], BencodeRef::Integer(-3));
+        assert_eq!(value.get("spam").and_then(BencodeRef::as_str), Some("eggs"));
+        assert_eq!(
+            Bencode::from(&value),
+            bencode!({ "key": ["value", -3], "spam": "eggs" })
+        );
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        assert!(matches!(
+            BencodeRef::decode(b"i1ei2e"),
+            Err(Error::UnexpectedByte(b'i'))
+        ));
+        assert!(matches!(
+            BencodeRef::decode(b"4:spam "),
+            Err(Error::UnexpectedByte(b' '))
+        ));
+    }
+
+    #[test]
+    fn keys_must_be_strings() {
+        assert!(matches!(
+            BencodeRef::decode(b"di1ei2ee"),
+            Err(Error::KeyMustBeAString)
+        ));
+        // Formats other than bencode can offer other kinds of keys.
+        let entries = MapDeserializer::<_, ValueError>::new([(1i64, 2i64)].into_iter());
+        assert!(BencodeRef::deserialize(entries).is_err());
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        assert!(matches!(
+            BencodeRef::decode(&vec![b'l'; 1_000_000]),
+            Err(Error::DepthLimitExceeded { .. })
+        ));
+    }
+}
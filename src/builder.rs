@@ -0,0 +1,73 @@
+//! Fluent builders for lists and dictionaries.
+
+use std::collections::BTreeMap;
+
+use crate::value::Bencode;
+
+/// Builds a [`Bencode::Dictionary`] one entry at a time.
+///
+/// ```
+/// use bencode::Bencode;
+///
+/// let value = Bencode::dict().insert("cow", "moo").insert("count", 3).build();
+/// ```
+#[derive(Debug, Default)]
+pub struct DictBuilder {
+    dictionary: BTreeMap<Vec<u8>, Bencode>,
+}
+
+impl DictBuilder {
+    pub fn new() -> Self {
+        DictBuilder::default()
+    }
+
+    /// Adds an entry, replacing any earlier entry with the same key.
+    pub fn insert(mut self, key: impl Into<Vec<u8>>, value: impl Into<Bencode>) -> Self {
+        self.dictionary.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn build(self) -> Bencode {
+        Bencode::Dictionary(self.dictionary)
+    }
+}
+
+/// Builds a [`Bencode::List`] one element at a time.
+#[derive(Debug, Default)]
+pub struct ListBuilder {
+    list: Vec<Bencode>,
+}
+
+impl ListBuilder {
+    pub fn new() -> Self {
+        ListBuilder::default()
+    }
+
+    pub fn push(mut self, value: impl Into<Bencode>) -> Self {
+        self.list.push(value.into());
+        self
+    }
+
+    pub fn build(self) -> Bencode {
+        Bencode::List(self.list)
+    }
+}
+
+impl From<DictBuilder> for Bencode {
+    fn from(builder: DictBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl From<ListBuilder> for Bencode {
+    fn from(builder: ListBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl Bencode {
+    /// Starts building a dictionary.
+    pub fn dict() -> DictBuilder {
+        DictBuilder::new()
+    }
+}
@@ -0,0 +1,665 @@
+//! Canonical encoding, and normalization of bencode that is not canonical.
+//!
+//! The canonical form of a value has dictionary keys in sorted order with no duplicates,
+//! integers without leading zeros or a negative zero, and string lengths without leading
+//! zeros. Two equal values always have the same canonical encoding.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+
+use crate::decoder::DecoderOptions;
+use crate::path::{Path, Segment};
+use crate::value::Bencode;
+
+impl Bencode {
+    /// Encodes the value in canonical form.
+    ///
+    /// A `Bencode` always holds its dictionary entries sorted and unique, so there is
+    /// nothing to normalize; see [`canonicalize_bytes`] for input that may not be canonical.
+    pub fn canonicalize(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_canonical(&mut bytes)
+            .expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    /// Writes the canonical encoding of the value to `writer`.
+    ///
+    /// Lists and dictionaries are walked with a stack of their own rather than by
+    /// recursion, so however deeply the value nests, the call stack does not grow.
+    pub fn write_canonical<W: Write + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        let mut open: Vec<Open<'_>> = Vec::new();
+        let mut next = Some(self);
+        loop {
+            match next {
+                Some(Bencode::Integer(integer)) => {
+                    write_integer(writer, *integer < 0, integer.unsigned_abs())?
+                }
+                #[cfg(feature = "num-bigint")]
+                Some(Bencode::BigInteger(integer)) => write!(writer, "i{integer}e")?,
+                Some(Bencode::String(bytes)) => write_string(writer, bytes)?,
+                Some(Bencode::List(list)) => {
+                    writer.write_all(b"l")?;
+                    open.push(Open::List(list.iter()));
+                }
+                Some(Bencode::Dictionary(dictionary)) => {
+                    writer.write_all(b"d")?;
+                    open.push(Open::Dictionary(dictionary.iter()));
+                }
+                None => {}
+            }
+            next = match open.last_mut() {
+                None => return Ok(()),
+                Some(Open::List(elements)) => elements.next(),
+                Some(Open::Dictionary(entries)) => match entries.next() {
+                    Some((key, value)) => {
+                        write_string(writer, key)?;
+                        Some(value)
+                    }
+                    None => None,
+                },
+            };
+            if next.is_none() {
+                writer.write_all(b"e")?;
+                open.pop();
+            }
+        }
+    }
+
+    /// The length of the canonical encoding of the value, worked out without encoding it.
+    ///
+    /// This is always the length of what [`write_canonical`](Bencode::write_canonical)
+    /// writes, so it can be used to size buffers, check a message against a size limit,
+    /// or write a length prefix ahead of the value.
+    ///
+    /// ```
+    /// use bencode::bencode;
+    ///
+    /// let value = bencode!({ "t": "aa", "a": { "id": [-120, 0, 10], "v": "0123456789" } });
+    /// assert_eq!(value.encoded_len(), value.canonicalize().len());
+    /// ```
+    pub fn encoded_len(&self) -> usize {
+        let mut length = 0;
+        let mut pending = vec![self];
+        while let Some(value) = pending.pop() {
+            length += match value {
+                Bencode::Integer(integer) => integer_len(*integer),
+                #[cfg(feature = "num-bigint")]
+                Bencode::BigInteger(integer) => big_integer_len(integer),
+                Bencode::String(bytes) => string_len(bytes.len()),
+                Bencode::List(list) => {
+                    pending.extend(list);
+                    2
+                }
+                Bencode::Dictionary(dictionary) => {
+                    pending.extend(dictionary.values());
+                    2 + dictionary
+                        .keys()
+                        .map(|key| string_len(key.len()))
+                        .sum::<usize>()
+                }
+            };
+        }
+        length
+    }
+}
+
+/// A list or dictionary being written by [`Bencode::write_canonical`], with the elements
+/// or entries still to come.
+enum Open<'a> {
+    List(std::slice::Iter<'a, Bencode>),
+    Dictionary(std::collections::btree_map::Iter<'a, Vec<u8>, Bencode>),
+}
+
+/// The number of decimal digits in `number`.
+fn digits(number: u128) -> usize {
+    number.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+/// The length of the encoding of `integer`.
+pub(crate) fn integer_len(integer: i128) -> usize {
+    2 + usize::from(integer < 0) + digits(integer.unsigned_abs())
+}
+
+/// The length of the encoding of `integer`.
+#[cfg(feature = "num-bigint")]
+pub(crate) fn big_integer_len(integer: &num_bigint::BigInt) -> usize {
+    let mut counter = Counter(0);
+    fmt::Write::write_fmt(&mut counter, format_args!("{integer}")).expect("counting cannot fail");
+    2 + counter.0
+}
+
+/// The length of the encoding of a string `len` bytes long.
+pub(crate) fn string_len(len: usize) -> usize {
+    digits(len as u128) + 1 + len
+}
+
+/// Counts the bytes formatted into it.
+#[cfg(feature = "num-bigint")]
+struct Counter(usize);
+
+#[cfg(feature = "num-bigint")]
+impl fmt::Write for Counter {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.0 += string.len();
+        Ok(())
+    }
+}
+
+pub(crate) fn write_string<W: Write + ?Sized>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_length(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+/// Writes an integer, given as its sign and magnitude so that any `i128` or `u128` fits.
+///
+/// Integers and lengths are formatted by hand into a buffer on the stack, since going
+/// through `fmt` costs more than everything else when encoding many small integers.
+pub(crate) fn write_integer<W: Write + ?Sized>(
+    writer: &mut W,
+    negative: bool,
+    magnitude: u128,
+) -> io::Result<()> {
+    writer.write_all(encode_integer(&mut [0; INTEGER_LEN], negative, magnitude))
+}
+
+/// Room for `i`, a sign, the 39 digits of `u128::MAX` and `e`.
+pub(crate) const INTEGER_LEN: usize = 42;
+
+/// Formats the encoding of an integer into the end of `buffer`, returning it.
+pub(crate) fn encode_integer(
+    buffer: &mut [u8; INTEGER_LEN],
+    negative: bool,
+    magnitude: u128,
+) -> &[u8] {
+    let end = buffer.len() - 1;
+    buffer[end] = b'e';
+    let mut start = format_digits(&mut buffer[..end], magnitude);
+    if negative {
+        start -= 1;
+        buffer[start] = b'-';
+    }
+    start -= 1;
+    buffer[start] = b'i';
+    &buffer[start..]
+}
+
+/// Writes the length header of a string, up to and including the `:`.
+pub(crate) fn write_length<W: Write + ?Sized>(writer: &mut W, length: u64) -> io::Result<()> {
+    writer.write_all(encode_length(&mut [0; LENGTH_LEN], length))
+}
+
+/// Room for the 20 digits of `u64::MAX` and `:`.
+pub(crate) const LENGTH_LEN: usize = 21;
+
+/// Formats the length header of a string into the end of `buffer`, returning it.
+pub(crate) fn encode_length(buffer: &mut [u8; LENGTH_LEN], length: u64) -> &[u8] {
+    let end = buffer.len() - 1;
+    buffer[end] = b':';
+    let start = format_digits(&mut buffer[..end], u128::from(length));
+    &buffer[start..]
+}
+
+const DIGIT_PAIRS: &[u8; 200] = b"\
+    0001020304050607080910111213141516171819\
+    2021222324252627282930313233343536373839\
+    4041424344454647484950515253545556575859\
+    6061626364656667686970717273747576777879\
+    8081828384858687888990919293949596979899";
+
+/// Writes the decimal digits of `number` to the end of `buffer`, returning where they
+/// start.
+fn format_digits(buffer: &mut [u8], number: u128) -> usize {
+    let mut end = buffer.len();
+    // Dividing a `u128` is slow, so it is only done to split off 19 digits at a time
+    // until what is left fits in a `u64`.
+    let mut wide = number;
+    let mut number = loop {
+        if let Ok(number) = u64::try_from(wide) {
+            break number;
+        }
+        const TEN_TO_THE_19: u128 = 10_000_000_000_000_000_000;
+        let mut low = (wide % TEN_TO_THE_19) as u64;
+        wide /= TEN_TO_THE_19;
+        for _ in 0..19 {
+            end -= 1;
+            buffer[end] = b'0' + (low % 10) as u8;
+            low /= 10;
+        }
+    };
+    while number >= 100 {
+        let pair = (number % 100) as usize * 2;
+        number /= 100;
+        end -= 2;
+        buffer[end..end + 2].copy_from_slice(&DIGIT_PAIRS[pair..pair + 2]);
+    }
+    if number >= 10 {
+        let pair = number as usize * 2;
+        end -= 2;
+        buffer[end..end + 2].copy_from_slice(&DIGIT_PAIRS[pair..pair + 2]);
+    } else {
+        end -= 1;
+        buffer[end] = b'0' + number as u8;
+    }
+    end
+}
+
+/// Something [`canonicalize_bytes`] had to change to make its input canonical.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fix {
+    /// The keys of the dictionary at this path were not in sorted order.
+    UnsortedKeys(Path),
+    /// The key at the end of this path appeared more than once; the last value was kept.
+    DuplicateKey(Path),
+    /// The integer at this path had leading zeros or was a negative zero.
+    NonMinimalInteger(Path),
+    /// The length of the string at this path, or of a key of the dictionary at this path,
+    /// had leading zeros.
+    NonMinimalLength(Path),
+}
+
+/// The result of [`canonicalize_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Canonicalized {
+    /// The canonical encoding of the input.
+    pub bytes: Vec<u8>,
+    /// Everything that had to change, in the order it was found.
+    pub fixes: Vec<Fix>,
+}
+
+impl Canonicalized {
+    /// Whether the input was already canonical.
+    pub fn was_canonical(&self) -> bool {
+        self.fixes.is_empty()
+    }
+}
+
+/// The reasons [`canonicalize_bytes`] can fail: the input is not bencode at all, or
+/// nests too deeply to be safely handled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanonicalizeError {
+    Eof,
+    UnexpectedByte {
+        offset: usize,
+        byte: u8,
+    },
+    IntegerOutOfRange {
+        offset: usize,
+    },
+    /// A string length too large to represent, at the offset of its first digit.
+    LengthOutOfRange {
+        offset: usize,
+    },
+    /// Lists and dictionaries nested more deeply than the default
+    /// [`DecoderOptions::max_depth`].
+    DepthLimitExceeded {
+        limit: usize,
+        offset: usize,
+    },
+    TrailingBytes {
+        offset: usize,
+    },
+}
+
+impl Display for CanonicalizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanonicalizeError::Eof => f.write_str("unexpected end of input"),
+            CanonicalizeError::UnexpectedByte { offset, byte } => {
+                write!(f, "unexpected byte {:?} at offset {offset}", *byte as char)
+            }
+            CanonicalizeError::IntegerOutOfRange { offset } => {
+                write!(f, "integer at offset {offset} is out of range")
+            }
+            CanonicalizeError::LengthOutOfRange { offset } => {
+                write!(f, "string length at offset {offset} is out of range")
+            }
+            CanonicalizeError::DepthLimitExceeded { limit, offset } => {
+                write!(f, "nesting deeper than {limit} levels at offset {offset}")
+            }
+            CanonicalizeError::TrailingBytes { offset } => {
+                write!(f, "trailing bytes after the value, at offset {offset}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CanonicalizeError {}
+
+/// Re-encodes a single value in canonical form, reporting everything that changed.
+///
+/// Non-canonical input is accepted as long as it is otherwise well formed. Lists and
+/// dictionaries may nest as deeply as the default [`DecoderOptions::max_depth`] allows,
+/// and are parsed without recursion, so untrusted input cannot exhaust the call stack.
+///
+/// ```
+/// use bencode::canonical::{canonicalize_bytes, Fix};
+/// use bencode::{Path, Segment};
+///
+/// let canonical = canonicalize_bytes(b"d1:bi03e1:a0:e").unwrap();
+/// assert_eq!(canonical.bytes, b"d1:a0:1:bi3ee");
+/// assert_eq!(
+///     canonical.fixes,
+///     [
+///         Fix::NonMinimalInteger(Path::root().join(Segment::Key(b"b".to_vec()))),
+///         Fix::UnsortedKeys(Path::root()),
+///     ],
+/// );
+/// ```
+pub fn canonicalize_bytes(input: &[u8]) -> Result<Canonicalized, CanonicalizeError> {
+    let mut parser = Parser {
+        input,
+        index: 0,
+        path: Path::root(),
+        fixes: Vec::new(),
+        max_depth: DecoderOptions::default().max_depth,
+    };
+    let value = parser.parse_value()?;
+    if parser.index < input.len() {
+        return Err(CanonicalizeError::TrailingBytes {
+            offset: parser.index,
+        });
+    }
+    Ok(Canonicalized {
+        bytes: value.canonicalize(),
+        fixes: parser.fixes,
+    })
+}
+
+/// A lenient parser that notes every departure from canonical form.
+struct Parser<'a> {
+    input: &'a [u8],
+    index: usize,
+    path: Path,
+    fixes: Vec<Fix>,
+    max_depth: usize,
+}
+
+/// A list or dictionary being parsed by [`Parser::parse_value`], with what has been read
+/// of it so far.
+enum Container {
+    List(Vec<Bencode>),
+    Dictionary {
+        entries: BTreeMap<Vec<u8>, Bencode>,
+        /// The key of the entry whose value is being parsed.
+        key: Vec<u8>,
+        previous: Option<Vec<u8>>,
+        sorted: bool,
+    },
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Result<u8, CanonicalizeError> {
+        self.input
+            .get(self.index)
+            .copied()
+            .ok_or(CanonicalizeError::Eof)
+    }
+
+    fn next(&mut self) -> Result<u8, CanonicalizeError> {
+        let byte = self.peek()?;
+        self.index += 1;
+        Ok(byte)
+    }
+
+    fn unexpected(&self, offset: usize) -> CanonicalizeError {
+        match self.input.get(offset) {
+            Some(&byte) => CanonicalizeError::UnexpectedByte { offset, byte },
+            None => CanonicalizeError::Eof,
+        }
+    }
+
+    /// Reads a run of digits, returning them along with whether they had leading zeros.
+    fn digits(&mut self) -> Result<(&[u8], bool), CanonicalizeError> {
+        let start = self.index;
+        while self.peek()?.is_ascii_digit() {
+            self.index += 1;
+        }
+        let digits = &self.input[start..self.index];
+        if digits.is_empty() {
+            return Err(self.unexpected(start));
+        }
+        Ok((digits, digits.len() > 1 && digits[0] == b'0'))
+    }
+
+    /// Parses one value, keeping the lists and dictionaries it is inside of on a stack of
+    /// their own rather than recursing.
+    fn parse_value(&mut self) -> Result<Bencode, CanonicalizeError> {
+        let mut open: Vec<Container> = Vec::new();
+        loop {
+            let mut finished = match self.peek()? {
+                b'i' => Some(self.parse_integer()?),
+                b'0'..=b'9' => Some(Bencode::String(self.parse_string()?)),
+                byte @ (b'l' | b'd') => {
+                    if open.len() >= self.max_depth {
+                        return Err(CanonicalizeError::DepthLimitExceeded {
+                            limit: self.max_depth,
+                            offset: self.index,
+                        });
+                    }
+                    self.next()?;
+                    open.push(if byte == b'l' {
+                        Container::List(Vec::new())
+                    } else {
+                        Container::Dictionary {
+                            entries: BTreeMap::new(),
+                            key: Vec::new(),
+                            previous: None,
+                            sorted: true,
+                        }
+                    });
+                    None
+                }
+                _ => return Err(self.unexpected(self.index)),
+            };
+            // Hand the finished value to the container it is in, then either close that
+            // container, which finishes it in turn, or move on to its next element.
+            loop {
+                let Some(container) = open.last_mut() else {
+                    return Ok(finished.expect("a value is finished once nothing is open"));
+                };
+                if let Some(value) = finished.take() {
+                    self.add(container, value);
+                }
+                if self.peek()? != b'e' {
+                    self.begin_element(container)?;
+                    break;
+                }
+                self.next()?;
+                let container = open.pop().expect("a container is open");
+                finished = Some(self.close(container));
+            }
+        }
+    }
+
+    /// Reads up to the value of the next element of `container`: nothing for a list, and
+    /// the key for a dictionary.
+    fn begin_element(&mut self, container: &mut Container) -> Result<(), CanonicalizeError> {
+        match container {
+            Container::List(list) => self.path.push(Segment::Index(list.len())),
+            Container::Dictionary {
+                key,
+                previous,
+                sorted,
+                ..
+            } => {
+                let offset = self.index;
+                if !self.peek()?.is_ascii_digit() {
+                    return Err(self.unexpected(offset));
+                }
+                *key = self.parse_string()?;
+                if previous.as_ref().is_some_and(|previous| previous > key) {
+                    *sorted = false;
+                }
+                self.path.push(Segment::Key(key.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, container: &mut Container, value: Bencode) {
+        match container {
+            Container::List(list) => list.push(value),
+            Container::Dictionary {
+                entries,
+                key,
+                previous,
+                ..
+            } => {
+                if entries.contains_key(key) {
+                    self.fixes.push(Fix::DuplicateKey(self.path.clone()));
+                }
+                entries.insert(key.clone(), value);
+                *previous = Some(std::mem::take(key));
+            }
+        }
+        self.path.pop();
+    }
+
+    fn close(&mut self, container: Container) -> Bencode {
+        match container {
+            Container::List(list) => Bencode::List(list),
+            Container::Dictionary {
+                entries, sorted, ..
+            } => {
+                if !sorted {
+                    self.fixes.push(Fix::UnsortedKeys(self.path.clone()));
+                }
+                Bencode::Dictionary(entries)
+            }
+        }
+    }
+
+    fn parse_integer(&mut self) -> Result<Bencode, CanonicalizeError> {
+        #[cfg_attr(feature = "num-bigint", allow(unused_variables))]
+        let start = self.index;
+        self.next()?;
+        let negative = self.peek()? == b'-';
+        if negative {
+            self.next()?;
+        }
+        #[cfg(feature = "num-bigint")]
+        let digits_start = self.index;
+        let (digits, leading_zeros) = self.digits()?;
+        let is_zero = digits.iter().all(|&digit| digit == b'0');
+        let text = std::str::from_utf8(digits).unwrap();
+        let magnitude = text.parse::<u128>().ok();
+        let end = self.index;
+        if self.next()? != b'e' {
+            return Err(self.unexpected(end));
+        }
+        let integer = magnitude.and_then(|magnitude| {
+            if negative {
+                0i128.checked_sub_unsigned(magnitude)
+            } else {
+                i128::try_from(magnitude).ok()
+            }
+        });
+        if leading_zeros || (negative && is_zero) {
+            self.fixes.push(Fix::NonMinimalInteger(self.path.clone()));
+        }
+        match integer {
+            Some(integer) => Ok(Bencode::Integer(integer)),
+            #[cfg(feature = "num-bigint")]
+            None => {
+                let magnitude =
+                    num_bigint::BigInt::parse_bytes(&self.input[digits_start..end], 10).unwrap();
+                Ok(Bencode::BigInteger(if negative {
+                    -magnitude
+                } else {
+                    magnitude
+                }))
+            }
+            #[cfg(not(feature = "num-bigint"))]
+            None => Err(CanonicalizeError::IntegerOutOfRange { offset: start }),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<Vec<u8>, CanonicalizeError> {
+        let start = self.index;
+        let (digits, leading_zeros) = self.digits()?;
+        let length = std::str::from_utf8(digits)
+            .unwrap()
+            .parse::<usize>()
+            .map_err(|_| CanonicalizeError::LengthOutOfRange { offset: start })?;
+        if leading_zeros {
+            self.fixes.push(Fix::NonMinimalLength(self.path.clone()));
+        }
+        let colon = self.index;
+        if self.next()? != b':' {
+            return Err(self.unexpected(colon));
+        }
+        let bytes = self
+            .input
+            .get(self.index..)
+            .and_then(|rest| rest.get(..length))
+            .ok_or(CanonicalizeError::Eof)?;
+        self.index += length;
+        Ok(bytes.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &[u8]) -> Segment {
+        Segment::Key(key.to_vec())
+    }
+
+    #[test]
+    fn every_fix_is_reported_where_it_was_found() {
+        let canonical = canonicalize_bytes(b"d1:bli-0e02:xye1:ad1:yi1e1:xi2e1:xi3eee").unwrap();
+        assert_eq!(canonical.bytes, b"d1:ad1:xi3e1:yi1ee1:bli0e2:xyee");
+        let a = Path::root().join(key(b"a"));
+        let b = Path::root().join(key(b"b"));
+        assert_eq!(
+            canonical.fixes,
+            [
+                Fix::NonMinimalInteger(b.join(Segment::Index(0))),
+                Fix::NonMinimalLength(b.join(Segment::Index(1))),
+                Fix::DuplicateKey(a.join(key(b"x"))),
+                Fix::UnsortedKeys(a),
+                Fix::UnsortedKeys(Path::root()),
+            ]
+        );
+        assert!(!canonical.was_canonical());
+    }
+
+    #[test]
+    fn canonical_input_is_unchanged() {
+        let input = b"d1:ali1ei-2ee1:bd1:c0:ee";
+        let canonical = canonicalize_bytes(input).unwrap();
+        assert_eq!(canonical.bytes, input);
+        assert!(canonical.was_canonical());
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_rather_than_a_stack_overflow() {
+        let depth = 1_000_000;
+        let mut input = vec![b'l'; depth];
+        input.extend(std::iter::repeat_n(b'e', depth));
+        let limit = DecoderOptions::default().max_depth;
+        assert_eq!(
+            canonicalize_bytes(&input),
+            Err(CanonicalizeError::DepthLimitExceeded {
+                limit,
+                offset: limit
+            })
+        );
+
+        let mut input = vec![b'l'; limit];
+        input.extend(std::iter::repeat_n(b'e', limit));
+        assert_eq!(canonicalize_bytes(&input).unwrap().bytes, input);
+    }
+
+    #[test]
+    fn length_overflow_has_its_own_error() {
+        assert_eq!(
+            canonicalize_bytes(b"l99999999999999999999999:xe"),
+            Err(CanonicalizeError::LengthOutOfRange { offset: 1 })
+        );
+        assert_eq!(canonicalize_bytes(b"5:abc"), Err(CanonicalizeError::Eof));
+    }
+}
@@ -0,0 +1,247 @@
+//! A [`tokio_util::codec`] codec for streams of bencode messages, and an encoder for
+//! Tokio writers, behind the `tokio` feature.
+
+use std::future;
+use std::io::{self, Write};
+use std::pin::Pin;
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio::io::AsyncWrite;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::async_buffer::WriteBuffer;
+use crate::decoder::{DecoderError, DecoderOptions};
+use crate::push::Framer;
+use crate::value::Bencode;
+
+/// Frames a byte stream as a sequence of [`Bencode`] values, so that a
+/// `Framed<TcpStream, BencodeCodec>` is a stream and sink of messages.
+///
+/// Each value is decoded once all of it has arrived, as by a
+/// [`PushDecoder`](crate::PushDecoder), and encoded in canonical form. Decoding errors,
+/// whose offsets count from the start of the stream, are returned as
+/// [`io::ErrorKind::InvalidData`] errors wrapping the [`DecoderError`]. Set limits with
+/// [`with_options`](BencodeCodec::with_options) to bound how much of a value a peer can
+/// make the codec buffer.
+///
+/// ```
+/// use bencode::{bencode, BencodeCodec};
+/// use bytes::BytesMut;
+/// use tokio_util::codec::{Decoder, Encoder};
+///
+/// let mut codec = BencodeCodec::new();
+/// let mut buffer = BytesMut::new();
+/// codec.encode(bencode!({ "y": "q" }), &mut buffer).unwrap();
+/// buffer.extend_from_slice(b"d1:y");
+/// assert_eq!(codec.decode(&mut buffer).unwrap(), Some(bencode!({ "y": "q" })));
+/// assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+/// ```
+#[derive(Debug, Default)]
+pub struct BencodeCodec {
+    framer: Framer,
+}
+
+impl BencodeCodec {
+    pub fn new() -> Self {
+        BencodeCodec::default()
+    }
+
+    /// Creates a codec that decodes with the given options, except that it never
+    /// decodes leniently.
+    pub fn with_options(options: DecoderOptions) -> Self {
+        BencodeCodec {
+            framer: Framer::new(options),
+        }
+    }
+}
+
+fn invalid_data(error: DecoderError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+impl Decoder for BencodeCodec {
+    type Item = Bencode;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Bencode>> {
+        match self.framer.next(src).map_err(invalid_data)? {
+            Some((value, length)) => {
+                src.advance(length);
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> io::Result<Option<Bencode>> {
+        match self.decode(src)? {
+            None if !src.is_empty() => Err(invalid_data(self.framer.end_of_input(src))),
+            item => Ok(item),
+        }
+    }
+}
+
+impl Encoder<Bencode> for BencodeCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bencode, dst: &mut BytesMut) -> io::Result<()> {
+        self.encode(&item, dst)
+    }
+}
+
+impl Encoder<&Bencode> for BencodeCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: &Bencode, dst: &mut BytesMut) -> io::Result<()> {
+        let mut writer = dst.writer();
+        item.write_canonical(&mut writer)?;
+        writer.flush()
+    }
+}
+
+/// Encodes [`Bencode`] values in canonical form to a [`tokio::io::AsyncWrite`], such as a
+/// `TcpStream`.
+///
+/// This is the Tokio counterpart of `async_io::AsyncEncoder`, behind the `futures` feature,
+/// and behaves the same way: if an [`encode`](AsyncEncoder::encode) is cancelled partway
+/// through, what is left of the value is written ahead of the next one, or by
+/// [`flush`](AsyncEncoder::flush).
+///
+/// ```
+/// use bencode::bencode;
+/// use bencode::codec::AsyncEncoder;
+/// use futures::executor::block_on;
+///
+/// let mut encoder = AsyncEncoder::new(Vec::new());
+/// block_on(async {
+///     encoder.encode(&bencode!({ "y": "q" })).await.unwrap();
+///     encoder.encode(&bencode!({ "y": "r" })).await.unwrap();
+/// });
+/// assert_eq!(encoder.into_inner(), b"d1:y1:qed1:y1:re");
+/// ```
+pub struct AsyncEncoder<W> {
+    writer: W,
+    buffer: WriteBuffer,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        AsyncEncoder {
+            writer,
+            buffer: WriteBuffer::default(),
+        }
+    }
+
+    /// Writes `value`, returning once all of it has been handed to the writer.
+    pub async fn encode(&mut self, value: &Bencode) -> io::Result<()> {
+        self.write_buffered().await?;
+        self.buffer.fill(value)?;
+        self.write_buffered().await
+    }
+
+    /// Writes whatever is left of the last value and flushes the writer.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.write_buffered().await?;
+        future::poll_fn(|cx| Pin::new(&mut self.writer).poll_flush(cx)).await
+    }
+
+    async fn write_buffered(&mut self) -> io::Result<()> {
+        let writer = &mut self.writer;
+        self.buffer
+            .write_all(|cx, bytes| Pin::new(&mut *writer).poll_write(cx, bytes))
+            .await
+    }
+
+    /// The encoded bytes not yet handed to the writer.
+    pub fn buffered(&self) -> &[u8] {
+        self.buffer.buffered()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the writer, which has not been sent what is still
+    /// [`buffered`](AsyncEncoder::buffered).
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{Context, Poll};
+
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn input_ending_partway_through_a_value_is_an_error() {
+        let mut codec = BencodeCodec::new();
+        let mut buffer = BytesMut::from(&b"i1el1:"[..]);
+        assert_eq!(
+            codec.decode_eof(&mut buffer).unwrap(),
+            Some(Bencode::Integer(1))
+        );
+        let error = codec.decode_eof(&mut buffer).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = error
+            .into_inner()
+            .unwrap()
+            .downcast::<DecoderError>()
+            .unwrap();
+        assert!(matches!(
+            *error,
+            DecoderError::UnexpectedEof { offset: 6, .. }
+        ));
+    }
+
+    /// Takes at most two bytes per write, and turns every other write away.
+    #[derive(Default)]
+    struct Trickle {
+        written: Vec<u8>,
+        ready: bool,
+    }
+
+    impl AsyncWrite for Trickle {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bytes: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let length = bytes.len().min(2);
+            self.written.extend_from_slice(&bytes[..length]);
+            Poll::Ready(Ok(length))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn values_written_in_pieces() {
+        let mut encoder = AsyncEncoder::new(Trickle::default());
+        block_on(async {
+            encoder.encode(&bencode!({ "y": "q" })).await.unwrap();
+            encoder.encode(&bencode!([1, "abc"])).await.unwrap();
+            encoder.flush().await.unwrap();
+        });
+        assert!(encoder.buffered().is_empty());
+        assert_eq!(encoder.into_inner().written, b"d1:y1:qeli1e3:abce");
+    }
+}
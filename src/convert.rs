@@ -0,0 +1,648 @@
+//! Conversions between Rust types and [`Bencode`] values without going through serde.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use crate::value::Bencode;
+
+/// Types that can be converted into a [`Bencode`] value.
+pub trait ToBencode {
+    /// Fails only for values that bencode has no way to hold, such as a `None` that is
+    /// not a dictionary entry.
+    fn to_bencode(&self) -> Result<Bencode, ConversionError>;
+
+    /// Whether the value is left out of the dictionary containing it.
+    ///
+    /// Bencode has no null, so this is how `None` is represented.
+    fn is_absent(&self) -> bool {
+        false
+    }
+}
+
+/// Types that can be built from a [`Bencode`] value.
+pub trait FromBencode: Sized {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError>;
+
+    /// The value to use when a dictionary entry is missing, if there is one.
+    fn from_absent() -> Option<Self> {
+        None
+    }
+}
+
+/// The reasons a [`Bencode`] value can fail to convert into a Rust type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    UnexpectedType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    IntegerOutOfRange(i128),
+    #[cfg(feature = "num-bigint")]
+    BigIntegerOutOfRange(num_bigint::BigInt),
+    InvalidUtf8,
+    InvalidLength {
+        expected: usize,
+        found: usize,
+    },
+    MissingField(&'static str),
+    UnknownField(String),
+    UnknownVariant(String),
+    /// A `None` somewhere other than a dictionary entry, where it cannot be left out.
+    AbsentValue,
+    /// A `u128` too large for an `i128`, which needs the `num-bigint` feature.
+    IntegerTooLarge(u128),
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::UnexpectedType { expected, found } => {
+                write!(f, "expected {expected}, found {found}")
+            }
+            ConversionError::IntegerOutOfRange(integer) => {
+                write!(f, "integer {integer} is out of range")
+            }
+            #[cfg(feature = "num-bigint")]
+            ConversionError::BigIntegerOutOfRange(integer) => {
+                write!(f, "integer {integer} is out of range")
+            }
+            ConversionError::InvalidUtf8 => f.write_str("string is not valid UTF-8"),
+            ConversionError::InvalidLength { expected, found } => {
+                write!(f, "expected a list of {expected} elements, found {found}")
+            }
+            ConversionError::MissingField(field) => write!(f, "missing field `{field}`"),
+            ConversionError::UnknownField(field) => write!(f, "unknown field `{field}`"),
+            ConversionError::UnknownVariant(variant) => write!(f, "unknown variant `{variant}`"),
+            ConversionError::AbsentValue => {
+                f.write_str("`None` can only be left out of a dictionary")
+            }
+            ConversionError::IntegerTooLarge(integer) => {
+                write!(
+                    f,
+                    "integer {integer} is too large without the num-bigint feature"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+pub(crate) fn type_name(value: &Bencode) -> &'static str {
+    match value {
+        Bencode::Integer(_) => "integer",
+        #[cfg(feature = "num-bigint")]
+        Bencode::BigInteger(_) => "integer",
+        Bencode::String(_) => "string",
+        Bencode::List(_) => "list",
+        Bencode::Dictionary(_) => "dictionary",
+    }
+}
+
+fn unexpected(expected: &'static str, found: &Bencode) -> ConversionError {
+    ConversionError::UnexpectedType {
+        expected,
+        found: type_name(found),
+    }
+}
+
+impl ToBencode for Bencode {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        Ok(self.clone())
+    }
+}
+
+impl FromBencode for Bencode {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        Ok(value)
+    }
+}
+
+impl<T: ToBencode + ?Sized> ToBencode for &T {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        (**self).to_bencode()
+    }
+
+    fn is_absent(&self) -> bool {
+        (**self).is_absent()
+    }
+}
+
+// `u8` is deliberately left out so that `Vec<u8>` can be a byte string rather than a list.
+macro_rules! impl_integer {
+    ($($to:ty)*; $($from:ty)*) => {
+        $(
+            impl ToBencode for $to {
+                fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+                    // None of these is wider than 64 bits, apart from `i128` itself.
+                    Ok(Bencode::Integer(*self as i128))
+                }
+            }
+        )*
+        $(
+            impl FromBencode for $from {
+                fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+                    match value {
+                        Bencode::Integer(integer) => <$from>::try_from(integer)
+                            .map_err(|_| ConversionError::IntegerOutOfRange(integer)),
+                        #[cfg(feature = "num-bigint")]
+                        Bencode::BigInteger(integer) => {
+                            Err(ConversionError::BigIntegerOutOfRange(integer))
+                        }
+                        other => Err(unexpected("integer", &other)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(
+    i8 i16 i32 i64 i128 isize u16 u32 u64 usize;
+    i8 i16 i32 i64 i128 isize u16 u32 u64 usize
+);
+
+/// Values beyond `i128::MAX` become a [`Bencode::BigInteger`] with the `num-bigint`
+/// feature, and fail with [`ConversionError::IntegerTooLarge`] without it.
+impl ToBencode for u128 {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        match i128::try_from(*self) {
+            Ok(integer) => Ok(Bencode::Integer(integer)),
+            #[cfg(feature = "num-bigint")]
+            Err(_) => Ok(Bencode::BigInteger((*self).into())),
+            #[cfg(not(feature = "num-bigint"))]
+            Err(_) => Err(ConversionError::IntegerTooLarge(*self)),
+        }
+    }
+}
+
+impl FromBencode for u128 {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        match value {
+            Bencode::Integer(integer) => {
+                u128::try_from(integer).map_err(|_| ConversionError::IntegerOutOfRange(integer))
+            }
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(integer) => {
+                u128::try_from(&integer).map_err(|_| ConversionError::BigIntegerOutOfRange(integer))
+            }
+            other => Err(unexpected("integer", &other)),
+        }
+    }
+}
+
+/// Values that fit in an `i128` become a [`Bencode::Integer`], like every other integer.
+#[cfg(feature = "num-bigint")]
+impl ToBencode for num_bigint::BigInt {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        Ok(match i128::try_from(self) {
+            Ok(integer) => Bencode::Integer(integer),
+            Err(_) => Bencode::BigInteger(self.clone()),
+        })
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+impl FromBencode for num_bigint::BigInt {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        match value {
+            Bencode::Integer(integer) => Ok(integer.into()),
+            Bencode::BigInteger(integer) => Ok(integer),
+            other => Err(unexpected("integer", &other)),
+        }
+    }
+}
+
+impl ToBencode for str {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        Ok(Bencode::String(self.as_bytes().to_vec()))
+    }
+}
+
+impl ToBencode for String {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        self.as_str().to_bencode()
+    }
+}
+
+impl FromBencode for String {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        let bytes = Vec::<u8>::from_bencode(value)?;
+        String::from_utf8(bytes).map_err(|_| ConversionError::InvalidUtf8)
+    }
+}
+
+impl ToBencode for [u8] {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        Ok(Bencode::String(self.to_vec()))
+    }
+}
+
+impl ToBencode for Vec<u8> {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        self.as_slice().to_bencode()
+    }
+}
+
+impl FromBencode for Vec<u8> {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        match value {
+            Bencode::String(bytes) => Ok(bytes),
+            other => Err(unexpected("string", &other)),
+        }
+    }
+}
+
+/// Fails with [`ConversionError::AbsentValue`] for a `None` element, since leaving it out
+/// would move every element after it.
+impl<T: ToBencode> ToBencode for [T] {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        self.iter()
+            .map(ToBencode::to_bencode)
+            .collect::<Result<_, _>>()
+            .map(Bencode::List)
+    }
+}
+
+impl<T: ToBencode> ToBencode for Vec<T> {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        self.as_slice().to_bencode()
+    }
+}
+
+impl<T: FromBencode> FromBencode for Vec<T> {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        match value {
+            Bencode::List(list) => list.into_iter().map(T::from_bencode).collect(),
+            other => Err(unexpected("list", &other)),
+        }
+    }
+}
+
+impl<T: ToBencode, const N: usize> ToBencode for [T; N] {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        self.as_slice().to_bencode()
+    }
+}
+
+/// Fails with [`ConversionError::InvalidLength`] unless the list has exactly `N` elements.
+impl<T: FromBencode, const N: usize> FromBencode for [T; N] {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        let list = Vec::<T>::from_bencode(value)?;
+        let found = list.len();
+        list.try_into()
+            .map_err(|_| ConversionError::InvalidLength { expected: N, found })
+    }
+}
+
+impl<T: ToBencode> ToBencode for BTreeMap<String, T> {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        self.iter()
+            .filter(|(_, value)| !value.is_absent())
+            .map(|(key, value)| Ok((key.clone().into_bytes(), value.to_bencode()?)))
+            .collect::<Result<_, _>>()
+            .map(Bencode::Dictionary)
+    }
+}
+
+/// Fails with [`ConversionError::InvalidUtf8`] if any key is not valid UTF-8.
+impl<T: FromBencode> FromBencode for BTreeMap<String, T> {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        match value {
+            Bencode::Dictionary(dictionary) => dictionary
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = String::from_utf8(key).map_err(|_| ConversionError::InvalidUtf8)?;
+                    Ok((key, T::from_bencode(value)?))
+                })
+                .collect(),
+            other => Err(unexpected("dictionary", &other)),
+        }
+    }
+}
+
+impl<T: ToBencode> ToBencode for BTreeMap<Vec<u8>, T> {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        self.iter()
+            .filter(|(_, value)| !value.is_absent())
+            .map(|(key, value)| Ok((key.clone(), value.to_bencode()?)))
+            .collect::<Result<_, _>>()
+            .map(Bencode::Dictionary)
+    }
+}
+
+impl<T: FromBencode> FromBencode for BTreeMap<Vec<u8>, T> {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        match value {
+            Bencode::Dictionary(dictionary) => dictionary
+                .into_iter()
+                .map(|(key, value)| Ok((key, T::from_bencode(value)?)))
+                .collect(),
+            other => Err(unexpected("dictionary", &other)),
+        }
+    }
+}
+
+/// `None` is left out of dictionaries. Anywhere else it fails with
+/// [`ConversionError::AbsentValue`], as it does with the serde serializer.
+impl<T: ToBencode> ToBencode for Option<T> {
+    fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+        match self {
+            Some(value) => value.to_bencode(),
+            None => Err(ConversionError::AbsentValue),
+        }
+    }
+
+    fn is_absent(&self) -> bool {
+        self.is_none()
+    }
+}
+
+impl<T: FromBencode> FromBencode for Option<T> {
+    fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+        T::from_bencode(value).map(Some)
+    }
+
+    fn from_absent() -> Option<Self> {
+        Some(None)
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($len:literal => ($($name:ident)+))+) => {
+        $(
+            impl<$($name: ToBencode),+> ToBencode for ($($name,)+) {
+                fn to_bencode(&self) -> Result<Bencode, ConversionError> {
+                    #[allow(non_snake_case)]
+                    let ($($name,)+) = self;
+                    Ok(Bencode::List(vec![$($name.to_bencode()?),+]))
+                }
+            }
+
+            impl<$($name: FromBencode),+> FromBencode for ($($name,)+) {
+                fn from_bencode(value: Bencode) -> Result<Self, ConversionError> {
+                    let list = match value {
+                        Bencode::List(list) if list.len() == $len => list,
+                        Bencode::List(list) => {
+                            return Err(ConversionError::InvalidLength {
+                                expected: $len,
+                                found: list.len(),
+                            })
+                        }
+                        other => return Err(unexpected("list", &other)),
+                    };
+                    let mut elements = list.into_iter();
+                    Ok(($($name::from_bencode(elements.next().unwrap())?,)+))
+                }
+            }
+        )+
+    };
+}
+
+impl_tuple! {
+    1 => (A)
+    2 => (A B)
+    3 => (A B C)
+    4 => (A B C D)
+    5 => (A B C D E)
+    6 => (A B C D E F)
+}
+
+macro_rules! impl_try_from {
+    ($($ty:ty)*) => {
+        $(
+            impl TryFrom<Bencode> for $ty {
+                type Error = ConversionError;
+
+                fn try_from(value: Bencode) -> Result<Self, ConversionError> {
+                    <$ty>::from_bencode(value)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from!(
+    i64 String Vec<u8> Vec<Bencode> BTreeMap<String, Bencode> BTreeMap<Vec<u8>, Bencode>
+);
+
+impl From<i64> for Bencode {
+    fn from(integer: i64) -> Self {
+        Bencode::Integer(integer.into())
+    }
+}
+
+impl From<&str> for Bencode {
+    fn from(string: &str) -> Self {
+        Bencode::String(string.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Bencode {
+    fn from(string: String) -> Self {
+        Bencode::String(string.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Bencode {
+    fn from(bytes: &[u8]) -> Self {
+        Bencode::String(bytes.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Bencode {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bencode::String(bytes)
+    }
+}
+
+impl From<Vec<Bencode>> for Bencode {
+    fn from(list: Vec<Bencode>) -> Self {
+        Bencode::List(list)
+    }
+}
+
+impl From<BTreeMap<String, Bencode>> for Bencode {
+    fn from(dictionary: BTreeMap<String, Bencode>) -> Self {
+        dictionary.into_iter().collect()
+    }
+}
+
+impl From<BTreeMap<Vec<u8>, Bencode>> for Bencode {
+    fn from(dictionary: BTreeMap<Vec<u8>, Bencode>) -> Self {
+        Bencode::Dictionary(dictionary)
+    }
+}
+
+impl FromIterator<Bencode> for Bencode {
+    fn from_iter<I: IntoIterator<Item = Bencode>>(iter: I) -> Self {
+        Bencode::List(iter.into_iter().collect())
+    }
+}
+
+impl FromIterator<(String, Bencode)> for Bencode {
+    fn from_iter<I: IntoIterator<Item = (String, Bencode)>>(iter: I) -> Self {
+        iter.into_iter()
+            .map(|(key, value)| (key.into_bytes(), value))
+            .collect()
+    }
+}
+
+impl FromIterator<(Vec<u8>, Bencode)> for Bencode {
+    fn from_iter<I: IntoIterator<Item = (Vec<u8>, Bencode)>>(iter: I) -> Self {
+        Bencode::Dictionary(iter.into_iter().collect())
+    }
+}
+
+/// Appends to a list.
+///
+/// # Panics
+///
+/// Panics if the value is not a [`Bencode::List`].
+impl Extend<Bencode> for Bencode {
+    fn extend<I: IntoIterator<Item = Bencode>>(&mut self, iter: I) {
+        match self {
+            Bencode::List(list) => list.extend(iter),
+            other => panic!("cannot extend {} with list elements", type_name(other)),
+        }
+    }
+}
+
+/// Inserts into a dictionary, replacing existing entries with the same key.
+///
+/// # Panics
+///
+/// Panics if the value is not a [`Bencode::Dictionary`].
+impl Extend<(String, Bencode)> for Bencode {
+    fn extend<I: IntoIterator<Item = (String, Bencode)>>(&mut self, iter: I) {
+        self.extend(
+            iter.into_iter()
+                .map(|(key, value)| (key.into_bytes(), value)),
+        );
+    }
+}
+
+/// Inserts into a dictionary, replacing existing entries with the same key.
+///
+/// # Panics
+///
+/// Panics if the value is not a [`Bencode::Dictionary`].
+impl Extend<(Vec<u8>, Bencode)> for Bencode {
+    fn extend<I: IntoIterator<Item = (Vec<u8>, Bencode)>>(&mut self, iter: I) {
+        match self {
+            Bencode::Dictionary(dictionary) => dictionary.extend(iter),
+            other => panic!("cannot extend {} with dictionary entries", type_name(other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers_out_of_range_are_rejected() {
+        assert_eq!(
+            i8::from_bencode(Bencode::integer(300)),
+            Err(ConversionError::IntegerOutOfRange(300))
+        );
+        assert_eq!(
+            u64::from_bencode(Bencode::integer(-1)),
+            Err(ConversionError::IntegerOutOfRange(-1))
+        );
+        assert_eq!(
+            usize::from_bencode(Bencode::from_utf8("1")),
+            Err(ConversionError::UnexpectedType {
+                expected: "integer",
+                found: "string",
+            })
+        );
+        for integer in [0, usize::MAX] {
+            let value = integer.to_bencode().unwrap();
+            assert_eq!(usize::from_bencode(value), Ok(integer));
+        }
+        assert_eq!(
+            isize::MIN.to_bencode(),
+            Ok(Bencode::integer(isize::MIN as i64))
+        );
+    }
+
+    #[test]
+    fn u128_beyond_i128_needs_big_integers() {
+        let value = u128::MAX.to_bencode();
+        #[cfg(feature = "num-bigint")]
+        assert_eq!(u128::from_bencode(value.unwrap()), Ok(u128::MAX));
+        #[cfg(not(feature = "num-bigint"))]
+        assert_eq!(value, Err(ConversionError::IntegerTooLarge(u128::MAX)));
+
+        let value = (i128::MAX as u128).to_bencode().unwrap();
+        assert_eq!(value, Bencode::Integer(i128::MAX));
+        assert_eq!(u128::from_bencode(value), Ok(i128::MAX as u128));
+    }
+
+    #[test]
+    fn tuples_and_arrays_need_their_length() {
+        let value = bencode!([1, "a"]);
+        assert_eq!(
+            <(i64, String)>::from_bencode(value.clone()),
+            Ok((1, "a".to_owned()))
+        );
+        assert_eq!(
+            <(i64, String, i64)>::from_bencode(value),
+            Err(ConversionError::InvalidLength {
+                expected: 3,
+                found: 2,
+            })
+        );
+
+        let value = [1, 2, 3].to_bencode().unwrap();
+        assert_eq!(<[i64; 3]>::from_bencode(value.clone()), Ok([1, 2, 3]));
+        assert_eq!(
+            <[i64; 2]>::from_bencode(value),
+            Err(ConversionError::InvalidLength {
+                expected: 2,
+                found: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn text_must_be_utf8() {
+        assert_eq!(
+            String::from_bencode(Bencode::string(b"\xff".to_vec())),
+            Err(ConversionError::InvalidUtf8)
+        );
+        let value = Bencode::Dictionary(BTreeMap::from([(b"\xff".to_vec(), Bencode::integer(1))]));
+        assert_eq!(
+            BTreeMap::<String, i64>::from_bencode(value.clone()),
+            Err(ConversionError::InvalidUtf8)
+        );
+        assert!(BTreeMap::<Vec<u8>, i64>::from_bencode(value).is_ok());
+    }
+
+    #[test]
+    fn none_is_only_left_out_of_dictionaries() {
+        assert_eq!(None::<i64>.to_bencode(), Err(ConversionError::AbsentValue));
+        assert_eq!(
+            vec![None, Some(1)].to_bencode(),
+            Err(ConversionError::AbsentValue)
+        );
+        assert_eq!(
+            (Some(1), None::<i64>).to_bencode(),
+            Err(ConversionError::AbsentValue)
+        );
+        let dictionary = BTreeMap::from([("a".to_owned(), None), ("b".to_owned(), Some(1))]);
+        assert_eq!(dictionary.to_bencode(), Ok(bencode!({ "b": 1 })));
+    }
+
+    #[test]
+    fn only_options_may_be_absent() {
+        assert_eq!(Option::<i64>::from_absent(), Some(None));
+        assert_eq!(i64::from_absent(), None);
+        assert_eq!(Vec::<i64>::from_absent(), None);
+        assert_eq!(
+            Option::<i64>::from_bencode(Bencode::integer(1)),
+            Ok(Some(1))
+        );
+    }
+}
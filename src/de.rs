@@ -0,0 +1,620 @@
+//! Deserialize bencode into Rust data structures through serde.
+
+use std::io;
+
+use serde::de::{
+    self, Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, Unexpected, Visitor,
+};
+
+use crate::decoder::DecoderOptions;
+use crate::error::{Error, Result};
+use crate::raw;
+use crate::read::{IoRead, Read, Reference, SliceRead};
+use crate::ser::{EnumRepresentation, SerializerOptions};
+
+/// A serde deserializer reading bencode from a [`Read`] source.
+///
+/// Each list or dictionary is deserialized by a nested call, so how deeply they may nest
+/// is limited to keep hostile input from overflowing the stack; see
+/// [`with_max_depth`](Deserializer::with_max_depth).
+pub struct Deserializer<R> {
+    read: R,
+    scratch: Vec<u8>,
+    options: SerializerOptions,
+    max_depth: usize,
+    remaining_depth: usize,
+}
+
+impl<R: io::Read> Deserializer<IoRead<R>> {
+    /// Creates a deserializer reading from `reader`, which should be buffered unless
+    /// small reads from it are cheap; see [`IoRead`].
+    pub fn new(reader: R) -> Self {
+        Deserializer::with_read(IoRead::new(reader))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.read.into_inner()
+    }
+}
+
+impl<'a> Deserializer<SliceRead<'a>> {
+    /// Creates a deserializer that borrows strings directly from `bytes`.
+    pub fn from_slice(bytes: &'a [u8]) -> Self {
+        Deserializer::with_read(SliceRead::new(bytes))
+    }
+}
+
+impl<'de, R: Read<'de>> Deserializer<R> {
+    pub fn with_read(read: R) -> Self {
+        let max_depth = DecoderOptions::default().max_depth;
+        Deserializer {
+            read,
+            scratch: Vec::new(),
+            options: SerializerOptions::default(),
+            max_depth,
+            remaining_depth: max_depth,
+        }
+    }
+
+    /// Sets the options the input was serialized with.
+    pub fn with_options(mut self, options: SerializerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets how deeply lists and dictionaries may nest, which is
+    /// [`DecoderOptions::max_depth`] unless changed.
+    ///
+    /// Raising the limit far past the default risks overflowing the stack on deep input.
+    ///
+    /// ```
+    /// use bencode::{Bencode, Deserializer, Error};
+    /// use serde::Deserialize;
+    ///
+    /// let mut deserializer = Deserializer::from_slice(b"llleee").with_max_depth(2);
+    /// let error = Bencode::deserialize(&mut deserializer).unwrap_err();
+    /// assert!(matches!(error, Error::DepthLimitExceeded { limit: 2 }));
+    /// ```
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self.remaining_depth = max_depth;
+        self
+    }
+
+    /// How many bytes of the input have been read. After an error, this is where the byte
+    /// it is about starts or ends, depending on whether it had been read yet.
+    ///
+    /// ```
+    /// use bencode::{Deserializer, Error};
+    /// use serde::Deserialize;
+    ///
+    /// let mut deserializer = Deserializer::from_slice(b"li1ei2xe");
+    /// let error = Vec::<i64>::deserialize(&mut deserializer).unwrap_err();
+    /// assert!(matches!(error, Error::UnexpectedByte(b'x')));
+    /// assert_eq!(deserializer.byte_offset(), 7);
+    /// ```
+    pub fn byte_offset(&self) -> usize {
+        self.read.offset()
+    }
+
+    /// Checks that the input has been used up, as it should be once the last value is read.
+    pub fn end(&mut self) -> Result<()> {
+        match self.read.peek()? {
+            None => Ok(()),
+            Some(byte) => Err(Error::UnexpectedByte(byte)),
+        }
+    }
+
+    fn peek(&mut self) -> Result<u8> {
+        self.read.peek()?.ok_or(Error::Eof)
+    }
+
+    fn next(&mut self) -> Result<u8> {
+        self.read.next()?.ok_or(Error::Eof)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        match self.next()? {
+            byte if byte == expected => Ok(()),
+            byte => Err(Error::UnexpectedByte(byte)),
+        }
+    }
+
+    /// Reads the digits of a number up to and including `terminator`.
+    fn read_number(&mut self, terminator: u8) -> Result<String> {
+        let mut digits = String::new();
+        loop {
+            match self.next()? {
+                byte if byte == terminator => return Ok(digits),
+                byte @ (b'0'..=b'9' | b'-') => digits.push(byte as char),
+                byte => return Err(Error::UnexpectedByte(byte)),
+            }
+        }
+    }
+
+    fn parse_integer<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value> {
+        self.expect(b'i')?;
+        let digits = self.read_number(b'e')?;
+        if let Ok(value) = digits.parse::<i64>() {
+            visitor.visit_i64(value)
+        } else if let Ok(value) = digits.parse::<u64>() {
+            visitor.visit_u64(value)
+        } else if let Ok(value) = digits.parse::<i128>() {
+            visitor.visit_i128(value)
+        } else if let Ok(value) = digits.parse::<u128>() {
+            visitor.visit_u128(value)
+        } else {
+            Err(Error::InvalidInteger)
+        }
+    }
+
+    fn parse_bytes(&mut self) -> Result<Reference<'de, '_>> {
+        let length = self
+            .read_number(b':')?
+            .parse()
+            .map_err(|_| Error::InvalidInteger)?;
+        self.read.read_bytes(length, &mut self.scratch)
+    }
+
+    /// Reads a dictionary key and checks that it is `key`.
+    fn expect_key(&mut self, key: &'static str) -> Result<()> {
+        if self.peek()?.is_ascii_digit() && *self.parse_bytes()? == *key.as_bytes() {
+            Ok(())
+        } else {
+            Err(<Error as de::Error>::missing_field(key))
+        }
+    }
+
+    /// Runs `f` one level of nesting deeper, failing if that is too deep.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.remaining_depth == 0 {
+            return Err(Error::DepthLimitExceeded {
+                limit: self.max_depth,
+            });
+        }
+        self.remaining_depth -= 1;
+        let result = f(self);
+        self.remaining_depth += 1;
+        result
+    }
+
+    fn parse_str<V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value> {
+        match self.parse_bytes()? {
+            Reference::Borrowed(bytes) => match std::str::from_utf8(bytes) {
+                Ok(string) => visitor.visit_borrowed_str(string),
+                Err(_) => visitor.visit_borrowed_bytes(bytes),
+            },
+            Reference::Copied(bytes) => match std::str::from_utf8(bytes) {
+                Ok(string) => visitor.visit_str(string),
+                Err(_) => visitor.visit_bytes(bytes),
+            },
+        }
+    }
+}
+
+/// Deserialize an instance of `T` from bencode read from `reader`.
+///
+/// The reader should be buffered unless small reads from it are cheap; see [`IoRead`].
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    T::deserialize(&mut Deserializer::new(reader))
+}
+
+/// Deserialize an instance of `T` from a slice of bencode, borrowing strings from it.
+///
+/// With `T` as [`Bencode`](crate::Bencode), this decodes a value of any shape, nested no
+/// more deeply than [`DecoderOptions::max_depth`] allows by default:
+///
+/// ```
+/// use bencode::{bencode, Bencode};
+///
+/// let value: Bencode = bencode::from_slice(b"d1:t2:aa1:y1:qe").unwrap();
+/// assert_eq!(value, bencode!({ "t": "aa", "y": "q" }));
+/// ```
+///
+/// This goes through serde, so it needs the `serde` feature, which is on by default.
+/// Without it, a [`Decoder`](crate::Decoder) decodes a `Bencode` just as well.
+pub fn from_slice<'a, T>(bytes: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    T::deserialize(&mut Deserializer::from_slice(bytes))
+}
+
+/// Like [`from_slice`], but fails if anything follows the value.
+///
+/// ```
+/// let error = bencode::from_slice_complete::<i64>(b"i42ex").unwrap_err();
+/// assert!(matches!(error, bencode::Error::UnexpectedByte(b'x')));
+/// ```
+pub fn from_slice_complete<'a, T>(bytes: &'a [u8]) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::from_slice(bytes);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+impl<'de, R: Read<'de>> de::Deserializer<'de> for &mut Deserializer<R> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.peek()? {
+            b'i' => self.parse_integer(visitor),
+            b'0'..=b'9' => self.parse_str(visitor),
+            // A visitor that knows how many elements to expect, such as a tuple's, stops
+            // without asking for more, so the closing `e` may still be there to read.
+            b'l' => self.nested(|de| {
+                de.next()?;
+                let mut list = List { de, ended: false };
+                let value = visitor.visit_seq(&mut list)?;
+                if !list.ended {
+                    de.expect(b'e')?;
+                }
+                Ok(value)
+            }),
+            b'd' => self.nested(|de| {
+                de.next()?;
+                let mut dictionary = Dictionary { de, ended: false };
+                let value = visitor.visit_map(&mut dictionary)?;
+                if !dictionary.ended {
+                    de.expect(b'e')?;
+                }
+                Ok(value)
+            }),
+            byte => Err(Error::UnexpectedByte(byte)),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.expect(b'i')?;
+        match self.read_number(b'e')?.as_str() {
+            "0" => visitor.visit_bool(false),
+            "1" => visitor.visit_bool(true),
+            _ => Err(Error::InvalidInteger),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // Absent values are simply left out, so anything that is present is `Some`.
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == raw::TOKEN {
+            self.read.begin_raw();
+            de::IgnoredAny::deserialize(&mut *self)?;
+            return match self.read.end_raw() {
+                Reference::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+                Reference::Copied(bytes) => visitor.visit_bytes(bytes),
+            };
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let representation = self.options.enum_representation;
+        match self.peek()? {
+            b'0'..=b'9' if representation == EnumRepresentation::UnitAsString => {
+                let bytes = self.parse_bytes()?;
+                let variant = std::str::from_utf8(&bytes)
+                    .map_err(|_| {
+                        <Error as de::Error>::invalid_value(
+                            Unexpected::Bytes(&bytes),
+                            &"a variant name",
+                        )
+                    })?
+                    .to_owned();
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            b'd' => self.nested(|de| {
+                de.next()?;
+                if representation == EnumRepresentation::AdjacentlyTagged {
+                    de.expect_key("type")?;
+                }
+                let value = visitor.visit_enum(Enum { de: &mut *de })?;
+                de.expect(b'e')?;
+                Ok(value)
+            }),
+            byte => Err(Error::UnexpectedByte(byte)),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.peek()? {
+            b'0'..=b'9' => match self.parse_bytes()? {
+                Reference::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+                Reference::Copied(bytes) => visitor.visit_bytes(bytes),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Gives serde access to the elements of a bencode list.
+struct List<'a, R> {
+    de: &'a mut Deserializer<R>,
+    /// Whether the closing `e` has been read.
+    ended: bool,
+}
+
+impl<'de, R: Read<'de>> de::SeqAccess<'de> for List<'_, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.de.peek()? == b'e' {
+            self.de.next()?;
+            self.ended = true;
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+}
+
+/// Gives serde access to the entries of a bencode dictionary.
+struct Dictionary<'a, R> {
+    de: &'a mut Deserializer<R>,
+    /// Whether the closing `e` has been read.
+    ended: bool,
+}
+
+impl<'de, R: Read<'de>> de::MapAccess<'de> for Dictionary<'_, R> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.de.peek()? {
+            b'e' => {
+                self.de.next()?;
+                self.ended = true;
+                Ok(None)
+            }
+            b'0'..=b'9' => seed.deserialize(&mut *self.de).map(Some),
+            _ => Err(Error::KeyMustBeAString),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+/// Gives serde access to an enum encoded as a dictionary, positioned at the variant name.
+struct Enum<'a, R> {
+    de: &'a mut Deserializer<R>,
+}
+
+impl<'de, R: Read<'de>> Enum<'_, R> {
+    /// Moves past whatever separates the variant name from its contents.
+    fn begin_contents(&mut self) -> Result<()> {
+        match self.de.options.enum_representation {
+            EnumRepresentation::AdjacentlyTagged => self.de.expect_key("value"),
+            EnumRepresentation::UnitAsString | EnumRepresentation::ExternallyTagged => Ok(()),
+        }
+    }
+}
+
+impl<'de, R: Read<'de>> de::EnumAccess<'de> for Enum<'_, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(&mut *self.de)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, R: Read<'de>> de::VariantAccess<'de> for Enum<'_, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.de.options.enum_representation {
+            // Adjacently tagged unit variants have no `value` entry at all.
+            EnumRepresentation::AdjacentlyTagged => Ok(()),
+            EnumRepresentation::UnitAsString | EnumRepresentation::ExternallyTagged => {
+                de::IgnoredAny::deserialize(self.de)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.begin_contents()?;
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(mut self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.begin_contents()?;
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        mut self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.begin_contents()?;
+        de::Deserializer::deserialize_map(self.de, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::ser::to_vec;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct File {
+        name: String,
+        length: u64,
+        md5sum: Option<String>,
+        path: Vec<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Message {
+        Ping,
+        Error(i64),
+        Announce(String, u16),
+        FindNode { id: String, target: String },
+    }
+
+    #[test]
+    fn structs_round_trip() {
+        let file = File {
+            name: "a.txt".into(),
+            length: 5,
+            md5sum: None,
+            path: vec!["dir".into(), "a.txt".into()],
+        };
+        let bytes = to_vec(&file).unwrap();
+        assert_eq!(from_slice::<File>(&bytes).unwrap(), file);
+        assert_eq!(from_reader::<_, File>(&bytes[..]).unwrap(), file);
+
+        // Entries the struct does not have are skipped, whatever they hold.
+        let bytes = b"d5:extrad1:ali1eee6:lengthi5e4:name5:a.txt4:pathl5:a.txtee";
+        let file: File = from_slice(bytes).unwrap();
+        assert_eq!(file.path, ["a.txt"]);
+    }
+
+    #[test]
+    fn enums_round_trip_in_each_representation() {
+        let messages = [
+            Message::Ping,
+            Message::Error(-201),
+            Message::Announce("hash".into(), 6881),
+            Message::FindNode {
+                id: "abc".into(),
+                target: "def".into(),
+            },
+        ];
+        for enum_representation in [
+            EnumRepresentation::UnitAsString,
+            EnumRepresentation::ExternallyTagged,
+            EnumRepresentation::AdjacentlyTagged,
+        ] {
+            let options = SerializerOptions {
+                enum_representation,
+            };
+            for message in &messages {
+                let mut serializer = crate::Serializer::new(Vec::new()).with_options(options);
+                message.serialize(&mut serializer).unwrap();
+                let bytes = serializer.into_inner();
+                let mut deserializer = Deserializer::from_slice(&bytes).with_options(options);
+                assert_eq!(&Message::deserialize(&mut deserializer).unwrap(), message);
+                deserializer.end().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn strings_are_borrowed_from_slices() {
+        #[derive(Deserialize)]
+        struct Peer<'a> {
+            ip: &'a str,
+            id: &'a [u8],
+        }
+
+        let input = b"d2:id3:\xff\x00\x012:ip9:127.0.0.1e";
+        let peer: Peer<'_> = from_slice(input).unwrap();
+        assert_eq!(peer.ip, "127.0.0.1");
+        assert_eq!(peer.id, b"\xff\x00\x01");
+        assert!(input.as_ptr_range().contains(&peer.ip.as_ptr()));
+        assert!(input.as_ptr_range().contains(&peer.id.as_ptr()));
+
+        // Strings read from a reader cannot be borrowed.
+        assert!(Peer::deserialize(&mut Deserializer::new(&input[..])).is_err());
+    }
+
+    #[test]
+    fn errors_leave_the_offset_where_they_were_found() {
+        for (input, offset) in [
+            (&b"d4:name5:a.txt6:lengthi5xe"[..], 25),
+            (b"d4:name5:a.txt6:length", 22),
+            (b"d4:name5:a.t", 12),
+        ] {
+            let mut deserializer = Deserializer::from_slice(input);
+            assert!(File::deserialize(&mut deserializer).is_err());
+            assert_eq!(deserializer.byte_offset(), offset);
+
+            let mut deserializer = Deserializer::new(input);
+            assert!(File::deserialize(&mut deserializer).is_err());
+            assert_eq!(deserializer.byte_offset(), offset);
+        }
+        assert!(matches!(
+            from_slice::<File>(b"d4:name5:a.txt6:lengthi5xe"),
+            Err(Error::UnexpectedByte(b'x'))
+        ));
+        assert!(matches!(from_slice::<File>(b"d4:name"), Err(Error::Eof)));
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected_before_it_overflows_the_stack() {
+        let nested = vec![b'l'; 1_000_000];
+        let limit = DecoderOptions::default().max_depth;
+        assert!(matches!(
+            from_slice::<crate::Bencode>(&nested),
+            Err(Error::DepthLimitExceeded { limit: found }) if found == limit
+        ));
+        assert!(matches!(
+            from_slice::<de::IgnoredAny>(&nested),
+            Err(Error::DepthLimitExceeded { .. })
+        ));
+
+        let mut deepest = vec![b'l'; limit];
+        deepest.extend(vec![b'e'; limit]);
+        assert!(from_slice::<crate::Bencode>(&deepest).is_ok());
+        deepest.insert(0, b'l');
+        deepest.push(b'e');
+        assert!(from_slice::<crate::Bencode>(&deepest).is_err());
+    }
+
+    #[test]
+    fn tuples_end_with_their_list() {
+        let pairs: Vec<(i64, String)> = from_slice(b"lli1e1:aeli2e1:bee").unwrap();
+        assert_eq!(pairs, [(1, "a".into()), (2, "b".into())]);
+        assert!(matches!(
+            from_slice::<(i64, i64)>(b"li1ei2ei3ee"),
+            Err(Error::UnexpectedByte(b'i'))
+        ));
+    }
+}
@@ -0,0 +1,2241 @@
+//! Decoding bencode into [`Bencode`] values without going through serde.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, Read, Seek, Write};
+use std::mem;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::input::Input;
+use crate::path::{Path, Segment};
+use crate::spanned::Spanned;
+use crate::value::{Bencode, BencodeKind};
+
+/// What the decoder was looking for when it found something else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Expected {
+    /// The start of a value: `i`, `l`, `d` or the length of a string.
+    Value,
+    /// A value or the `e` closing a list.
+    ValueOrEnd,
+    /// A string key or the `e` closing a dictionary.
+    KeyOrEnd,
+    /// A digit of a number, or the byte that ends it.
+    Digit { terminator: u8 },
+    /// The rest of a string's contents.
+    StringBytes { remaining: u64 },
+}
+
+impl Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Value => f.write_str("a value"),
+            Expected::ValueOrEnd => f.write_str("a value or `e`"),
+            Expected::KeyOrEnd => f.write_str("a string key or `e`"),
+            Expected::Digit { terminator } => write!(f, "a digit or `{}`", char::from(*terminator)),
+            Expected::StringBytes { remaining } => write!(f, "{remaining} more bytes of string"),
+        }
+    }
+}
+
+/// The reasons decoding can fail.
+///
+/// Every variant carries the offset, counted in bytes from the start of the input, at
+/// which the problem was found, and the path of the list or dictionary being decoded at
+/// the time, such as `info.files[3].path`.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DecoderError {
+    /// Reading from the underlying reader, or writing out a raw value or streamed string,
+    /// failed.
+    IO {
+        offset: usize,
+        path: Path,
+        source: io::Error,
+    },
+    /// The input ended in the middle of a value.
+    UnexpectedEof {
+        expected: Expected,
+        offset: usize,
+        path: Path,
+    },
+    /// A byte other than the ones that could come next.
+    UnexpectedByte {
+        byte: u8,
+        expected: Expected,
+        offset: usize,
+        path: Path,
+    },
+    /// An integer or string length with no digits, or a negative string length.
+    NAN { offset: usize, path: Path },
+    /// An integer or string length too large to represent.
+    IntegerOverflow { offset: usize, path: Path },
+    /// An integer with leading zeros, or negative zero, when decoding strictly.
+    NonCanonicalInteger { offset: usize, path: Path },
+    /// A dictionary key that sorts before the one preceding it, when decoding strictly.
+    UnsortedKey {
+        key: String,
+        offset: usize,
+        path: Path,
+    },
+    /// A dictionary key that appears more than once, when decoding strictly.
+    DuplicateKey {
+        key: String,
+        offset: usize,
+        path: Path,
+    },
+    /// Lists and dictionaries nested more deeply than [`DecoderOptions::max_depth`].
+    DepthLimitExceeded {
+        limit: usize,
+        offset: usize,
+        path: Path,
+    },
+    /// A string longer than [`DecoderOptions::max_string_length`].
+    StringTooLong {
+        length: u64,
+        limit: u64,
+        offset: usize,
+        path: Path,
+    },
+    /// Strings adding up to more than [`DecoderOptions::max_total_bytes`].
+    BudgetExceeded {
+        limit: u64,
+        offset: usize,
+        path: Path,
+    },
+    /// More list elements and dictionary entries than [`DecoderOptions::max_elements`].
+    TooManyElements {
+        limit: usize,
+        offset: usize,
+        path: Path,
+    },
+    /// Bytes left over after the value, found by [`Decoder::decode_complete`].
+    TrailingBytes { offset: usize, path: Path },
+    /// A value of another kind than the one asked for, such as by
+    /// [`Decoder::decode_dictionary`].
+    WrongType {
+        expected: BencodeKind,
+        found: BencodeKind,
+        offset: usize,
+        path: Path,
+    },
+}
+
+impl DecoderError {
+    /// The offset in the input at which the problem was found.
+    pub fn offset(&self) -> usize {
+        match self {
+            DecoderError::IO { offset, .. }
+            | DecoderError::UnexpectedEof { offset, .. }
+            | DecoderError::UnexpectedByte { offset, .. }
+            | DecoderError::NAN { offset, .. }
+            | DecoderError::IntegerOverflow { offset, .. }
+            | DecoderError::NonCanonicalInteger { offset, .. }
+            | DecoderError::UnsortedKey { offset, .. }
+            | DecoderError::DuplicateKey { offset, .. }
+            | DecoderError::DepthLimitExceeded { offset, .. }
+            | DecoderError::StringTooLong { offset, .. }
+            | DecoderError::BudgetExceeded { offset, .. }
+            | DecoderError::TooManyElements { offset, .. }
+            | DecoderError::TrailingBytes { offset, .. }
+            | DecoderError::WrongType { offset, .. } => *offset,
+        }
+    }
+
+    /// Moves the offset `by` bytes further on, for input that was decoded in pieces.
+    pub(crate) fn offset_by(mut self, by: usize) -> Self {
+        match &mut self {
+            DecoderError::IO { offset, .. }
+            | DecoderError::UnexpectedEof { offset, .. }
+            | DecoderError::UnexpectedByte { offset, .. }
+            | DecoderError::NAN { offset, .. }
+            | DecoderError::IntegerOverflow { offset, .. }
+            | DecoderError::NonCanonicalInteger { offset, .. }
+            | DecoderError::UnsortedKey { offset, .. }
+            | DecoderError::DuplicateKey { offset, .. }
+            | DecoderError::DepthLimitExceeded { offset, .. }
+            | DecoderError::StringTooLong { offset, .. }
+            | DecoderError::BudgetExceeded { offset, .. }
+            | DecoderError::TooManyElements { offset, .. }
+            | DecoderError::TrailingBytes { offset, .. }
+            | DecoderError::WrongType { offset, .. } => *offset += by,
+        }
+        self
+    }
+
+    /// Where in the document the problem was found.
+    pub fn path(&self) -> &Path {
+        match self {
+            DecoderError::IO { path, .. }
+            | DecoderError::UnexpectedEof { path, .. }
+            | DecoderError::UnexpectedByte { path, .. }
+            | DecoderError::NAN { path, .. }
+            | DecoderError::IntegerOverflow { path, .. }
+            | DecoderError::NonCanonicalInteger { path, .. }
+            | DecoderError::UnsortedKey { path, .. }
+            | DecoderError::DuplicateKey { path, .. }
+            | DecoderError::DepthLimitExceeded { path, .. }
+            | DecoderError::StringTooLong { path, .. }
+            | DecoderError::BudgetExceeded { path, .. }
+            | DecoderError::TooManyElements { path, .. }
+            | DecoderError::TrailingBytes { path, .. }
+            | DecoderError::WrongType { path, .. } => path,
+        }
+    }
+}
+
+impl Display for DecoderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecoderError::IO { source, .. } => write!(f, "failed to read input: {source}")?,
+            DecoderError::UnexpectedEof { expected, .. } => {
+                write!(f, "expected {expected}, found end of input")?
+            }
+            DecoderError::UnexpectedByte { byte, expected, .. } => {
+                write!(f, "expected {expected}, found {:?}", char::from(*byte))?
+            }
+            DecoderError::NAN { .. } => f.write_str("expected a number")?,
+            DecoderError::IntegerOverflow { .. } => f.write_str("number is too large")?,
+            DecoderError::NonCanonicalInteger { .. } => {
+                f.write_str("integer has leading zeros or is negative zero")?
+            }
+            DecoderError::UnsortedKey { key, .. } => write!(f, "key {key:?} is out of order")?,
+            DecoderError::DuplicateKey { key, .. } => write!(f, "duplicate key {key:?}")?,
+            DecoderError::DepthLimitExceeded { limit, .. } => {
+                write!(f, "nested more than {limit} levels deep")?
+            }
+            DecoderError::StringTooLong { length, limit, .. } => {
+                write!(f, "string of {length} bytes is longer than {limit}")?
+            }
+            DecoderError::BudgetExceeded { limit, .. } => {
+                write!(f, "strings add up to more than {limit} bytes")?
+            }
+            DecoderError::TooManyElements { limit, .. } => {
+                write!(f, "more than {limit} elements and entries")?
+            }
+            DecoderError::TrailingBytes { .. } => f.write_str("trailing bytes after the value")?,
+            DecoderError::WrongType {
+                expected, found, ..
+            } => write!(f, "expected {expected}, found {found}")?,
+        }
+        write!(f, " at offset {}", self.offset())?;
+        if !self.path().segments().is_empty() {
+            write!(f, " in `{}`", self.path())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for DecoderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecoderError::IO { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+pub type DecoderResult<T> = Result<T, DecoderError>;
+
+/// The result of [`Decoder::decode_lenient`]: whatever could be decoded, and the problems
+/// that were skipped over to get it.
+#[derive(Debug)]
+pub struct Recovered {
+    pub value: Bencode,
+    pub diagnostics: Vec<DecoderError>,
+}
+
+/// Settings for a [`Decoder`].
+///
+/// ```
+/// use bencode::{Decoder, DecoderOptions};
+///
+/// let options = DecoderOptions {
+///     lenient: true,
+///     ..DecoderOptions::default()
+/// };
+/// let mut decoder = Decoder::with_options(&b"li1exi2ee"[..], options);
+/// assert_eq!(decoder.decode().unwrap().as_list().unwrap().len(), 2);
+/// assert_eq!(decoder.diagnostics().len(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderOptions {
+    /// Makes [`Decoder::decode`] skip over malformed input the way
+    /// [`Decoder::decode_lenient`] does, leaving the problems in
+    /// [`Decoder::diagnostics`].
+    pub lenient: bool,
+    /// Rejects input that is not in the canonical form BEP 3 requires: integers such as
+    /// `i03e` and `i-0e`, and dictionaries whose keys are not unique and sorted by their
+    /// raw bytes. Off by default, which accepts them.
+    pub strict: bool,
+    /// How deeply lists and dictionaries may be nested, 512 by default.
+    ///
+    /// Nesting does not use up the call stack, but every level of it still has to be
+    /// tracked until it is closed, and many other operations on values recurse.
+    pub max_depth: usize,
+    /// The longest string that may be decoded, if any string is too long.
+    pub max_string_length: Option<u64>,
+    /// How many bytes of strings, in total, one value may hold.
+    pub max_total_bytes: Option<u64>,
+    /// How many list elements and dictionary entries, in total, one value may hold.
+    pub max_elements: Option<usize>,
+    /// How many bytes to read from the reader at a time, 8 KiB by default.
+    ///
+    /// Reading ahead means the decoder may take bytes from the reader beyond the value it
+    /// returns. Set this to 1 to read no further than needed, or use
+    /// [`Decoder::from_buf_read`], which ignores this setting and consumes only what it
+    /// decodes.
+    pub buffer_size: usize,
+}
+
+impl Default for DecoderOptions {
+    fn default() -> Self {
+        DecoderOptions {
+            lenient: false,
+            strict: false,
+            max_depth: 512,
+            max_string_length: None,
+            max_total_bytes: None,
+            max_elements: None,
+            buffer_size: 8 * 1024,
+        }
+    }
+}
+
+/// Decodes [`Bencode`] values from an [`io::Read`].
+///
+/// A decoder made with [`new`](Decoder::new) reads through a buffer of its own, of
+/// [`DecoderOptions::buffer_size`] bytes, so that a [`File`](std::fs::File) or
+/// [`TcpStream`](std::net::TcpStream) does not need wrapping in a
+/// [`BufReader`](io::BufReader). One made with [`from_buf_read`](Decoder::from_buf_read)
+/// uses the reader's own buffer instead.
+///
+/// ```
+/// use bencode::{bencode, Decoder};
+///
+/// let mut decoder = Decoder::new(&b"d4:spaml1:a1:bee"[..]);
+/// assert_eq!(decoder.decode().unwrap(), bencode!({ "spam": ["a", "b"] }));
+/// ```
+pub struct Decoder<R> {
+    input: Input<R>,
+    /// How many bytes have been consumed.
+    offset: usize,
+    /// Where the value being decoded sits in the document.
+    path: Path,
+    options: DecoderOptions,
+    /// The problems skipped over so far, when decoding leniently.
+    diagnostics: Option<Vec<DecoderError>>,
+    /// The problems skipped over by the last lenient call to [`decode`](Decoder::decode).
+    skipped: Vec<DecoderError>,
+    /// Whether the input ran out during lenient decoding.
+    truncated: bool,
+    /// How many bytes of strings the value being decoded holds so far.
+    string_bytes: u64,
+    /// How many list elements and dictionary entries the value being decoded holds so far.
+    elements: usize,
+    /// Whether iterating has failed, leaving the input somewhere no value can start.
+    failed: bool,
+    /// Buffers left over from the value passed to [`decode_into`](Decoder::decode_into),
+    /// to decode the next one into.
+    spare: Spare,
+    /// The offset [`rollback`](Decoder::rollback) goes back to.
+    checkpoint: usize,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(reader: R) -> Self {
+        Decoder::with_options(reader, DecoderOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: DecoderOptions) -> Self {
+        Decoder::with_input(Input::new(reader, options.buffer_size), options)
+    }
+
+    /// Creates a decoder that reads straight out of `reader`'s buffer.
+    ///
+    /// ```
+    /// use std::io::BufReader;
+    ///
+    /// use bencode::{bencode, Decoder, DecoderOptions};
+    ///
+    /// let reader = BufReader::new(&b"d4:spaml1:a1:bee"[..]);
+    /// let mut decoder = Decoder::from_buf_read(reader, DecoderOptions::default());
+    /// assert_eq!(decoder.decode().unwrap(), bencode!({ "spam": ["a", "b"] }));
+    /// ```
+    pub fn from_buf_read(reader: R, options: DecoderOptions) -> Self
+    where
+        R: BufRead,
+    {
+        Decoder::with_input(Input::with_buf_read(reader), options)
+    }
+
+    fn with_input(input: Input<R>, options: DecoderOptions) -> Self {
+        Decoder {
+            input,
+            offset: 0,
+            path: Path::root(),
+            options,
+            diagnostics: None,
+            skipped: Vec::new(),
+            truncated: false,
+            string_bytes: 0,
+            elements: 0,
+            failed: false,
+            spare: Spare::default(),
+            checkpoint: 0,
+        }
+    }
+
+    pub fn options(&self) -> &DecoderOptions {
+        &self.options
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.input.get_ref()
+    }
+
+    /// Returns the reader. Reading from it directly skips over whatever the decoder has
+    /// buffered.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.input.get_mut()
+    }
+
+    /// Returns the reader, which carries on from where the decoder's
+    /// [`buffer`](Decoder::buffer) ends.
+    ///
+    /// ```
+    /// use std::io::Read;
+    ///
+    /// use bencode::Decoder;
+    ///
+    /// // A ut_metadata piece: a header dictionary followed by raw data.
+    /// let message = b"d8:msg_typei1e5:piecei0ee<raw piece data>";
+    /// let mut decoder = Decoder::new(&message[..]);
+    /// decoder.decode().unwrap();
+    /// let mut piece = decoder.buffer().to_vec();
+    /// decoder.into_inner().read_to_end(&mut piece).unwrap();
+    /// assert_eq!(piece, b"<raw piece data>");
+    /// ```
+    pub fn into_inner(self) -> R {
+        self.input.into_inner()
+    }
+
+    /// The bytes the decoder has read from the reader but not yet decoded.
+    ///
+    /// This is always empty with a [`buffer_size`](DecoderOptions::buffer_size) of 1, or
+    /// for a decoder made with [`from_buf_read`](Decoder::from_buf_read), whose reader
+    /// keeps those bytes in its own buffer.
+    pub fn buffer(&self) -> &[u8] {
+        self.input.buffer()
+    }
+
+    /// How many bytes of the input have been decoded so far.
+    ///
+    /// Right after [`decode`](Decoder::decode) returns a value, this is where the value
+    /// ended. With a [`buffer_size`](DecoderOptions::buffer_size) of 1, or a decoder made
+    /// with [`from_buf_read`](Decoder::from_buf_read), nothing past it has been taken from
+    /// the reader either, so whatever follows can be read from there directly.
+    ///
+    /// ```
+    /// use bencode::Decoder;
+    ///
+    /// // A ut_metadata piece: a header dictionary followed by raw data.
+    /// let message = b"d8:msg_typei1e5:piecei0ee<raw piece data>";
+    /// let mut decoder = Decoder::new(&message[..]);
+    /// decoder.decode().unwrap();
+    /// assert_eq!(&message[decoder.bytes_consumed()..], b"<raw piece data>");
+    /// ```
+    pub fn bytes_consumed(&self) -> usize {
+        self.offset
+    }
+
+    /// Decodes the next value.
+    pub fn decode(&mut self) -> DecoderResult<Bencode> {
+        if self.options.lenient {
+            self.skipped.clear();
+            let recovered = self.decode_lenient()?;
+            self.skipped = recovered.diagnostics;
+            return Ok(recovered.value);
+        }
+        self.begin();
+        self.decode_current()
+    }
+
+    /// Decodes the next value into `value`, reusing the strings and lists it holds
+    /// instead of allocating new ones where it can.
+    ///
+    /// Decoding message after message into the same value this way saves most of the
+    /// allocations [`decode`](Decoder::decode) would make, once the messages settle into
+    /// a similar shape. If decoding fails, `value` is left as [`Bencode::default`].
+    ///
+    /// ```
+    /// use bencode::{bencode, Bencode, Decoder};
+    ///
+    /// let mut decoder = Decoder::new(&b"d1:y1:qed1:y1:re"[..]);
+    /// let mut message = Bencode::default();
+    /// decoder.decode_into(&mut message).unwrap();
+    /// assert_eq!(message, bencode!({ "y": "q" }));
+    /// decoder.decode_into(&mut message).unwrap();
+    /// assert_eq!(message, bencode!({ "y": "r" }));
+    /// ```
+    pub fn decode_into(&mut self, value: &mut Bencode) -> DecoderResult<()> {
+        self.spare.take_apart(mem::take(value));
+        let result = self.decode();
+        // Whatever was not reused is not kept past this value.
+        self.spare = Spare::default();
+        *value = result?;
+        Ok(())
+    }
+
+    /// Decodes the next value along with the range of input bytes it, and each value
+    /// inside it, was decoded from.
+    ///
+    /// Unlike [`decode`](Decoder::decode), this never skips over malformed input, even
+    /// when [`DecoderOptions::lenient`] is set.
+    ///
+    /// ```
+    /// use bencode::Decoder;
+    ///
+    /// let spanned = Decoder::new(&b"d4:name5:a.txt6:lengthi5ee"[..]).decode_spanned().unwrap();
+    /// assert_eq!(spanned.span, 0..26);
+    /// assert_eq!(spanned.get("length").unwrap().span, 22..25);
+    /// ```
+    pub fn decode_spanned(&mut self) -> DecoderResult<Spanned> {
+        self.begin();
+        self.decode_current()
+    }
+
+    /// Decodes the next value, passing the contents of each string of at least
+    /// `threshold` bytes to `on_chunk` a piece at a time rather than holding them in
+    /// memory.
+    ///
+    /// Each such string is left empty in the value returned, and `on_chunk` is called
+    /// with the path to it along with each piece, so that strings of any size can be
+    /// written out or hashed as they arrive. Dictionary keys are never streamed, and
+    /// streamed strings still count against the limits in [`DecoderOptions`]. An error
+    /// returned by `on_chunk` stops decoding and is reported as [`DecoderError::IO`].
+    /// Like [`decode_spanned`](Decoder::decode_spanned), this never skips over malformed
+    /// input.
+    ///
+    /// ```
+    /// use std::io::Write;
+    ///
+    /// use bencode::{bencode, Decoder};
+    ///
+    /// let mut decoder = Decoder::new(&b"d4:name5:a.txt6:pieces12:abcdefghijkle"[..]);
+    /// let mut pieces = Vec::new();
+    /// let value = decoder
+    ///     .decode_streaming(10, |path, chunk| {
+    ///         assert_eq!(path.to_string(), "pieces");
+    ///         pieces.write_all(chunk)
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(value, bencode!({ "name": "a.txt", "pieces": "" }));
+    /// assert_eq!(pieces, b"abcdefghijkl");
+    /// ```
+    pub fn decode_streaming(
+        &mut self,
+        threshold: u64,
+        mut on_chunk: impl FnMut(&Path, &[u8]) -> io::Result<()>,
+    ) -> DecoderResult<Bencode> {
+        self.begin();
+        let mut sink = StringSink {
+            threshold,
+            on_chunk: &mut on_chunk,
+        };
+        let value = self.decode_value(Expected::Value, Some(&mut sink))?;
+        Ok(value.expect("values are only skipped when decoding leniently"))
+    }
+
+    /// Decodes the next value, failing if the input does not end right after it.
+    ///
+    /// ```
+    /// use bencode::{Decoder, DecoderError};
+    ///
+    /// assert!(Decoder::new(&b"i42e"[..]).decode_complete().is_ok());
+    /// assert!(matches!(
+    ///     Decoder::new(&b"i42ei43e"[..]).decode_complete(),
+    ///     Err(DecoderError::TrailingBytes { offset: 4, .. })
+    /// ));
+    /// ```
+    pub fn decode_complete(&mut self) -> DecoderResult<Bencode> {
+        let value = self.decode()?;
+        self.expect_end()?;
+        Ok(value)
+    }
+
+    /// Decodes values one after another until the input runs out.
+    ///
+    /// Input that ends between two values is not an error, but input that ends in the
+    /// middle of one is.
+    ///
+    /// ```
+    /// use bencode::{bencode, Decoder};
+    ///
+    /// let mut decoder = Decoder::new(&b"d1:ti1eed1:ti2ee"[..]);
+    /// assert_eq!(
+    ///     decoder.decode_all().unwrap(),
+    ///     [bencode!({ "t": 1 }), bencode!({ "t": 2 })],
+    /// );
+    /// assert!(Decoder::new(&b"i1ei2"[..]).decode_all().is_err());
+    /// ```
+    pub fn decode_all(&mut self) -> DecoderResult<Vec<Bencode>> {
+        let mut values = Vec::new();
+        while !self.at_end()? {
+            values.push(self.decode()?);
+        }
+        Ok(values)
+    }
+
+    /// Looks at what kind of value comes next, without consuming any of it.
+    ///
+    /// ```
+    /// use bencode::{BencodeKind, Decoder};
+    ///
+    /// let mut decoder = Decoder::new(&b"d1:y1:qe"[..]);
+    /// assert_eq!(decoder.peek_type().unwrap(), BencodeKind::Dictionary);
+    /// assert!(decoder.decode().unwrap().as_dictionary().is_some());
+    /// ```
+    pub fn peek_type(&mut self) -> DecoderResult<BencodeKind> {
+        match self.peek(Expected::Value)? {
+            b'i' => Ok(BencodeKind::Integer),
+            b'0'..=b'9' => Ok(BencodeKind::String),
+            b'l' => Ok(BencodeKind::List),
+            b'd' => Ok(BencodeKind::Dictionary),
+            byte => Err(self.unexpected_byte(byte, Expected::Value)),
+        }
+    }
+
+    /// Decodes the next value, which must be a dictionary, as torrent files and KRPC
+    /// messages are.
+    ///
+    /// Anything else is rejected as [`DecoderError::WrongType`] from its first byte,
+    /// without being decoded.
+    ///
+    /// ```
+    /// use bencode::{BencodeKind, Decoder, DecoderError};
+    ///
+    /// let message = Decoder::new(&b"d1:y1:qe"[..]).decode_dictionary().unwrap();
+    /// assert!(message.contains_key(&b"y"[..]));
+    /// assert!(matches!(
+    ///     Decoder::new(&b"l1:ye"[..]).decode_dictionary(),
+    ///     Err(DecoderError::WrongType { found: BencodeKind::List, .. })
+    /// ));
+    /// ```
+    pub fn decode_dictionary(&mut self) -> DecoderResult<BTreeMap<Vec<u8>, Bencode>> {
+        let value = self.decode_kind(BencodeKind::Dictionary)?;
+        Ok(value
+            .into_dictionary()
+            .expect("the value starts as a dictionary"))
+    }
+
+    /// Decodes the next value, which must be a list; see
+    /// [`decode_dictionary`](Decoder::decode_dictionary).
+    pub fn decode_list(&mut self) -> DecoderResult<Vec<Bencode>> {
+        let value = self.decode_kind(BencodeKind::List)?;
+        Ok(value.into_list().expect("the value starts as a list"))
+    }
+
+    /// Decodes the next value, which must be a string; see
+    /// [`decode_dictionary`](Decoder::decode_dictionary).
+    pub fn decode_string(&mut self) -> DecoderResult<Vec<u8>> {
+        let value = self.decode_kind(BencodeKind::String)?;
+        Ok(value.into_string().expect("the value starts as a string"))
+    }
+
+    /// Decodes the next value, which must be an integer; see
+    /// [`decode_dictionary`](Decoder::decode_dictionary). One too large for an `i128` is
+    /// reported as [`DecoderError::IntegerOverflow`], even with the `num-bigint` feature.
+    pub fn decode_integer(&mut self) -> DecoderResult<i128> {
+        let start = self.offset;
+        let value = self.decode_kind(BencodeKind::Integer)?;
+        value.into_integer().ok_or(DecoderError::IntegerOverflow {
+            offset: start + 1,
+            path: Path::root(),
+        })
+    }
+
+    /// Decodes the next value, failing before decoding it if it is not an `expected`.
+    fn decode_kind(&mut self, expected: BencodeKind) -> DecoderResult<Bencode> {
+        let offset = self.offset;
+        let found = self.peek_type()?;
+        if found != expected {
+            return Err(DecoderError::WrongType {
+                expected,
+                found,
+                offset,
+                path: Path::root(),
+            });
+        }
+        self.decode()
+    }
+
+    /// Consumes the next value without decoding it.
+    ///
+    /// Strings are passed over rather than read into memory, and nothing is built, so
+    /// this is a cheap way past large fields that are not needed. The value is still
+    /// checked to be well formed, but not against the [`DecoderOptions`].
+    ///
+    /// ```
+    /// use bencode::{bencode, Decoder};
+    ///
+    /// let mut decoder = Decoder::new(&b"d6:pieces20:aaaaaaaaaaaaaaaaaaaaei42e"[..]);
+    /// decoder.skip_value().unwrap();
+    /// assert_eq!(decoder.decode().unwrap(), bencode!(42));
+    /// ```
+    pub fn skip_value(&mut self) -> DecoderResult<()> {
+        self.write_raw_value(&mut io::sink())
+    }
+
+    /// Consumes the next value, returning it exactly as it was encoded.
+    ///
+    /// This is what an infohash is computed over, since re-encoding the decoded `info`
+    /// dictionary would change it if the original was not canonical. Like
+    /// [`skip_value`](Decoder::skip_value), the value is checked to be well formed, but
+    /// not against the [`DecoderOptions`].
+    ///
+    /// ```
+    /// use bencode::Decoder;
+    ///
+    /// let mut decoder = Decoder::new(&b"li03e2:abei1e"[..]);
+    /// assert_eq!(decoder.raw_value().unwrap(), b"li03e2:abe");
+    /// ```
+    pub fn raw_value(&mut self) -> DecoderResult<Vec<u8>> {
+        let mut raw = Vec::new();
+        self.write_raw_value(&mut raw)?;
+        Ok(raw)
+    }
+
+    /// Consumes the next value, writing it to `writer` exactly as it was encoded.
+    ///
+    /// See [`raw_value`](Decoder::raw_value). A failure to write is reported as
+    /// [`DecoderError::IO`].
+    pub fn write_raw_value<W: Write + ?Sized>(&mut self, writer: &mut W) -> DecoderResult<()> {
+        self.begin();
+        let mut open = Vec::new();
+        loop {
+            let expected = match open.last() {
+                None | Some(Copying::Value) => Expected::Value,
+                Some(Copying::List) => Expected::ValueOrEnd,
+                Some(Copying::Key) => Expected::KeyOrEnd,
+            };
+            match self.peek(expected)? {
+                b'e' if matches!(open.last(), Some(Copying::List | Copying::Key)) => {
+                    self.advance(expected)?;
+                    self.write(writer, b"e")?;
+                    open.pop();
+                }
+                b'0'..=b'9' => {
+                    let start = self.offset;
+                    let digits = self.read_digits(b':')?;
+                    let length = self.parse_length(&digits, start)?;
+                    self.write(writer, digits.as_bytes())?;
+                    self.write(writer, b":")?;
+                    self.copy_bytes(length, writer)?;
+                }
+                byte if open.last() == Some(&Copying::Key) => {
+                    return Err(self.unexpected_byte(byte, expected));
+                }
+                b'i' => {
+                    self.advance(expected)?;
+                    let digits = self.read_digits(b'e')?;
+                    self.write(writer, b"i")?;
+                    self.write(writer, digits.as_bytes())?;
+                    self.write(writer, b"e")?;
+                }
+                byte @ (b'l' | b'd') => {
+                    self.advance(expected)?;
+                    self.write(writer, &[byte])?;
+                    open.push(if byte == b'l' {
+                        Copying::List
+                    } else {
+                        Copying::Key
+                    });
+                    continue;
+                }
+                byte => return Err(self.unexpected_byte(byte, expected)),
+            }
+            // A value has been copied; a dictionary goes on to its next key or value.
+            match open.last_mut() {
+                None => return Ok(()),
+                Some(Copying::List) => {}
+                Some(top @ Copying::Key) => *top = Copying::Value,
+                Some(top @ Copying::Value) => *top = Copying::Key,
+            }
+        }
+    }
+
+    /// Writes bytes of a raw value to `writer`.
+    fn write<W: Write + ?Sized>(&self, writer: &mut W, bytes: &[u8]) -> DecoderResult<()> {
+        writer
+            .write_all(bytes)
+            .map_err(|source| self.io_error(source))
+    }
+
+    /// The problems skipped over by the last call to [`decode`](Decoder::decode), when
+    /// [`DecoderOptions::lenient`] is set.
+    pub fn diagnostics(&self) -> &[DecoderError] {
+        &self.skipped
+    }
+
+    /// Decodes the next value, skipping over whatever is malformed instead of failing.
+    ///
+    /// Elements that cannot be decoded are left out of their list or dictionary, stray
+    /// bytes are passed over, and containers cut short by the end of the input are closed
+    /// where they end. Each problem is reported in [`Recovered::diagnostics`]. Only a
+    /// failure to read, or input with no value in it at all, is an error.
+    ///
+    /// ```
+    /// use bencode::{bencode, Decoder};
+    ///
+    /// let recovered = Decoder::new(&b"d1:ali1ei2x3ei4ee1:bi5e"[..]).decode_lenient().unwrap();
+    /// assert_eq!(recovered.value, bencode!({ "a": [1, 4], "b": 5 }));
+    /// assert_eq!(recovered.diagnostics.len(), 3);
+    /// ```
+    pub fn decode_lenient(&mut self) -> DecoderResult<Recovered> {
+        self.begin();
+        self.diagnostics = Some(Vec::new());
+        self.truncated = false;
+        let result = loop {
+            match self.decode_value(Expected::Value, None) {
+                Ok(Some(value)) => break Ok(value),
+                Ok(None) if !self.truncated => continue,
+                // Nothing but garbage before the end of the input.
+                Ok(None) => {
+                    break Err(self
+                        .diagnostics
+                        .as_mut()
+                        .and_then(Vec::pop)
+                        .expect("running out of input is recorded"))
+                }
+                Err(error) => break Err(error),
+            }
+        };
+        let diagnostics = self.diagnostics.take().unwrap_or_default();
+        result.map(|value| Recovered { value, diagnostics })
+    }
+
+    /// Whether the input has run out, rather than going on to another value.
+    pub(crate) fn at_end(&mut self) -> DecoderResult<bool> {
+        match self.peek(Expected::Value) {
+            Ok(_) => Ok(false),
+            Err(DecoderError::UnexpectedEof { .. }) => Ok(true),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Fails unless the input has run out.
+    pub(crate) fn expect_end(&mut self) -> DecoderResult<()> {
+        if !self.at_end()? {
+            return Err(DecoderError::TrailingBytes {
+                offset: self.offset,
+                path: self.path.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Resets the state kept for each value decoded.
+    pub(crate) fn begin(&mut self) {
+        self.path = Path::root();
+        self.string_bytes = 0;
+        self.elements = 0;
+    }
+
+    /// Looks at the next byte without consuming it, failing at the end of the input.
+    pub(crate) fn peek(&mut self, expected: Expected) -> DecoderResult<u8> {
+        match self.input.fill(|available| available.first().copied()) {
+            Ok(Some(byte)) => Ok(byte),
+            Ok(None) => Err(DecoderError::UnexpectedEof {
+                expected,
+                offset: self.offset,
+                path: self.path.clone(),
+            }),
+            Err(source) => Err(self.io_error(source)),
+        }
+    }
+
+    /// Consumes the next byte.
+    pub(crate) fn advance(&mut self, expected: Expected) -> DecoderResult<u8> {
+        let byte = self.peek(expected)?;
+        self.input.consume(1);
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    /// Returns the bytes buffered so far, which should be `expected`, without consuming
+    /// them.
+    pub(crate) fn buffered(&mut self, expected: Expected) -> DecoderResult<&[u8]> {
+        match self.input.fill_buf() {
+            Ok([]) => Err(DecoderError::UnexpectedEof {
+                expected,
+                offset: self.offset,
+                path: self.path.clone(),
+            }),
+            Ok(available) => Ok(available),
+            Err(source) => Err(DecoderError::IO {
+                offset: self.offset,
+                path: self.path.clone(),
+                source,
+            }),
+        }
+    }
+
+    /// Consumes `amount` of the bytes returned by [`buffered`](Decoder::buffered).
+    pub(crate) fn consume(&mut self, amount: usize) {
+        self.input.consume(amount);
+        self.offset += amount;
+    }
+
+    /// The path of the value being decoded, for callers that walk the input themselves.
+    pub(crate) fn path_mut(&mut self) -> &mut Path {
+        &mut self.path
+    }
+
+    fn io_error(&self, source: io::Error) -> DecoderError {
+        DecoderError::IO {
+            offset: self.offset,
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    pub(crate) fn unexpected_byte(&self, byte: u8, expected: Expected) -> DecoderError {
+        DecoderError::UnexpectedByte {
+            byte,
+            expected,
+            offset: self.offset,
+            path: self.path.clone(),
+        }
+    }
+
+    fn decode_current<N: Node>(&mut self) -> DecoderResult<N> {
+        let value = self.decode_value(Expected::Value, None)?;
+        Ok(value.expect("values are only skipped when decoding leniently"))
+    }
+
+    /// Decodes the value starting at the next byte, which should be `expected`.
+    ///
+    /// Lists and dictionaries are kept on a stack of the ones still open rather than
+    /// decoded by recursion, so however deeply the input nests, the call stack does not
+    /// grow with it.
+    ///
+    /// Long strings are passed to `sink` instead, if there is one.
+    ///
+    /// Returns `None` if the value was malformed and has been skipped over.
+    fn decode_value<N: Node>(
+        &mut self,
+        mut expected: Expected,
+        mut sink: Option<&mut StringSink<'_>>,
+    ) -> DecoderResult<Option<N>> {
+        let mut open = Vec::new();
+        loop {
+            let start = self.offset;
+            let mut finished = match self.peek(expected) {
+                Ok(b'l') => {
+                    let list = N::empty_list(&mut self.spare);
+                    self.open(&mut open, Container::List(list))?;
+                    None
+                }
+                Ok(b'd') => {
+                    self.open(&mut open, Container::Dictionary(DictionaryState::new()))?;
+                    None
+                }
+                peeked => {
+                    let value = self.decode_scalar(peeked, expected, sink.as_deref_mut())?;
+                    Some(value.map(|value| N::scalar(value, start..self.offset)))
+                }
+            };
+            // Hand the finished value to the container it belongs in, closing containers
+            // until one of them has another value to decode.
+            loop {
+                let Some((_, container)) = open.last_mut() else {
+                    return Ok(finished.flatten());
+                };
+                if let Some(value) = finished.take() {
+                    self.path.pop();
+                    container.insert(value);
+                }
+                match self.next_element(container)? {
+                    Some(next) => {
+                        expected = next;
+                        break;
+                    }
+                    None => {
+                        let (start, container) =
+                            open.pop().expect("the container was just looked at");
+                        finished = Some(Some(container.into_value(start..self.offset)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decodes the integer or string starting with `peeked`, which should be `expected`.
+    ///
+    /// Returns `None` if the value was malformed and has been skipped over.
+    fn decode_scalar(
+        &mut self,
+        peeked: DecoderResult<u8>,
+        expected: Expected,
+        sink: Option<&mut StringSink<'_>>,
+    ) -> DecoderResult<Option<Bencode>> {
+        let result = match peeked {
+            Ok(b'i') => self.read_integer(),
+            Ok(b'0'..=b'9') => self.read_string(sink).map(Bencode::String),
+            Ok(byte) => {
+                let error = self.unexpected_byte(byte, expected);
+                self.advance(expected)?;
+                Err(error)
+            }
+            Err(error) => Err(error),
+        };
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(error) => {
+                // Step past a stray byte in the middle of a value, so that it is not
+                // reported again by the enclosing container.
+                if let DecoderError::UnexpectedByte { offset, .. } = error {
+                    if self.diagnostics.is_some() && offset == self.offset {
+                        self.advance(expected)?;
+                    }
+                }
+                self.recover(error).map(|()| None)
+            }
+        }
+    }
+
+    /// When decoding leniently, records `error` so that decoding can carry on past it;
+    /// otherwise, or if the error is not one that can be skipped, returns it.
+    fn recover(&mut self, error: DecoderError) -> DecoderResult<()> {
+        let Some(diagnostics) = &mut self.diagnostics else {
+            return Err(error);
+        };
+        match error {
+            // Skipping a value too deeply nested to decode would mean decoding it anyway.
+            DecoderError::IO { .. }
+            | DecoderError::DepthLimitExceeded { .. }
+            | DecoderError::StringTooLong { .. }
+            | DecoderError::BudgetExceeded { .. }
+            | DecoderError::TooManyElements { .. } => Err(error),
+            // Every open container runs into the end of the input; report it only once.
+            DecoderError::UnexpectedEof { .. } if self.truncated => Ok(()),
+            DecoderError::UnexpectedEof { .. } => {
+                self.truncated = true;
+                diagnostics.push(error);
+                Ok(())
+            }
+            error => {
+                diagnostics.push(error);
+                Ok(())
+            }
+        }
+    }
+
+    /// Parses the digits read from `start`, which are known to form a number.
+    pub(crate) fn parse_number<T: FromStr>(&self, digits: &str, start: usize) -> DecoderResult<T> {
+        digits.parse().map_err(|_| DecoderError::IntegerOverflow {
+            offset: start,
+            path: self.path.clone(),
+        })
+    }
+
+    /// Reads the digits of an optionally negative number up to and including `terminator`.
+    fn read_digits(&mut self, terminator: u8) -> DecoderResult<String> {
+        let start = self.offset;
+        let expected = Expected::Digit { terminator };
+        let mut digits = String::new();
+        loop {
+            self.peek(expected)?;
+            // Scan as much of the buffer as belongs to the number, and stop at whichever
+            // byte ends it.
+            let (scanned, end) = self
+                .input
+                .fill(|available| {
+                    for (scanned, &byte) in available.iter().enumerate() {
+                        match byte {
+                            b'0'..=b'9' => digits.push(byte as char),
+                            b'-' if digits.is_empty() => digits.push('-'),
+                            byte => return (scanned, Some(byte)),
+                        }
+                    }
+                    (available.len(), None)
+                })
+                .map_err(|source| self.io_error(source))?;
+            self.input.consume(scanned);
+            self.offset += scanned;
+            match end {
+                Some(byte) if byte == terminator => break,
+                Some(byte) => return Err(self.unexpected_byte(byte, expected)),
+                None => {}
+            }
+        }
+        self.advance(expected)?;
+        if digits.is_empty() || digits == "-" {
+            return Err(DecoderError::NAN {
+                offset: start,
+                path: self.path.clone(),
+            });
+        }
+        Ok(digits)
+    }
+
+    /// Decodes an integer, falling back to [`Bencode::BigInteger`] for one too large for
+    /// `i128` when the `num-bigint` feature is enabled.
+    fn read_integer(&mut self) -> DecoderResult<Bencode> {
+        self.advance(Expected::Value)?;
+        let start = self.offset;
+        let digits = self.read_integer_digits()?;
+        #[cfg(feature = "num-bigint")]
+        if digits.parse::<i128>().is_err() {
+            return self.parse_number(&digits, start).map(Bencode::BigInteger);
+        }
+        self.parse_number(&digits, start).map(Bencode::Integer)
+    }
+
+    /// Reads the digits of an integer after its `i`, checking them for leading zeros
+    /// when decoding strictly.
+    pub(crate) fn read_integer_digits(&mut self) -> DecoderResult<String> {
+        let start = self.offset;
+        let digits = self.read_digits(b'e')?;
+        let magnitude = digits.strip_prefix('-').unwrap_or(&digits);
+        if self.options.strict && magnitude.starts_with('0') && digits != "0" {
+            return Err(DecoderError::NonCanonicalInteger {
+                offset: start,
+                path: self.path.clone(),
+            });
+        }
+        Ok(digits)
+    }
+
+    /// Decodes a string, unless it is long enough to go to `sink`, in which case its
+    /// contents are passed there and an empty string is returned in its place.
+    fn read_string(&mut self, sink: Option<&mut StringSink<'_>>) -> DecoderResult<Vec<u8>> {
+        let start = self.offset;
+        let length = self.read_length()?;
+        self.check_string_length(length, start)?;
+        if let Some(sink) = sink.filter(|sink| length >= sink.threshold) {
+            let path = self.path.clone();
+            let mut writer = ChunkWriter {
+                path: &path,
+                on_chunk: &mut *sink.on_chunk,
+            };
+            self.copy_bytes(length, &mut writer)?;
+            return Ok(Vec::new());
+        }
+        // The buffer grows as bytes actually arrive rather than trusting `length` up front.
+        let mut bytes = self.spare.strings.pop().unwrap_or_default();
+        self.copy_bytes(length, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Counts a string of `length` bytes starting at `start` against the limits on strings.
+    pub(crate) fn check_string_length(&mut self, length: u64, start: usize) -> DecoderResult<()> {
+        if let Some(limit) = self.options.max_string_length {
+            if length > limit {
+                return Err(DecoderError::StringTooLong {
+                    length,
+                    limit,
+                    offset: start,
+                    path: self.path.clone(),
+                });
+            }
+        }
+        self.string_bytes = self.string_bytes.saturating_add(length);
+        if let Some(limit) = self.options.max_total_bytes {
+            if self.string_bytes > limit {
+                return Err(DecoderError::BudgetExceeded {
+                    limit,
+                    offset: start,
+                    path: self.path.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the length of a string, up to and including the `:` before its contents.
+    pub(crate) fn read_length(&mut self) -> DecoderResult<u64> {
+        let start = self.offset;
+        let digits = self.read_digits(b':')?;
+        self.parse_length(&digits, start)
+    }
+
+    /// Parses the digits of a string length read from `start`.
+    fn parse_length(&self, digits: &str, start: usize) -> DecoderResult<u64> {
+        let length: i64 = self.parse_number(digits, start)?;
+        u64::try_from(length).map_err(|_| DecoderError::NAN {
+            offset: start,
+            path: self.path.clone(),
+        })
+    }
+
+    /// Copies exactly `length` bytes of string contents into `writer`.
+    fn copy_bytes<W: Write + ?Sized>(&mut self, length: u64, writer: &mut W) -> DecoderResult<()> {
+        let copied = self
+            .input
+            .copy_to(length, writer)
+            .map_err(|source| self.io_error(source))?;
+        self.offset += copied as usize;
+        if copied < length {
+            return Err(DecoderError::UnexpectedEof {
+                expected: Expected::StringBytes {
+                    remaining: length - copied,
+                },
+                offset: self.offset,
+                path: self.path.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Fails if opening another list or dictionary would nest too deeply.
+    pub(crate) fn check_depth(&self) -> DecoderResult<()> {
+        let limit = self.options.max_depth;
+        if self.path.segments().len() >= limit {
+            return Err(DecoderError::DepthLimitExceeded {
+                limit,
+                offset: self.offset,
+                path: self.path.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Counts another list element or dictionary entry, failing if there are too many.
+    pub(crate) fn count_element(&mut self) -> DecoderResult<()> {
+        self.elements += 1;
+        match self.options.max_elements {
+            Some(limit) if self.elements > limit => Err(DecoderError::TooManyElements {
+                limit,
+                offset: self.offset,
+                path: self.path.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Consumes the `l` or `d` opening `container` and pushes it onto the `open` stack.
+    fn open<N: Node>(
+        &mut self,
+        open: &mut Vec<(usize, Container<N>)>,
+        container: Container<N>,
+    ) -> DecoderResult<()> {
+        self.check_depth()?;
+        let start = self.offset;
+        self.advance(Expected::Value)?;
+        open.push((start, container));
+        Ok(())
+    }
+
+    /// Moves on to the next element of `container`, returning what it should start with,
+    /// or `None` once the container has ended.
+    fn next_element<N: Node>(
+        &mut self,
+        container: &mut Container<N>,
+    ) -> DecoderResult<Option<Expected>> {
+        match container {
+            Container::List(list) => self.next_list_element(list.len()),
+            Container::Dictionary(state) => self.next_dictionary_entry(state),
+        }
+    }
+
+    fn next_list_element(&mut self, index: usize) -> DecoderResult<Option<Expected>> {
+        match self.peek(Expected::ValueOrEnd) {
+            Ok(b'e') => {
+                self.advance(Expected::ValueOrEnd)?;
+                Ok(None)
+            }
+            Ok(_) => {
+                self.path.push(Segment::Index(index));
+                self.count_element()?;
+                Ok(Some(Expected::ValueOrEnd))
+            }
+            Err(error) => {
+                self.recover(error)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Checks that `key` sorts after the key before it in the same dictionary, if any.
+    pub(crate) fn check_key_order(
+        &mut self,
+        previous: Option<&[u8]>,
+        key: &[u8],
+        offset: usize,
+    ) -> DecoderResult<()> {
+        let Some(previous) = previous else {
+            return Ok(());
+        };
+        let key_string = || String::from_utf8_lossy(key).into_owned();
+        let path = self.path.clone();
+        match previous.cmp(key) {
+            Ordering::Less => Ok(()),
+            Ordering::Equal => self.recover(DecoderError::DuplicateKey {
+                key: key_string(),
+                offset,
+                path,
+            }),
+            Ordering::Greater => self.recover(DecoderError::UnsortedKey {
+                key: key_string(),
+                offset,
+                path,
+            }),
+        }
+    }
+
+    /// Decodes the next key of a dictionary, leaving it in `state` while its value is
+    /// decoded.
+    fn next_dictionary_entry<N: Node>(
+        &mut self,
+        state: &mut DictionaryState<N>,
+    ) -> DecoderResult<Option<Expected>> {
+        loop {
+            let offset = self.offset;
+            match self.peek(Expected::KeyOrEnd) {
+                Ok(b'e') => {
+                    self.advance(Expected::KeyOrEnd)?;
+                    return Ok(None);
+                }
+                Ok(b'0'..=b'9') => self.count_element()?,
+                Ok(byte) => {
+                    let error = self.unexpected_byte(byte, Expected::KeyOrEnd);
+                    self.advance(Expected::KeyOrEnd)?;
+                    self.recover(error)?;
+                    continue;
+                }
+                Err(error) => {
+                    self.recover(error)?;
+                    return Ok(None);
+                }
+            }
+            let key = match self.read_string(None) {
+                Ok(key) => key,
+                Err(error) => {
+                    self.recover(error)?;
+                    continue;
+                }
+            };
+            if self.options.strict {
+                self.check_key_order(state.previous_key.as_deref(), &key, offset)?;
+                state.previous_key = Some(key.clone());
+            }
+            self.path.push(Segment::Key(key.clone()));
+            state.key = Some(key);
+            return Ok(Some(Expected::Value));
+        }
+    }
+}
+
+impl<R: Read + Seek> Decoder<R> {
+    /// Marks the current position in the input for [`rollback`](Decoder::rollback) to
+    /// return to.
+    pub fn checkpoint(&mut self) {
+        self.checkpoint = self.offset;
+    }
+
+    /// Seeks back to the last [`checkpoint`](Decoder::checkpoint), or to the start of
+    /// the input if there has been none, as though nothing after it had been decoded.
+    ///
+    /// This lets a value that failed to decode, such as one whose end has not been
+    /// written yet, be tried again later. The reader must seek relative to what has been
+    /// consumed of it, as a [`BufReader`](io::BufReader) does. If seeking fails, the
+    /// decoder's position is unknown and it should not be used further.
+    ///
+    /// ```
+    /// use std::io::Cursor;
+    ///
+    /// use bencode::{bencode, Decoder};
+    ///
+    /// let mut decoder = Decoder::new(Cursor::new(b"i1ed1:y".to_vec()));
+    /// decoder.decode().unwrap();
+    /// decoder.checkpoint();
+    /// assert!(decoder.decode().is_err());
+    ///
+    /// decoder.rollback().unwrap();
+    /// decoder.get_mut().get_mut().extend_from_slice(b"1:qe");
+    /// assert_eq!(decoder.decode().unwrap(), bencode!({ "y": "q" }));
+    /// ```
+    pub fn rollback(&mut self) -> io::Result<()> {
+        self.input
+            .seek_back((self.offset - self.checkpoint) as u64)?;
+        self.offset = self.checkpoint;
+        self.path = Path::root();
+        self.failed = false;
+        Ok(())
+    }
+}
+
+impl<I: Iterator<Item = u8>> Decoder<ByteReader<I>> {
+    /// Creates a decoder over the bytes `bytes` yields, for input that is not an
+    /// [`io::Read`].
+    ///
+    /// ```
+    /// use bencode::{bencode, Decoder, DecoderOptions};
+    ///
+    /// let message = b"d1:y1:qe".iter().copied();
+    /// let mut decoder = Decoder::from_bytes(message, DecoderOptions::default());
+    /// assert_eq!(decoder.decode().unwrap(), bencode!({ "y": "q" }));
+    /// ```
+    pub fn from_bytes(bytes: impl IntoIterator<IntoIter = I>, options: DecoderOptions) -> Self {
+        Decoder::with_options(ByteReader(bytes.into_iter()), options)
+    }
+}
+
+impl<I: Iterator<Item = io::Result<u8>>> Decoder<ByteResultReader<I>> {
+    /// Creates a decoder over the bytes `bytes` yields, reporting the errors it yields
+    /// as [`DecoderError::IO`].
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use bencode::{Decoder, DecoderError, DecoderOptions};
+    ///
+    /// let bytes = b"li1e".iter().copied().map(Ok).chain([Err(io::ErrorKind::Other.into())]);
+    /// let mut decoder = Decoder::from_byte_results(bytes, DecoderOptions::default());
+    /// assert!(matches!(decoder.decode(), Err(DecoderError::IO { offset: 4, .. })));
+    /// ```
+    pub fn from_byte_results(
+        bytes: impl IntoIterator<IntoIter = I>,
+        options: DecoderOptions,
+    ) -> Self {
+        Decoder::with_options(
+            ByteResultReader {
+                bytes: bytes.into_iter(),
+                error: None,
+            },
+            options,
+        )
+    }
+}
+
+/// A [`BufRead`] that also writes every byte consumed from it to an observer, such as a
+/// hasher, so that a [`Decoder`] made with [`from_buf_read`](Decoder::from_buf_read) can
+/// hash or archive the exact bytes it decodes as it goes.
+///
+/// Only bytes the decoder has consumed reach the observer, never those it has merely
+/// looked at. A failure to write to the observer is returned by the next read.
+///
+/// ```
+/// use std::io::BufReader;
+///
+/// use bencode::decoder::Tee;
+/// use bencode::{Decoder, DecoderOptions};
+///
+/// let input = b"d4:name5:a.txte<rest of the stream>";
+/// let reader = Tee::new(BufReader::new(&input[..]), Vec::new());
+/// let mut decoder = Decoder::from_buf_read(reader, DecoderOptions::default());
+/// decoder.decode().unwrap();
+/// assert_eq!(decoder.into_inner().into_observer(), b"d4:name5:a.txte");
+/// ```
+pub struct Tee<R, W> {
+    reader: R,
+    observer: W,
+    /// A failure to write to the observer while consuming, kept to be returned later.
+    error: Option<io::Error>,
+}
+
+impl<R: BufRead, W: Write> Tee<R, W> {
+    pub fn new(reader: R, observer: W) -> Self {
+        Tee {
+            reader,
+            observer,
+            error: None,
+        }
+    }
+
+    pub fn observer(&self) -> &W {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut W {
+        &mut self.observer
+    }
+
+    pub fn into_observer(self) -> W {
+        self.observer
+    }
+
+    pub fn into_parts(self) -> (R, W) {
+        (self.reader, self.observer)
+    }
+}
+
+impl<R: BufRead, W: Write> Read for Tee<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let amount = available.len().min(buf.len());
+        buf[..amount].copy_from_slice(&available[..amount]);
+        self.consume(amount);
+        Ok(amount)
+    }
+}
+
+impl<R: BufRead, W: Write> BufRead for Tee<R, W> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        // The bytes being consumed are still buffered, so this does not read.
+        if let Ok(available) = self.reader.fill_buf() {
+            let consumed = &available[..amount.min(available.len())];
+            if let Err(error) = self.observer.write_all(consumed) {
+                self.error.get_or_insert(error);
+            }
+        }
+        self.reader.consume(amount);
+    }
+}
+
+/// Reads the bytes an iterator yields, for [`Decoder::from_bytes`].
+pub struct ByteReader<I>(I);
+
+impl<I: Iterator<Item = u8>> Read for ByteReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        for (slot, byte) in buf.iter_mut().zip(&mut self.0) {
+            *slot = byte;
+            read += 1;
+        }
+        Ok(read)
+    }
+}
+
+/// Reads the bytes an iterator yields, for [`Decoder::from_byte_results`].
+pub struct ByteResultReader<I> {
+    bytes: I,
+    /// An error that came after some bytes, to be returned once they have been read.
+    error: Option<io::Error>,
+}
+
+impl<I: Iterator<Item = io::Result<u8>>> Read for ByteResultReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        let mut read = 0;
+        for slot in buf.iter_mut() {
+            match self.bytes.next() {
+                Some(Ok(byte)) => *slot = byte,
+                Some(Err(error)) if read == 0 => return Err(error),
+                Some(Err(error)) => {
+                    self.error = Some(error);
+                    break;
+                }
+                None => break,
+            }
+            read += 1;
+        }
+        Ok(read)
+    }
+}
+
+/// Yields each value in the input until it runs out, as [`Decoder::decode_all`] does.
+///
+/// Iteration ends after the first error, since there is no telling where the next value
+/// would start.
+///
+/// ```
+/// use bencode::Decoder;
+///
+/// let mut messages = 0;
+/// for message in Decoder::new(&b"d1:y1:qed1:y1:re"[..]) {
+///     assert!(message.unwrap()["y"].as_str().is_some());
+///     messages += 1;
+/// }
+/// assert_eq!(messages, 2);
+/// ```
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = DecoderResult<Bencode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = match self.at_end() {
+            Ok(true) => return None,
+            Ok(false) => self.decode(),
+            Err(error) => Err(error),
+        };
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// What comes next in a list or dictionary being copied by
+/// [`Decoder::write_raw_value`].
+#[derive(PartialEq)]
+enum Copying {
+    List,
+    Key,
+    Value,
+}
+
+/// Where [`Decoder::decode_streaming`] sends the contents of long strings.
+struct StringSink<'s> {
+    threshold: u64,
+    on_chunk: &'s mut dyn FnMut(&Path, &[u8]) -> io::Result<()>,
+}
+
+/// Passes what is written to it on to a [`StringSink`]'s callback.
+struct ChunkWriter<'a, 's> {
+    path: &'a Path,
+    on_chunk: &'a mut (dyn FnMut(&Path, &[u8]) -> io::Result<()> + 's),
+}
+
+impl Write for ChunkWriter<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (self.on_chunk)(self.path, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// What the decoder builds: plain [`Bencode`] values, or [`Spanned`] ones.
+pub(crate) trait Node: Sized {
+    /// Wraps an integer or string that was decoded from `span`.
+    fn scalar(value: Bencode, span: Range<usize>) -> Self;
+    fn list(list: Vec<Self>, span: Range<usize>) -> Self;
+    fn dictionary(dictionary: BTreeMap<Vec<u8>, Self>, span: Range<usize>) -> Self;
+
+    /// A list to decode elements into.
+    fn empty_list(_spare: &mut Spare) -> Vec<Self> {
+        Vec::new()
+    }
+}
+
+impl Node for Bencode {
+    fn scalar(value: Bencode, _: Range<usize>) -> Self {
+        value
+    }
+
+    fn list(list: Vec<Self>, _: Range<usize>) -> Self {
+        Bencode::List(list)
+    }
+
+    fn dictionary(dictionary: BTreeMap<Vec<u8>, Self>, _: Range<usize>) -> Self {
+        Bencode::Dictionary(dictionary)
+    }
+
+    fn empty_list(spare: &mut Spare) -> Vec<Self> {
+        spare.lists.pop().unwrap_or_default()
+    }
+}
+
+/// Emptied buffers to decode into, for [`Decoder::decode_into`].
+#[derive(Default)]
+pub(crate) struct Spare {
+    strings: Vec<Vec<u8>>,
+    lists: Vec<Vec<Bencode>>,
+}
+
+impl Spare {
+    /// Empties the strings and lists inside `value` and keeps them.
+    fn take_apart(&mut self, value: Bencode) {
+        let mut pending = vec![value];
+        while let Some(value) = pending.pop() {
+            match value {
+                Bencode::String(mut bytes) => {
+                    bytes.clear();
+                    self.strings.push(bytes);
+                }
+                Bencode::List(mut list) => {
+                    pending.append(&mut list);
+                    self.lists.push(list);
+                }
+                Bencode::Dictionary(dictionary) => {
+                    for (mut key, value) in dictionary {
+                        key.clear();
+                        self.strings.push(key);
+                        pending.push(value);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A list or dictionary whose elements are still being decoded.
+enum Container<N> {
+    List(Vec<N>),
+    Dictionary(DictionaryState<N>),
+}
+
+struct DictionaryState<N> {
+    dictionary: BTreeMap<Vec<u8>, N>,
+    /// The key before the current one, kept to check the order of keys when decoding
+    /// strictly.
+    previous_key: Option<Vec<u8>>,
+    /// The key whose value is being decoded.
+    key: Option<Vec<u8>>,
+}
+
+impl<N> DictionaryState<N> {
+    fn new() -> Self {
+        DictionaryState {
+            dictionary: BTreeMap::new(),
+            previous_key: None,
+            key: None,
+        }
+    }
+}
+
+impl<N: Node> Container<N> {
+    /// Adds the element just decoded, unless it was skipped over.
+    fn insert(&mut self, value: Option<N>) {
+        match self {
+            Container::List(list) => list.extend(value),
+            Container::Dictionary(state) => {
+                let key = state
+                    .key
+                    .take()
+                    .expect("values are decoded after their key");
+                if let Some(value) = value {
+                    state.dictionary.insert(key, value);
+                }
+            }
+        }
+    }
+
+    /// Finishes the container, which was decoded from `span`.
+    fn into_value(self, span: Range<usize>) -> N {
+        match self {
+            Container::List(list) => N::list(list, span),
+            Container::Dictionary(state) => N::dictionary(state.dictionary, span),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(input: &[u8]) -> DecoderResult<Bencode> {
+        Decoder::new(input).decode()
+    }
+
+    fn assert_unexpected_byte(input: &[u8], expected_byte: u8, expected_offset: usize) {
+        match decode(input) {
+            Err(DecoderError::UnexpectedByte { byte, offset, .. }) => {
+                assert_eq!(
+                    (byte, offset),
+                    (expected_byte, expected_offset),
+                    "{input:?}"
+                );
+            }
+            other => panic!("expected UnexpectedByte for {input:?}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn garbage_prefix_is_an_error() {
+        for byte in (0..=u8::MAX).filter(|byte| !b"ild0123456789".contains(byte)) {
+            assert_unexpected_byte(&[byte, b'i', b'1', b'e'], byte, 0);
+        }
+    }
+
+    #[test]
+    fn garbage_inside_containers_is_an_error() {
+        assert_unexpected_byte(b"li1exe", b'x', 4);
+        assert_unexpected_byte(b"d1:a\xffe", 0xff, 4);
+        assert_unexpected_byte(b"di1ei2ee", b'i', 1);
+    }
+
+    #[test]
+    fn errors_name_what_was_expected() {
+        assert_eq!(
+            decode(b"li1exe").unwrap_err().to_string(),
+            "expected a value or `e`, found 'x' at offset 4 in `[1]`",
+        );
+        assert_eq!(
+            decode(b"i12").unwrap_err().to_string(),
+            "expected a digit or `e`, found end of input at offset 3",
+        );
+        assert_eq!(
+            decode(b"5:ab").unwrap_err().to_string(),
+            "expected 3 more bytes of string, found end of input at offset 4",
+        );
+    }
+
+    #[test]
+    fn error_reports_path() {
+        let error = decode(b"d4:infod5:filesli1e?eee").unwrap_err();
+        assert_eq!(error.path().to_string(), "info.files[1]");
+        assert_eq!(error.offset(), 19);
+    }
+
+    #[test]
+    fn iteration_stops_after_an_error() {
+        let mut decoder = Decoder::new(&b"i1exi2e"[..]);
+        assert_eq!(decoder.next().unwrap().unwrap(), Bencode::Integer(1));
+        assert!(matches!(
+            decoder.next(),
+            Some(Err(DecoderError::UnexpectedByte { byte: b'x', .. }))
+        ));
+        assert!(decoder.next().is_none());
+        assert_eq!(Decoder::new(&b""[..]).count(), 0);
+    }
+
+    #[test]
+    fn skipping_checks_structure() {
+        let mut decoder = Decoder::new(&b"d4:infod5:filesld6:lengthi5eeee1:ai1eei7e"[..]);
+        decoder.skip_value().unwrap();
+        assert_eq!(decoder.bytes_consumed(), 38);
+        assert_eq!(decoder.decode().unwrap(), Bencode::Integer(7));
+
+        for (input, byte, offset) in [
+            (&b"di1ei2ee"[..], b'i', 1),
+            (b"d1:ae", b'e', 4),
+            (b"li1exe", b'x', 4),
+        ] {
+            let error = Decoder::new(input).skip_value().unwrap_err();
+            assert!(matches!(
+                error,
+                DecoderError::UnexpectedByte { byte: b, offset: o, .. } if (b, o) == (byte, offset)
+            ));
+        }
+        assert!(matches!(
+            Decoder::new(&b"l5:abe"[..]).skip_value(),
+            Err(DecoderError::UnexpectedEof { offset: 6, .. })
+        ));
+    }
+
+    #[test]
+    fn raw_values_are_verbatim() {
+        let value = b"d1:bi-0e1:ali007e03:abcee";
+        let input = [&value[..], b"i1e"].concat();
+        let mut decoder = Decoder::new(&input[..]);
+        assert_eq!(decoder.raw_value().unwrap(), value);
+        assert_eq!(decoder.raw_value().unwrap(), b"i1e");
+        assert!(matches!(
+            decoder.raw_value(),
+            Err(DecoderError::UnexpectedEof { .. })
+        ));
+    }
+
+    #[test]
+    fn spans_cover_each_value() {
+        let input = b"d5:filesld6:lengthi5eee4:name5:a.txte";
+        let spanned = Decoder::new(&input[..]).decode_spanned().unwrap();
+        assert_eq!(spanned.span, 0..input.len());
+        let files = spanned.get("files").unwrap();
+        assert_eq!(&input[files.span.clone()], b"ld6:lengthi5eee");
+        let length = files.as_list().unwrap()[0].get("length").unwrap();
+        assert_eq!(&input[length.span.clone()], b"i5e");
+        assert_eq!(
+            &input[spanned.get("name").unwrap().span.clone()],
+            b"5:a.txt"
+        );
+        assert_eq!(Bencode::from(spanned), decode(input).unwrap());
+    }
+
+    #[test]
+    fn buffered_reading_matches_byte_by_byte() {
+        let inputs: [&[u8]; 8] = [
+            b"d4:infod5:filesld6:lengthi12345e4:pathl5:a.txteeee4:name3:abce",
+            b"li-42ei0e10:0123456789e",
+            b"i123456789012345678901234567890e",
+            b"li1ei2x3ee",
+            b"d1:ai1e",
+            b"12:short",
+            b"i--1e",
+            b"",
+        ];
+        for input in inputs {
+            let expected = format!("{:?}", decode(input));
+            for capacity in [1, 2, 3, 7, 64] {
+                let reader = io::BufReader::with_capacity(capacity, input);
+                let mut decoder = Decoder::from_buf_read(reader, DecoderOptions::default());
+                assert_eq!(format!("{:?}", decoder.decode()), expected, "{input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn plain_readers_are_buffered() {
+        struct CountingReader<'a> {
+            input: &'a [u8],
+            reads: usize,
+        }
+
+        impl Read for CountingReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.reads += 1;
+                self.input.read(buf)
+            }
+        }
+
+        let input = b"d4:infod6:lengthi12345e4:name9:something12:piece lengthi16384eee";
+        let reads = |buffer_size| {
+            let options = DecoderOptions {
+                buffer_size,
+                ..DecoderOptions::default()
+            };
+            let reader = CountingReader { input, reads: 0 };
+            let mut decoder = Decoder::with_options(reader, options);
+            assert!(decoder.decode().is_ok());
+            decoder.input.into_inner().reads
+        };
+        assert_eq!(reads(DecoderOptions::default().buffer_size), 1);
+        assert!(reads(1) > 20);
+    }
+
+    #[test]
+    fn decoding_into_reuses_buffers() {
+        let input = b"d1:al5:hello5:worldeed1:al2:hie1:bi1ee";
+        let mut decoder = Decoder::new(&input[..]);
+        let mut value = Bencode::default();
+        decoder.decode_into(&mut value).unwrap();
+        let list = value["a"].as_list().unwrap();
+        let (list_buffer, string_buffers) = (
+            list.as_ptr(),
+            [
+                list[0].as_string().unwrap().as_ptr(),
+                list[1].as_string().unwrap().as_ptr(),
+            ],
+        );
+        decoder.decode_into(&mut value).unwrap();
+        assert_eq!(value, bencode!({ "a": ["hi"], "b": 1 }));
+        let list = value["a"].as_list().unwrap();
+        assert_eq!(list.as_ptr(), list_buffer);
+        assert!(string_buffers.contains(&list[0].as_string().unwrap().as_ptr()));
+        assert!(decoder.spare.strings.is_empty() && decoder.spare.lists.is_empty());
+    }
+
+    #[test]
+    fn long_strings_are_streamed_in_pieces() {
+        let input = b"l20:aaaaaaaaaaaaaaaaaaaa2:bbe";
+        let reader = io::BufReader::with_capacity(4, &input[..]);
+        let mut chunks = Vec::new();
+        let value = Decoder::from_buf_read(reader, DecoderOptions::default())
+            .decode_streaming(3, |path, chunk| {
+                chunks.push((path.to_string(), chunk.len()));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(value, bencode!(["", "bb"]));
+        assert!(chunks.len() > 1 && chunks.iter().all(|(path, _)| path == "[0]"));
+        assert_eq!(chunks.iter().map(|(_, length)| length).sum::<usize>(), 20);
+
+        let error = Decoder::new(&input[..])
+            .decode_streaming(3, |_, _| Err(io::ErrorKind::WriteZero.into()))
+            .unwrap_err();
+        assert!(matches!(error, DecoderError::IO { offset: 4, .. }));
+    }
+
+    #[test]
+    fn declared_lengths_are_not_trusted() {
+        let error = decode(b"4294967296:abc").unwrap_err();
+        assert!(matches!(
+            error,
+            DecoderError::UnexpectedEof {
+                expected: Expected::StringBytes {
+                    remaining: 4294967293
+                },
+                offset: 14,
+                ..
+            }
+        ));
+        #[cfg(feature = "serde")]
+        assert!(matches!(
+            crate::from_reader::<_, Bencode>(&b"4294967296:abc"[..]),
+            Err(crate::Error::Eof)
+        ));
+    }
+
+    #[test]
+    fn tee_sees_exactly_what_is_consumed() {
+        let input = b"d6:pieces20:aaaaaaaaaaaaaaaaaaaa4:name5:a.txtei42e";
+        for capacity in [1, 3, 64] {
+            let reader = Tee::new(
+                io::BufReader::with_capacity(capacity, &input[..]),
+                Vec::new(),
+            );
+            let mut decoder = Decoder::from_buf_read(reader, DecoderOptions::default());
+            decoder.skip_value().unwrap();
+            assert_eq!(decoder.get_ref().observer(), &input[..46]);
+            decoder.decode().unwrap();
+            assert_eq!(decoder.into_inner().into_observer(), input);
+        }
+    }
+
+    #[test]
+    fn rollback_returns_to_the_checkpoint() {
+        let input = b"li1ei2eed1:ai1ee";
+        for capacity in [1, 4, 64] {
+            let reader = io::BufReader::with_capacity(capacity, io::Cursor::new(&input[..]));
+            let mut decoder = Decoder::from_buf_read(reader, DecoderOptions::default());
+            decoder.checkpoint();
+            let list = decoder.decode().unwrap();
+            decoder.rollback().unwrap();
+            assert_eq!(decoder.bytes_consumed(), 0);
+            assert_eq!(decoder.decode().unwrap(), list);
+            decoder.checkpoint();
+            decoder.skip_value().unwrap();
+            decoder.rollback().unwrap();
+            assert_eq!(decoder.bytes_consumed(), 8);
+            assert_eq!(decoder.decode().unwrap(), bencode!({ "a": 1 }));
+        }
+    }
+
+    #[test]
+    fn empty_input_is_eof() {
+        assert!(matches!(
+            decode(b""),
+            Err(DecoderError::UnexpectedEof { offset: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let hostile = [b'l'; 10_000];
+        assert!(matches!(
+            decode(&hostile),
+            Err(DecoderError::DepthLimitExceeded {
+                limit: 512,
+                offset: 512,
+                ..
+            })
+        ));
+
+        let nested = [vec![b'l'; 512], vec![b'e'; 512]].concat();
+        assert!(decode(&nested).is_ok());
+
+        let options = DecoderOptions {
+            max_depth: 2,
+            ..DecoderOptions::default()
+        };
+        let mut decoder = Decoder::with_options(&b"d1:ald1:bleeee"[..], options);
+        assert_eq!(decoder.decode().unwrap_err().path().to_string(), "a[0]");
+    }
+
+    #[test]
+    fn integers_round_trip_at_the_extremes() {
+        let integers = [
+            0,
+            -1,
+            9,
+            10,
+            -99,
+            100,
+            12345,
+            i64::MIN.into(),
+            i128::MIN,
+            i128::MAX,
+        ];
+        for integer in integers {
+            let encoded = Bencode::Integer(integer).canonicalize();
+            assert_eq!(encoded, format!("i{integer}e").into_bytes());
+            assert_eq!(
+                Decoder::new(&encoded[..]).decode().unwrap(),
+                Bencode::Integer(integer)
+            );
+        }
+        let mut encoded = Vec::new();
+        crate::canonical::write_integer(&mut encoded, false, u128::MAX).unwrap();
+        assert_eq!(encoded, format!("i{}e", u128::MAX).into_bytes());
+        let long = Bencode::string(vec![b'x'; 100]).canonicalize();
+        assert!(long.starts_with(b"100:x"));
+    }
+
+    #[test]
+    fn deep_nesting_does_not_overflow_the_stack() {
+        let depth = 10_000;
+        let options = DecoderOptions {
+            max_depth: usize::MAX,
+            ..DecoderOptions::default()
+        };
+        let truncated = vec![b'l'; depth];
+        let mut decoder = Decoder::with_options(&truncated[..], options);
+        let error = decoder.decode().unwrap_err();
+        assert!(matches!(error, DecoderError::UnexpectedEof { offset, .. } if offset == depth));
+        assert_eq!(error.path().segments().len(), depth - 1);
+
+        let nested = [vec![b'l'; depth], vec![b'e'; depth]].concat();
+        let value = Decoder::with_options(&nested[..], options)
+            .decode()
+            .unwrap();
+        let mut level = &value;
+        for _ in 1..depth {
+            level = &level[0];
+        }
+        assert_eq!(level, &Bencode::list());
+        assert_eq!(value.encoded_len(), nested.len());
+        assert_eq!(value.canonicalize(), nested);
+    }
+
+    #[test]
+    fn strings_are_limited() {
+        let options = DecoderOptions {
+            max_string_length: Some(4),
+            max_total_bytes: Some(10),
+            ..DecoderOptions::default()
+        };
+        let decode = |input: &[u8]| Decoder::with_options(input, options).decode();
+        assert!(decode(b"l4:spam4:eggse").is_ok());
+        assert!(matches!(
+            decode(b"l4:spam1000000000:"),
+            Err(DecoderError::StringTooLong {
+                length: 1_000_000_000,
+                offset: 7,
+                ..
+            })
+        ));
+        assert!(matches!(
+            decode(b"l4:spam4:eggs4:hame"),
+            Err(DecoderError::BudgetExceeded {
+                limit: 10,
+                offset: 13,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn elements_are_limited() {
+        let options = DecoderOptions {
+            max_elements: Some(3),
+            ..DecoderOptions::default()
+        };
+        let decode = |input: &[u8]| Decoder::with_options(input, options).decode();
+        assert!(decode(b"d1:ali0ei0eee").is_ok());
+        let error = decode(b"d1:ali0ei0ei0eee").unwrap_err();
+        assert!(matches!(
+            error,
+            DecoderError::TooManyElements {
+                limit: 3,
+                offset: 11,
+                ..
+            }
+        ));
+        assert_eq!(error.path().to_string(), "a[2]");
+    }
+
+    #[test]
+    fn strict_integers_are_canonical() {
+        let strict = DecoderOptions {
+            strict: true,
+            ..DecoderOptions::default()
+        };
+        for input in [&b"i03e"[..], b"i-0e", b"i00e", b"i-01e"] {
+            assert!(decode(input).is_ok(), "{input:?}");
+            assert!(
+                matches!(
+                    Decoder::with_options(input, strict).decode(),
+                    Err(DecoderError::NonCanonicalInteger { offset: 1, .. })
+                ),
+                "{input:?}"
+            );
+        }
+        for input in [&b"i0e"[..], b"i10e", b"i-10e"] {
+            assert!(Decoder::with_options(input, strict).decode().is_ok());
+        }
+        assert!(matches!(decode(b"i-e"), Err(DecoderError::NAN { .. })));
+    }
+
+    #[test]
+    fn strict_keys_are_sorted_and_unique() {
+        let strict = DecoderOptions {
+            strict: true,
+            ..DecoderOptions::default()
+        };
+        let decode_strict = |input: &[u8]| Decoder::with_options(input, strict).decode();
+        assert!(decode_strict(b"d1:ai1e1:bi2e2:bbi3ee").is_ok());
+        assert!(decode(b"d1:bi1e1:ai2ee").is_ok());
+
+        let error = decode_strict(b"d4:infod1:bi1e1:ai2eee").unwrap_err();
+        assert!(matches!(&error, DecoderError::UnsortedKey { key, offset: 14, .. } if key == "a"));
+        assert_eq!(error.path().to_string(), "info");
+        assert!(matches!(
+            decode_strict(b"d1:ai1e1:ai2ee"),
+            Err(DecoderError::DuplicateKey { offset: 7, .. })
+        ));
+    }
+
+    #[test]
+    fn keys_need_not_be_utf8() {
+        let input = b"d4:infod1:\x80i2ee2:\xff\xfei1ee";
+        let value = decode(input).unwrap();
+        assert_eq!(
+            value.as_dictionary().unwrap()[&b"\xff\xfe"[..]],
+            Bencode::Integer(1)
+        );
+        assert_eq!(value.canonicalize(), input);
+    }
+
+    #[test]
+    fn integers_cover_the_i128_range() {
+        let integers = [
+            i128::MIN,
+            i64::MIN.into(),
+            -1,
+            0,
+            i64::MAX.into(),
+            u64::MAX.into(),
+            i128::MAX,
+        ];
+        for integer in integers {
+            let input = format!("i{integer}e");
+            assert_eq!(decode(input.as_bytes()).unwrap(), Bencode::Integer(integer));
+        }
+        #[cfg(not(feature = "num-bigint"))]
+        {
+            let too_small = format!("i{}0e", i128::MIN);
+            let too_large = format!("i{}0e", i128::MAX);
+            for input in [too_small, too_large] {
+                assert!(matches!(
+                    decode(input.as_bytes()),
+                    Err(DecoderError::IntegerOverflow { offset: 1, .. })
+                ));
+            }
+        }
+    }
+
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn big_integers_decode_losslessly() {
+        use num_bigint::BigInt;
+
+        for digits in [
+            "1701411834604692317316873037158841057280",
+            "-98765432109876543210987654321098765432109876543210",
+        ] {
+            let input = format!("i{digits}e");
+            let value = decode(input.as_bytes()).unwrap();
+            assert_eq!(
+                value,
+                Bencode::BigInteger(digits.parse::<BigInt>().unwrap())
+            );
+            assert_eq!(value.canonicalize(), input.as_bytes());
+            #[cfg(feature = "serde")]
+            assert_eq!(crate::to_vec(&value).unwrap(), input.as_bytes());
+        }
+        assert_eq!(
+            decode(format!("i{}e", i128::MAX).as_bytes()).unwrap(),
+            Bencode::Integer(i128::MAX)
+        );
+        let strict = DecoderOptions {
+            strict: true,
+            ..DecoderOptions::default()
+        };
+        let mut decoder =
+            Decoder::with_options(&b"i0170141183460469231731687303715884105728e"[..], strict);
+        assert!(matches!(
+            decoder.decode(),
+            Err(DecoderError::NonCanonicalInteger { .. })
+        ));
+    }
+}
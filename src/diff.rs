@@ -0,0 +1,267 @@
+//! Structural differences between two [`Bencode`] values.
+
+use std::fmt::{self, Display};
+
+use crate::path::{Path, Segment};
+use crate::value::Bencode;
+
+/// A single edit that turns one value into another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Inserts a dictionary entry, or a list element at the given index.
+    Add { path: Path, value: Bencode },
+    /// Removes a dictionary entry or list element.
+    Remove { path: Path },
+    /// Overwrites the value at the path.
+    Replace { path: Path, value: Bencode },
+}
+
+impl Change {
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Add { path, .. } | Change::Remove { path } | Change::Replace { path, .. } => {
+                path
+            }
+        }
+    }
+}
+
+/// Why a [`Change`] could not be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// A container along the path does not exist, or the path ends somewhere the change
+    /// cannot be made.
+    InvalidPath(Path),
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::InvalidPath(path) => write!(f, "invalid patch path `{path}`"),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Computes the changes that turn `old` into `new`.
+///
+/// Applying the result to `old` with [`apply_patch`] yields a value equal to `new`.
+pub fn diff(old: &Bencode, new: &Bencode) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_into(old, new, &mut Path::root(), &mut changes);
+    changes
+}
+
+fn diff_into(old: &Bencode, new: &Bencode, path: &mut Path, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Bencode::Dictionary(old), Bencode::Dictionary(new)) => {
+            for (key, old_value) in old {
+                path.push(Segment::Key(key.clone()));
+                match new.get(key) {
+                    Some(new_value) => diff_into(old_value, new_value, path, changes),
+                    None => changes.push(Change::Remove { path: path.clone() }),
+                }
+                path.pop();
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    changes.push(Change::Add {
+                        path: path.join(Segment::Key(key.clone())),
+                        value: new_value.clone(),
+                    });
+                }
+            }
+        }
+        (Bencode::List(old), Bencode::List(new)) => {
+            let common = old.len().min(new.len());
+            for (index, (old_value, new_value)) in old.iter().zip(new).enumerate() {
+                path.push(Segment::Index(index));
+                diff_into(old_value, new_value, path, changes);
+                path.pop();
+            }
+            for (index, new_value) in new.iter().enumerate().skip(common) {
+                changes.push(Change::Add {
+                    path: path.join(Segment::Index(index)),
+                    value: new_value.clone(),
+                });
+            }
+            // Back to front, so that each removal leaves the earlier indices valid.
+            for index in (common..old.len()).rev() {
+                changes.push(Change::Remove {
+                    path: path.join(Segment::Index(index)),
+                });
+            }
+        }
+        (old, new) if old == new => {}
+        (_, new) => changes.push(Change::Replace {
+            path: path.clone(),
+            value: new.clone(),
+        }),
+    }
+}
+
+/// Applies `patch` to `value`, in order.
+///
+/// Stops at the first change that does not fit the value, leaving the changes before it
+/// applied.
+pub fn apply_patch(
+    value: &mut Bencode,
+    patch: impl IntoIterator<Item = Change>,
+) -> Result<(), PatchError> {
+    for change in patch {
+        apply_change(value, change)?;
+    }
+    Ok(())
+}
+
+fn apply_change(root: &mut Bencode, change: Change) -> Result<(), PatchError> {
+    let invalid = |path: &Path| PatchError::InvalidPath(path.clone());
+    match change {
+        Change::Replace { path, value } => {
+            *resolve(root, path.segments()).ok_or_else(|| invalid(&path))? = value;
+        }
+        Change::Add { path, value } => {
+            let (parent, last) = path.split_last().ok_or_else(|| invalid(&path))?;
+            match (resolve(root, parent), last) {
+                (Some(Bencode::Dictionary(dictionary)), Segment::Key(key)) => {
+                    dictionary.insert(key.clone(), value);
+                }
+                (Some(Bencode::List(list)), Segment::Index(index)) if *index <= list.len() => {
+                    list.insert(*index, value);
+                }
+                _ => return Err(invalid(&path)),
+            }
+        }
+        Change::Remove { path } => {
+            let (parent, last) = path.split_last().ok_or_else(|| invalid(&path))?;
+            let removed = match (resolve(root, parent), last) {
+                (Some(Bencode::Dictionary(dictionary)), Segment::Key(key)) => {
+                    dictionary.remove(key).is_some()
+                }
+                (Some(Bencode::List(list)), Segment::Index(index)) if *index < list.len() => {
+                    list.remove(*index);
+                    true
+                }
+                _ => false,
+            };
+            if !removed {
+                return Err(invalid(&path));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn resolve<'a>(mut value: &'a mut Bencode, segments: &[Segment]) -> Option<&'a mut Bencode> {
+    for segment in segments {
+        value = match (value, segment) {
+            (Bencode::Dictionary(dictionary), Segment::Key(key)) => dictionary.get_mut(key)?,
+            (Bencode::List(list), Segment::Index(index)) => list.get_mut(*index)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str) -> Segment {
+        Segment::Key(key.as_bytes().to_vec())
+    }
+
+    fn assert_patches(old: Bencode, new: Bencode, expected: Vec<Change>) {
+        let changes = diff(&old, &new);
+        assert_eq!(changes, expected);
+        let mut patched = old;
+        apply_patch(&mut patched, changes).unwrap();
+        assert_eq!(patched, new);
+    }
+
+    #[test]
+    fn equal_values_have_no_changes() {
+        let value = bencode!({ "a": [1, "x"], "b": { "c": 2 } });
+        assert_eq!(diff(&value, &value.clone()), []);
+    }
+
+    #[test]
+    fn dictionary_keys_are_added_removed_and_changed() {
+        assert_patches(
+            bencode!({ "a": 1, "b": { "c": 2, "d": 3 } }),
+            bencode!({ "b": { "c": 4, "d": 3 }, "e": "new" }),
+            vec![
+                Change::Remove {
+                    path: Path::from(vec![key("a")]),
+                },
+                Change::Replace {
+                    path: Path::from(vec![key("b"), key("c")]),
+                    value: bencode!(4),
+                },
+                Change::Add {
+                    path: Path::from(vec![key("e")]),
+                    value: bencode!("new"),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn lists_are_compared_by_index() {
+        assert_patches(
+            bencode!({ "l": [1, 2] }),
+            bencode!({ "l": [1, 3, [4], 5] }),
+            vec![
+                Change::Replace {
+                    path: Path::from(vec![key("l"), Segment::Index(1)]),
+                    value: bencode!(3),
+                },
+                Change::Add {
+                    path: Path::from(vec![key("l"), Segment::Index(2)]),
+                    value: bencode!([4]),
+                },
+                Change::Add {
+                    path: Path::from(vec![key("l"), Segment::Index(3)]),
+                    value: bencode!(5),
+                },
+            ],
+        );
+        assert_patches(
+            bencode!([1, 2, 3]),
+            bencode!([1]),
+            vec![
+                Change::Remove {
+                    path: Path::from(vec![Segment::Index(2)]),
+                },
+                Change::Remove {
+                    path: Path::from(vec![Segment::Index(1)]),
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn values_of_another_kind_are_replaced_whole() {
+        assert_patches(
+            bencode!({ "a": [1] }),
+            bencode!({ "a": { "b": 1 } }),
+            vec![Change::Replace {
+                path: Path::from(vec![key("a")]),
+                value: bencode!({ "b": 1 }),
+            }],
+        );
+    }
+
+    #[test]
+    fn changes_that_do_not_fit_are_rejected() {
+        let mut value = bencode!({ "a": 1 });
+        let change = Change::Remove {
+            path: Path::from(vec![key("b")]),
+        };
+        assert_eq!(
+            apply_patch(&mut value, [change.clone()]),
+            Err(PatchError::InvalidPath(change.path().clone()))
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Hashing of canonical encodings, behind the `digest` feature.
+
+use std::io::{self, Write};
+use std::mem;
+
+use digest::{Digest, Output};
+
+use crate::value::Bencode;
+
+impl Bencode {
+    /// Hashes the canonical encoding of the value with `D`.
+    ///
+    /// The encoding is fed to the hasher as it is produced rather than collected first, so
+    /// large documents are hashed without a copy of their encoding.
+    ///
+    /// ```
+    /// use bencode::bencode;
+    /// use sha1::{Digest, Sha1};
+    ///
+    /// let info = bencode!({ "length": 5, "name": "a.txt" });
+    /// assert_eq!(info.digest::<Sha1>(), Sha1::digest(info.canonicalize()));
+    /// ```
+    pub fn digest<D: Digest>(&self) -> Output<D> {
+        let mut writer = DigestWriter(D::new());
+        self.write_canonical(&mut writer)
+            .expect("writing to a hasher cannot fail");
+        writer.0.finalize()
+    }
+}
+
+struct DigestWriter<D>(D);
+
+impl<D: Digest> Write for DigestWriter<D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes to `W` while hashing exactly the bytes it accepts with `D`, so that a hash can be
+/// worked out as a document is written, without encoding it twice.
+///
+/// To hash only part of what is written, such as the `info` dictionary of a torrent, take
+/// the digest before and after that part: [`take_digest`](HashingWriter::take_digest)
+/// returns the hash of everything written since it was last called. Bytes still buffered
+/// in a [`StreamEncoder`](crate::StreamEncoder) have not reached the writer yet, so flush
+/// the encoder first.
+///
+/// ```
+/// use bencode::{bencode, HashingWriter, StreamEncoder};
+/// use sha1::{Digest, Sha1};
+///
+/// let info = bencode!({ "length": 5, "name": "a.txt", "piece length": 16384 });
+/// let mut encoder = StreamEncoder::new(HashingWriter::<_, Sha1>::new(Vec::new()));
+/// encoder.begin_dictionary()?;
+/// encoder.key(b"announce")?;
+/// encoder.bytes(b"http://tracker.example/announce")?;
+/// encoder.key(b"info")?;
+/// encoder.flush()?;
+/// encoder.get_mut().take_digest();
+/// encoder.value(&info)?;
+/// encoder.flush()?;
+/// let infohash = encoder.get_mut().take_digest();
+/// encoder.end()?;
+/// encoder.finish()?;
+/// assert_eq!(infohash, Sha1::digest(info.canonicalize()));
+/// # Ok::<(), bencode::EncoderError>(())
+/// ```
+pub struct HashingWriter<W, D> {
+    writer: W,
+    hasher: D,
+}
+
+impl<W: Write, D: Digest> HashingWriter<W, D> {
+    pub fn new(writer: W) -> Self {
+        HashingWriter {
+            writer,
+            hasher: D::new(),
+        }
+    }
+
+    /// Returns the hash of the bytes written since this was last called, or since the
+    /// writer was created.
+    pub fn take_digest(&mut self) -> Output<D> {
+        mem::replace(&mut self.hasher, D::new()).finalize()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// The writer, whatever is written to which directly is not hashed.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the writer, along with the hash of the bytes written since
+    /// [`take_digest`](HashingWriter::take_digest) was last called.
+    pub fn into_parts(self) -> (W, Output<D>) {
+        (self.writer, self.hasher.finalize())
+    }
+}
+
+impl<W: Write, D: Digest> Write for HashingWriter<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha1::Sha1;
+
+    use super::*;
+    use crate::encoder::StreamEncoder;
+
+    /// The SHA-1 of `d6:lengthi5e4:name5:a.txt12:piece lengthi16384e6:pieces20:<20 zero
+    /// bytes>e`, worked out independently of this crate.
+    const INFO_HASH: &str = "01602bcf4631827ee0244b891f1324f2d243ba72";
+
+    fn info() -> Bencode {
+        bencode!({
+            "name": "a.txt",
+            "piece length": 16384,
+            "pieces": [0u8; 20].as_slice(),
+            "length": 5,
+        })
+    }
+
+    #[test]
+    fn values_hash_to_their_info_hash() {
+        assert_eq!(format!("{:x}", info().digest::<Sha1>()), INFO_HASH);
+    }
+
+    #[test]
+    fn writers_hash_the_part_between_digests() {
+        let mut encoder = StreamEncoder::new(HashingWriter::<_, Sha1>::new(Vec::new()));
+        encoder.begin_dictionary().unwrap();
+        encoder.key(b"announce").unwrap();
+        encoder.bytes(b"http://tracker.example/announce").unwrap();
+        encoder.key(b"info").unwrap();
+        encoder.flush().unwrap();
+        encoder.get_mut().take_digest();
+        encoder.value(&info()).unwrap();
+        encoder.flush().unwrap();
+        let info_hash = encoder.get_mut().take_digest();
+        assert_eq!(format!("{info_hash:x}"), INFO_HASH);
+    }
+}
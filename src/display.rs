@@ -0,0 +1,163 @@
+//! Human-readable formatting of [`Bencode`] values.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Display, Write};
+
+use crate::value::Bencode;
+
+/// How many bytes of a binary string are shown before it is cut short.
+const HEX_PREVIEW_LEN: usize = 32;
+
+/// Renders the value in a JSON-like form.
+///
+/// Strings that are valid UTF-8 are quoted and escaped as in JSON. Any other string is
+/// shown as hex between angle brackets, cut short after the first few bytes.
+///
+/// ```
+/// use bencode::bencode;
+///
+/// let value = bencode!({ "name": "a.txt", "pieces": b"\x00\xff".as_slice(), "length": 5 });
+/// assert_eq!(value.to_string(), r#"{"length": 5, "name": "a.txt", "pieces": <00ff>}"#);
+/// ```
+impl Display for Bencode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bencode::Integer(integer) => write!(f, "{integer}"),
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(integer) => write!(f, "{integer}"),
+            Bencode::String(bytes) => write_string(f, bytes),
+            Bencode::List(list) => {
+                f.write_char('[')?;
+                for (position, element) in list.iter().enumerate() {
+                    if position > 0 {
+                        f.write_str(", ")?;
+                    }
+                    Display::fmt(element, f)?;
+                }
+                f.write_char(']')
+            }
+            Bencode::Dictionary(dictionary) => {
+                f.write_char('{')?;
+                for (position, (key, value)) in dictionary.iter().enumerate() {
+                    if position > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_string(f, key)?;
+                    f.write_str(": ")?;
+                    Display::fmt(value, f)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// Like the derived implementation, except that strings are shown as text when they are
+/// valid UTF-8 and as shortened hex otherwise, rather than as a list of every byte.
+impl Debug for Bencode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bencode::Integer(integer) => f.debug_tuple("Integer").field(integer).finish(),
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(integer) => f.debug_tuple("BigInteger").field(integer).finish(),
+            Bencode::String(bytes) => f.debug_tuple("String").field(&ByteString(bytes)).finish(),
+            Bencode::List(list) => f.debug_tuple("List").field(list).finish(),
+            Bencode::Dictionary(dictionary) => f
+                .debug_tuple("Dictionary")
+                .field(&Entries(dictionary))
+                .finish(),
+        }
+    }
+}
+
+/// A dictionary, shown as a map with its keys formatted like [`ByteString`]s.
+struct Entries<'a>(&'a BTreeMap<Vec<u8>, Bencode>);
+
+impl Debug for Entries<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(key, value)| (ByteString(key), value)))
+            .finish()
+    }
+}
+
+struct ByteString<'a>(&'a [u8]);
+
+impl Debug for ByteString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match std::str::from_utf8(self.0) {
+            Ok(string) => Debug::fmt(string, f),
+            Err(_) => write_hex(f, self.0),
+        }
+    }
+}
+
+/// Writes a string or dictionary key quoted if it is text, and as hex otherwise.
+fn write_string(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    match std::str::from_utf8(bytes) {
+        Ok(string) => write_quoted(f, string),
+        Err(_) => write_hex(f, bytes),
+    }
+}
+
+fn write_quoted(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in string.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Writes `bytes` as `<0a1b…>`, followed by the full length when it is cut short.
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    f.write_char('<')?;
+    for byte in bytes.iter().take(HEX_PREVIEW_LEN) {
+        write!(f, "{byte:02x}")?;
+    }
+    if bytes.len() > HEX_PREVIEW_LEN {
+        write!(f, "… {} bytes", bytes.len())?;
+    }
+    f.write_char('>')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_strings_are_cut_short_past_the_preview() {
+        let exact = Bencode::String(vec![0xff; HEX_PREVIEW_LEN]);
+        let hex = "ff".repeat(HEX_PREVIEW_LEN);
+        assert_eq!(exact.to_string(), format!("<{hex}>"));
+        assert_eq!(format!("{exact:?}"), format!("String(<{hex}>)"));
+
+        let longer = Bencode::String(vec![0xff; HEX_PREVIEW_LEN + 1]);
+        let length = HEX_PREVIEW_LEN + 1;
+        assert_eq!(longer.to_string(), format!("<{hex}… {length} bytes>"));
+        assert_eq!(
+            format!("{longer:?}"),
+            format!("String(<{hex}… {length} bytes>)")
+        );
+    }
+
+    #[test]
+    fn binary_keys_are_cut_short_too() {
+        let key = vec![0xff; HEX_PREVIEW_LEN + 1];
+        let value = Bencode::Dictionary(BTreeMap::from([(key, Bencode::Integer(1))]));
+        let hex = "ff".repeat(HEX_PREVIEW_LEN);
+        let length = HEX_PREVIEW_LEN + 1;
+        assert_eq!(value.to_string(), format!("{{<{hex}… {length} bytes>: 1}}"));
+        assert_eq!(
+            format!("{value:?}"),
+            format!("Dictionary({{<{hex}… {length} bytes>: Integer(1)}})")
+        );
+    }
+}
@@ -0,0 +1,825 @@
+//! Writing bencode a piece at a time, without building values first.
+
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::io::{self, Read, Write};
+
+use crate::canonical::{write_integer, write_length, write_string};
+use crate::decoder::DecoderError;
+use crate::tokenizer::{self, Event};
+use crate::value::Bencode;
+
+/// Errors produced by a [`StreamEncoder`].
+///
+/// Apart from [`Io`](EncoderError::Io), these are mistakes in the order of calls, and
+/// nothing is written for the call that fails.
+#[derive(Debug)]
+pub enum EncoderError {
+    /// The underlying writer failed.
+    Io(io::Error),
+    /// A value was written where a dictionary key was expected.
+    KeyExpected,
+    /// A key was written outside a dictionary, or where the value of the last key was
+    /// expected.
+    UnexpectedKey,
+    /// A dictionary was ended after a key but before its value.
+    MissingValue,
+    /// A key was not greater than the key before it in the same dictionary.
+    UnsortedKey(Vec<u8>),
+    /// A key was the same as the key before it in the same dictionary.
+    DuplicateKey(Vec<u8>),
+    /// [`end`](StreamEncoder::end) was called with no list or dictionary open.
+    NothingToEnd,
+    /// The encoder was finished with lists or dictionaries still open.
+    Unfinished,
+    /// An [`Event::StringChunk`] came without an [`Event::StringStart`] before it.
+    UnexpectedChunk,
+    /// The contents of a string ran past, or stopped short of, the length it was
+    /// started with.
+    StringLength,
+    /// The bytes given to [`write_raw_checked`](StreamEncoder::write_raw_checked) are not
+    /// a single well-formed value.
+    InvalidRaw(DecoderError),
+    /// Part of the value abandoned by [`reset`](StreamEncoder::reset) had already been
+    /// handed to the writer, so it could not be taken back.
+    AlreadyFlushed,
+}
+
+impl Display for EncoderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncoderError::Io(error) => write!(f, "io error: {error}"),
+            EncoderError::KeyExpected => f.write_str("expected a dictionary key"),
+            EncoderError::UnexpectedKey => f.write_str("a key is not expected here"),
+            EncoderError::MissingValue => f.write_str("dictionary key has no value"),
+            EncoderError::UnsortedKey(key) => {
+                write!(f, "key {:?} is out of order", String::from_utf8_lossy(key))
+            }
+            EncoderError::DuplicateKey(key) => {
+                write!(f, "duplicate key {:?}", String::from_utf8_lossy(key))
+            }
+            EncoderError::NothingToEnd => f.write_str("no list or dictionary to end"),
+            EncoderError::Unfinished => f.write_str("list or dictionary left open"),
+            EncoderError::UnexpectedChunk => f.write_str("string contents without a string"),
+            EncoderError::StringLength => {
+                f.write_str("string contents do not match the string's length")
+            }
+            EncoderError::InvalidRaw(error) => write!(f, "invalid raw value: {error}"),
+            EncoderError::AlreadyFlushed => {
+                f.write_str("part of the abandoned value was already written")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncoderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EncoderError::Io(error) => Some(error),
+            EncoderError::InvalidRaw(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EncoderError {
+    fn from(error: io::Error) -> Self {
+        EncoderError::Io(error)
+    }
+}
+
+/// Writes bencode to an [`io::Write`] one piece at a time, so that documents too large to
+/// hold in memory, such as a torrent being generated, can be written as they are produced.
+///
+/// The encoder checks that lists and dictionaries are properly nested and that the keys
+/// of each dictionary are written in strictly increasing order, as canonical bencode
+/// requires. Any number of values may be written one after another, so one encoder can
+/// write a whole stream of messages.
+///
+/// Output is collected in a buffer and handed to the writer in large pieces, as by a
+/// [`BufWriter`](std::io::BufWriter), so writing straight to a `File` or `TcpStream` does
+/// not take a system call for every `l`, `e` and length. Call [`flush`](StreamEncoder::flush) or
+/// [`finish`](StreamEncoder::finish) to write out what is left; what is still buffered
+/// when the encoder is dropped is written then, and any error ignored.
+///
+/// ```
+/// use bencode::encoder::StreamEncoder;
+///
+/// let mut encoder = StreamEncoder::new(Vec::new());
+/// encoder.begin_dictionary()?;
+/// encoder.key(b"info")?;
+/// encoder.begin_dictionary()?;
+/// encoder.key(b"length")?;
+/// encoder.integer(5)?;
+/// encoder.key(b"name")?;
+/// encoder.bytes(b"a.txt")?;
+/// encoder.end()?;
+/// encoder.end()?;
+/// assert_eq!(encoder.finish()?, b"d4:infod6:lengthi5e4:name5:a.txtee");
+/// # Ok::<(), bencode::encoder::EncoderError>(())
+/// ```
+pub struct StreamEncoder<W: Write> {
+    writer: Buffer<W>,
+    /// The lists and dictionaries open so far.
+    open: Vec<Open>,
+    /// The string whose contents are being written, if any.
+    string: Option<OpenString>,
+    flush_each_value: bool,
+    /// Where the value being written started, counted as by
+    /// [`bytes_written`](StreamEncoder::bytes_written).
+    value_start: usize,
+}
+
+/// How much a [`StreamEncoder`] buffers by default, the same as a
+/// [`BufWriter`](std::io::BufWriter).
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Collects output and hands it to the writer in large pieces, as a
+/// [`BufWriter`](std::io::BufWriter) does, while counting the bytes written through it and
+/// keeping those not yet handed on where they can be taken back.
+struct Buffer<W: Write> {
+    /// Only `None` once [`into_inner`](Buffer::into_inner) has taken it.
+    writer: Option<W>,
+    bytes: Vec<u8>,
+    capacity: usize,
+    /// How many bytes have been written, including those still buffered.
+    written: usize,
+}
+
+impl<W: Write> Buffer<W> {
+    fn writer(&self) -> &W {
+        self.writer
+            .as_ref()
+            .expect("the writer is only taken by into_inner")
+    }
+
+    fn writer_mut(&mut self) -> &mut W {
+        self.writer
+            .as_mut()
+            .expect("the writer is only taken by into_inner")
+    }
+
+    /// Hands everything buffered to the writer, keeping whatever it did not take.
+    fn write_buffered(&mut self) -> io::Result<()> {
+        let Some(writer) = &mut self.writer else {
+            return Ok(());
+        };
+        let mut handed = 0;
+        let result = loop {
+            if handed == self.bytes.len() {
+                break Ok(());
+            }
+            match writer.write(&self.bytes[handed..]) {
+                Ok(0) => break Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => handed += written,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => break Err(error),
+            }
+        };
+        self.bytes.drain(..handed);
+        result
+    }
+
+    /// Takes back everything written from `offset` on that has not yet been handed to
+    /// the writer, returning whether that was all of it.
+    fn truncate(&mut self, offset: usize) -> bool {
+        let handed = self.written - self.bytes.len();
+        self.bytes.truncate(offset.saturating_sub(handed));
+        self.written = offset.max(handed);
+        offset >= handed
+    }
+
+    fn into_inner(mut self) -> io::Result<W> {
+        self.write_buffered()?;
+        Ok(self
+            .writer
+            .take()
+            .expect("the writer is only taken by into_inner"))
+    }
+}
+
+impl<W: Write> Write for Buffer<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        if self.bytes.len() + bytes.len() > self.capacity {
+            self.write_buffered()?;
+        }
+        if bytes.len() >= self.capacity {
+            self.writer_mut().write_all(bytes)?;
+        } else {
+            self.bytes.extend_from_slice(bytes);
+        }
+        self.written += bytes.len();
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buffered()?;
+        self.writer_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for Buffer<W> {
+    fn drop(&mut self) {
+        let _ = self.write_buffered();
+    }
+}
+
+struct OpenString {
+    remaining: u64,
+    /// The contents so far, if the string is a dictionary key, which is only written
+    /// once all of it is there to be checked.
+    key: Option<Vec<u8>>,
+}
+
+enum Open {
+    List,
+    Dictionary {
+        previous_key: Option<Vec<u8>>,
+        /// Whether a key has been written and its value comes next.
+        in_value: bool,
+    },
+}
+
+impl<W: Write> StreamEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        StreamEncoder::with_capacity(DEFAULT_CAPACITY, writer)
+    }
+
+    /// Creates an encoder that buffers up to `capacity` bytes before writing them.
+    pub fn with_capacity(capacity: usize, writer: W) -> Self {
+        StreamEncoder {
+            writer: Buffer {
+                writer: Some(writer),
+                bytes: Vec::with_capacity(capacity),
+                capacity,
+                written: 0,
+            },
+            open: Vec::new(),
+            string: None,
+            flush_each_value: false,
+            value_start: 0,
+        }
+    }
+
+    /// Sets whether the writer is flushed after each whole value, so that each message of
+    /// a stream, such as KRPC messages over one connection, is sent as soon as it has been
+    /// written rather than when the buffer fills.
+    pub fn flush_each_value(mut self, flush: bool) -> Self {
+        self.flush_each_value = flush;
+        self
+    }
+
+    pub fn integer(&mut self, integer: impl Into<i128>) -> Result<(), EncoderError> {
+        self.begin_value()?;
+        let integer = integer.into();
+        write_integer(&mut self.writer, integer < 0, integer.unsigned_abs())?;
+        self.finish_value()
+    }
+
+    /// Writes a string holding `bytes`.
+    pub fn bytes(&mut self, bytes: &[u8]) -> Result<(), EncoderError> {
+        self.begin_value()?;
+        write_string(&mut self.writer, bytes)?;
+        self.finish_value()
+    }
+
+    /// Writes a string of `length` bytes read from `reader`, a piece at a time.
+    ///
+    /// This writes strings too large to hold in memory, such as the `pieces` of a large
+    /// torrent as they are hashed to a temporary file. If `reader` runs out before
+    /// `length` bytes, [`EncoderError::StringLength`] is returned, and the output is left
+    /// with a string cut short.
+    ///
+    /// ```
+    /// use bencode::StreamEncoder;
+    ///
+    /// let pieces = vec![7; 20 * 1000];
+    /// let mut encoder = StreamEncoder::new(Vec::new());
+    /// encoder.bytes_from_reader(pieces.len() as u64, &pieces[..])?;
+    /// assert_eq!(&encoder.finish()?[..6], b"20000:");
+    /// # Ok::<(), bencode::EncoderError>(())
+    /// ```
+    pub fn bytes_from_reader(
+        &mut self,
+        length: u64,
+        reader: impl Read,
+    ) -> Result<(), EncoderError> {
+        self.begin_value()?;
+        write_length(&mut self.writer, length)?;
+        let copied = io::copy(&mut reader.take(length), &mut self.writer)?;
+        if copied < length {
+            return Err(EncoderError::StringLength);
+        }
+        self.finish_value()
+    }
+
+    /// Writes the canonical encoding of a whole value.
+    pub fn value(&mut self, value: &Bencode) -> Result<(), EncoderError> {
+        self.begin_value()?;
+        value.write_canonical(&mut self.writer)?;
+        self.finish_value()
+    }
+
+    /// Writes `bytes`, which must be the encoding of exactly one value, as they are.
+    ///
+    /// This splices pre-encoded values into the output byte for byte, such as a cached
+    /// `info` dictionary whose hash must not change, or a message being forwarded. Nothing
+    /// checks that `bytes` are well-formed; see
+    /// [`write_raw_checked`](StreamEncoder::write_raw_checked).
+    pub fn write_raw(&mut self, bytes: &[u8]) -> Result<(), EncoderError> {
+        self.begin_value()?;
+        self.writer.write_all(bytes)?;
+        self.finish_value()
+    }
+
+    /// Like [`write_raw`](StreamEncoder::write_raw), but first checks that `bytes` are
+    /// exactly one well-formed value, as by [`validate`](crate::validate).
+    ///
+    /// ```
+    /// use bencode::{EncoderError, StreamEncoder};
+    ///
+    /// let mut encoder = StreamEncoder::new(Vec::new());
+    /// encoder.begin_list()?;
+    /// encoder.write_raw_checked(b"d1:ai03ee")?;
+    /// assert!(matches!(
+    ///     encoder.write_raw_checked(b"i1ei2e"),
+    ///     Err(EncoderError::InvalidRaw(_))
+    /// ));
+    /// encoder.end()?;
+    /// assert_eq!(encoder.finish()?, b"ld1:ai03eee");
+    /// # Ok::<(), EncoderError>(())
+    /// ```
+    pub fn write_raw_checked(&mut self, bytes: &[u8]) -> Result<(), EncoderError> {
+        self.begin_value()?;
+        tokenizer::validate(bytes).map_err(EncoderError::InvalidRaw)?;
+        self.write_raw(bytes)
+    }
+
+    pub fn begin_list(&mut self) -> Result<(), EncoderError> {
+        self.begin_value()?;
+        self.writer.write_all(b"l")?;
+        self.open.push(Open::List);
+        Ok(())
+    }
+
+    /// Starts a dictionary, whose entries are written as a [`key`](StreamEncoder::key)
+    /// followed by a value.
+    pub fn begin_dictionary(&mut self) -> Result<(), EncoderError> {
+        self.begin_value()?;
+        self.writer.write_all(b"d")?;
+        self.open.push(Open::Dictionary {
+            previous_key: None,
+            in_value: false,
+        });
+        Ok(())
+    }
+
+    /// Writes the key of the next entry of the innermost dictionary, which must be
+    /// greater than the key before it.
+    pub fn key(&mut self, key: &[u8]) -> Result<(), EncoderError> {
+        self.check_no_string()?;
+        let Some(Open::Dictionary {
+            previous_key,
+            in_value: in_value @ false,
+        }) = self.open.last_mut()
+        else {
+            return Err(EncoderError::UnexpectedKey);
+        };
+        match previous_key.as_deref().map(|previous| key.cmp(previous)) {
+            Some(Ordering::Less) => return Err(EncoderError::UnsortedKey(key.to_vec())),
+            Some(Ordering::Equal) => return Err(EncoderError::DuplicateKey(key.to_vec())),
+            _ => {}
+        }
+        write_string(&mut self.writer, key)?;
+        *previous_key = Some(key.to_vec());
+        *in_value = true;
+        Ok(())
+    }
+
+    /// Ends the innermost list or dictionary.
+    pub fn end(&mut self) -> Result<(), EncoderError> {
+        self.check_no_string()?;
+        match self.open.last() {
+            None => return Err(EncoderError::NothingToEnd),
+            Some(Open::Dictionary { in_value: true, .. }) => {
+                return Err(EncoderError::MissingValue)
+            }
+            Some(_) => {}
+        }
+        self.writer.write_all(b"e")?;
+        self.open.pop();
+        self.finish_value()
+    }
+
+    /// Writes what `event` stands for, so that the events a
+    /// [`Tokenizer`](crate::Tokenizer) reads, filtered or transformed along the way, can
+    /// be written out again without building values.
+    ///
+    /// A string is written as an [`Event::StringStart`] followed by
+    /// [`Event::StringChunk`]s holding exactly as many bytes as it gave. Keys are checked
+    /// for order as they are by [`key`](StreamEncoder::key), once all of them has arrived.
+    ///
+    /// ```
+    /// use bencode::tokenizer::{Event, Tokenizer};
+    /// use bencode::StreamEncoder;
+    ///
+    /// // Double every integer in a document.
+    /// let mut tokenizer = Tokenizer::new(&b"d1:ai1e1:bli2eee"[..]);
+    /// let mut encoder = StreamEncoder::new(Vec::new());
+    /// while let Some(event) = tokenizer.next_event()? {
+    ///     match event {
+    ///         Event::Integer(integer) => encoder.event(Event::Integer(integer * 2))?,
+    ///         event => encoder.event(event)?,
+    ///     }
+    /// }
+    /// assert_eq!(encoder.finish()?, b"d1:ai2e1:bli4eee");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn event(&mut self, event: Event<'_>) -> Result<(), EncoderError> {
+        if let Some(string) = &mut self.string {
+            let Event::StringChunk(chunk) = event else {
+                return Err(EncoderError::StringLength);
+            };
+            let length = chunk.len() as u64;
+            if length > string.remaining {
+                return Err(EncoderError::StringLength);
+            }
+            match &mut string.key {
+                Some(key) => key.extend_from_slice(chunk),
+                None => self.writer.write_all(chunk)?,
+            }
+            string.remaining -= length;
+            return self.finish_string();
+        }
+        match event {
+            Event::Integer(integer) => self.integer(integer),
+            #[cfg(feature = "num-bigint")]
+            Event::BigInteger(integer) => {
+                self.begin_value()?;
+                write!(self.writer, "i{integer}e")?;
+                self.finish_value()
+            }
+            Event::StringStart(length) => self.begin_string(length),
+            Event::StringChunk(_) => Err(EncoderError::UnexpectedChunk),
+            Event::ListStart => self.begin_list(),
+            Event::DictStart => self.begin_dictionary(),
+            Event::End => self.end(),
+        }
+    }
+
+    /// Writes each of `events` in turn, as by [`event`](StreamEncoder::event).
+    pub fn encode_events<'a>(
+        &mut self,
+        events: impl IntoIterator<Item = Event<'a>>,
+    ) -> Result<(), EncoderError> {
+        events.into_iter().try_for_each(|event| self.event(event))
+    }
+
+    /// How many bytes have been written so far, including any still buffered.
+    ///
+    /// ```
+    /// use bencode::StreamEncoder;
+    ///
+    /// let mut encoder = StreamEncoder::new(Vec::new());
+    /// encoder.begin_list()?;
+    /// encoder.bytes(b"spam")?;
+    /// assert_eq!(encoder.bytes_written(), 7);
+    /// # Ok::<(), bencode::EncoderError>(())
+    /// ```
+    pub fn bytes_written(&self) -> usize {
+        self.writer.written
+    }
+
+    /// Abandons the value being written, if any, so that the next call starts a new one.
+    ///
+    /// This recovers from a mistake partway through a value, such as a key out of order.
+    /// What has been written of the abandoned value is taken back out of the buffer, so
+    /// the output holds only whole values. If some of it has already been handed to the
+    /// writer, by [`flush`](StreamEncoder::flush) or by the buffer filling up, the rest
+    /// is still taken back and the value abandoned, but [`EncoderError::AlreadyFlushed`]
+    /// is returned, since the output holds the start of it.
+    ///
+    /// ```
+    /// use bencode::{EncoderError, StreamEncoder};
+    ///
+    /// let mut encoder = StreamEncoder::new(Vec::new());
+    /// encoder.integer(1)?;
+    /// encoder.begin_dictionary()?;
+    /// encoder.key(b"b")?;
+    /// encoder.integer(2)?;
+    /// assert!(encoder.key(b"a").is_err());
+    /// encoder.reset()?;
+    /// encoder.integer(3)?;
+    /// assert_eq!(encoder.finish()?, b"i1ei3e");
+    /// # Ok::<(), EncoderError>(())
+    /// ```
+    pub fn reset(&mut self) -> Result<(), EncoderError> {
+        self.open.clear();
+        self.string = None;
+        let taken_back = self.writer.truncate(self.value_start);
+        self.value_start = self.writer.written;
+        match taken_back {
+            true => Ok(()),
+            false => Err(EncoderError::AlreadyFlushed),
+        }
+    }
+
+    /// How many lists and dictionaries are open.
+    pub fn depth(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Writes out everything buffered and flushes the writer.
+    pub fn flush(&mut self) -> Result<(), EncoderError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// The writer, which has not been sent what is still buffered.
+    pub fn get_ref(&self) -> &W {
+        self.writer.writer()
+    }
+
+    /// The writer, which has not been sent what is still buffered; call
+    /// [`flush`](StreamEncoder::flush) before writing to it directly.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.writer_mut()
+    }
+
+    /// Writes out everything buffered and returns the writer, whether or not every list
+    /// and dictionary has been ended; see [`finish`](StreamEncoder::finish) to check that.
+    pub fn into_inner(self) -> Result<W, EncoderError> {
+        Ok(self.writer.into_inner()?)
+    }
+
+    /// Checks that every list and dictionary has been ended, flushes the writer and
+    /// returns it.
+    pub fn finish(self) -> Result<W, EncoderError> {
+        self.check_no_string()?;
+        if !self.open.is_empty() {
+            return Err(EncoderError::Unfinished);
+        }
+        let mut writer = self.into_inner()?;
+        writer.flush()?;
+        Ok(writer)
+    }
+
+    /// Starts a string of the given length, whose contents are written by
+    /// [`event`](StreamEncoder::event).
+    fn begin_string(&mut self, length: u64) -> Result<(), EncoderError> {
+        let key = match self.open.last() {
+            Some(Open::Dictionary {
+                in_value: false, ..
+            }) => Some(Vec::new()),
+            _ => {
+                self.begin_value()?;
+                write_length(&mut self.writer, length)?;
+                None
+            }
+        };
+        self.string = Some(OpenString {
+            remaining: length,
+            key,
+        });
+        self.finish_string()
+    }
+
+    /// Moves on from the string being written if all of its contents have been.
+    fn finish_string(&mut self) -> Result<(), EncoderError> {
+        let Some(OpenString { remaining: 0, key }) = self.string.take_if(|s| s.remaining == 0)
+        else {
+            return Ok(());
+        };
+        match key {
+            Some(key) => self.key(&key),
+            None => self.finish_value(),
+        }
+    }
+
+    fn check_no_string(&self) -> Result<(), EncoderError> {
+        match self.string {
+            Some(_) => Err(EncoderError::StringLength),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that a value may be written here.
+    fn begin_value(&self) -> Result<(), EncoderError> {
+        self.check_no_string()?;
+        match self.open.last() {
+            Some(Open::Dictionary {
+                in_value: false, ..
+            }) => Err(EncoderError::KeyExpected),
+            _ => Ok(()),
+        }
+    }
+
+    /// Moves on from a value that has just been written, flushing if it was a whole
+    /// document and the encoder was asked to.
+    fn finish_value(&mut self) -> Result<(), EncoderError> {
+        match self.open.last_mut() {
+            Some(Open::Dictionary { in_value, .. }) => *in_value = false,
+            Some(Open::List) => {}
+            None => {
+                self.value_start = self.writer.written;
+                if self.flush_each_value {
+                    self.writer.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::DecoderOptions;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn mistakes_write_nothing() {
+        let mut encoder = StreamEncoder::new(Vec::new());
+        assert!(matches!(
+            encoder.key(b"a"),
+            Err(EncoderError::UnexpectedKey)
+        ));
+        assert!(matches!(encoder.end(), Err(EncoderError::NothingToEnd)));
+        encoder.begin_dictionary().unwrap();
+        assert!(matches!(encoder.integer(1), Err(EncoderError::KeyExpected)));
+        encoder.key(b"b").unwrap();
+        assert!(matches!(
+            encoder.key(b"c"),
+            Err(EncoderError::UnexpectedKey)
+        ));
+        assert!(matches!(encoder.end(), Err(EncoderError::MissingValue)));
+        encoder.begin_list().unwrap();
+        encoder.end().unwrap();
+        assert!(matches!(
+            encoder.key(b"a"),
+            Err(EncoderError::UnsortedKey(key)) if key == b"a"
+        ));
+        assert!(matches!(
+            encoder.key(b"b"),
+            Err(EncoderError::DuplicateKey(_))
+        ));
+        encoder.key(b"c").unwrap();
+        encoder.value(&bencode!({ "x": 1 })).unwrap();
+        encoder.flush().unwrap();
+        assert_eq!(encoder.get_ref(), b"d1:ble1:cd1:xi1ee");
+        assert!(matches!(encoder.finish(), Err(EncoderError::Unfinished)));
+    }
+
+    #[test]
+    fn events_round_trip() {
+        let input = b"d1:ad1:xi-1ee1:bl3:abc0:ee";
+        let mut tokenizer = Tokenizer::with_options(
+            &input[..],
+            DecoderOptions {
+                buffer_size: 2,
+                ..DecoderOptions::default()
+            },
+        );
+        let mut encoder = StreamEncoder::new(Vec::new());
+        while let Some(event) = tokenizer.next_event().unwrap() {
+            encoder.event(event).unwrap();
+        }
+        assert_eq!(encoder.finish().unwrap(), input);
+
+        let mut encoder = StreamEncoder::new(Vec::new());
+        encoder
+            .encode_events([
+                Event::DictStart,
+                Event::StringStart(1),
+                Event::StringChunk(b"b"),
+            ])
+            .unwrap();
+        encoder
+            .encode_events([Event::Integer(1), Event::StringStart(1)])
+            .unwrap();
+        assert!(matches!(
+            encoder.event(Event::StringChunk(b"ab")),
+            Err(EncoderError::StringLength)
+        ));
+        assert!(matches!(
+            encoder.event(Event::Integer(1)),
+            Err(EncoderError::StringLength)
+        ));
+        assert!(matches!(
+            encoder.event(Event::StringChunk(b"a")),
+            Err(EncoderError::UnsortedKey(_))
+        ));
+    }
+
+    #[test]
+    fn raw_values_may_hold_integers_wider_than_i64() {
+        let mut encoder = StreamEncoder::new(Vec::new());
+        encoder.begin_list().unwrap();
+        encoder
+            .write_raw_checked(b"i18446744073709551615e")
+            .unwrap();
+        encoder
+            .write_raw_checked(b"d4:sizei-170141183460469231731687303715884105728ee")
+            .unwrap();
+        encoder.end().unwrap();
+        assert_eq!(
+            encoder.finish().unwrap(),
+            b"li18446744073709551615ed4:sizei-170141183460469231731687303715884105728eee"
+        );
+    }
+
+    #[test]
+    fn strings_from_readers_must_be_long_enough() {
+        let mut encoder = StreamEncoder::new(Vec::new());
+        encoder.begin_list().unwrap();
+        encoder.bytes_from_reader(3, &b"abcdef"[..]).unwrap();
+        assert!(matches!(
+            encoder.bytes_from_reader(3, &b"xy"[..]),
+            Err(EncoderError::StringLength)
+        ));
+        assert_eq!(encoder.into_inner().unwrap(), b"l3:abc3:xy");
+    }
+
+    #[test]
+    fn small_writes_are_batched() {
+        #[derive(Default)]
+        struct CountingWriter {
+            bytes: Vec<u8>,
+            writes: usize,
+        }
+
+        impl Write for CountingWriter {
+            fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+                self.writes += 1;
+                self.bytes.extend_from_slice(bytes);
+                Ok(bytes.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut encoder = StreamEncoder::with_capacity(64, CountingWriter::default());
+        encoder.begin_list().unwrap();
+        for integer in 0..100 {
+            encoder.integer(integer).unwrap();
+        }
+        encoder.end().unwrap();
+        assert_eq!(encoder.bytes_written(), 2 + 10 * 3 + 90 * 4);
+        let writer = encoder.finish().unwrap();
+        assert_eq!(writer.bytes.len(), 2 + 10 * 3 + 90 * 4);
+        assert!(writer.writes <= writer.bytes.len() / 64 + 1);
+    }
+
+    #[test]
+    fn whole_values_are_flushed_when_asked() {
+        #[derive(Default)]
+        struct Flushes(Vec<Vec<u8>>);
+
+        impl Write for Flushes {
+            fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+                self.0.last_mut().unwrap().extend_from_slice(bytes);
+                Ok(bytes.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.0.push(Vec::new());
+                Ok(())
+            }
+        }
+
+        let mut encoder = StreamEncoder::new(Flushes(vec![Vec::new()])).flush_each_value(true);
+        encoder.value(&bencode!({ "y": "q" })).unwrap();
+        encoder.begin_dictionary().unwrap();
+        encoder.key(b"y").unwrap();
+        assert!(matches!(
+            encoder.key(b"z"),
+            Err(EncoderError::UnexpectedKey)
+        ));
+        encoder.reset().unwrap();
+        encoder.begin_list().unwrap();
+        encoder.integer(1).unwrap();
+        encoder.end().unwrap();
+        assert_eq!(encoder.get_ref().0, [&b"d1:y1:qe"[..], b"li1ee", b""]);
+    }
+
+    #[test]
+    fn reset_cannot_take_back_what_was_flushed() {
+        let mut encoder = StreamEncoder::with_capacity(4, Vec::new());
+        encoder.integer(1).unwrap();
+        encoder.begin_list().unwrap();
+        encoder.bytes(b"spam").unwrap();
+        assert!(matches!(encoder.reset(), Err(EncoderError::AlreadyFlushed)));
+        encoder.integer(2).unwrap();
+        assert_eq!(encoder.finish().unwrap(), b"i1el4:spami2e");
+
+        let mut encoder = StreamEncoder::new(Vec::new());
+        encoder.begin_list().unwrap();
+        encoder.flush().unwrap();
+        encoder.integer(1).unwrap();
+        assert!(matches!(encoder.reset(), Err(EncoderError::AlreadyFlushed)));
+        assert_eq!(encoder.bytes_written(), 1);
+        encoder.reset().unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+//! Error type shared by the serde serializer and deserializer.
+
+use std::fmt::{self, Display};
+use std::io;
+
+/// Errors produced while serializing or deserializing bencode through serde.
+#[derive(Debug)]
+pub enum Error {
+    /// The underlying writer or reader failed.
+    Io(io::Error),
+    /// A custom error raised by a `Serialize` or `Deserialize` implementation.
+    Message(String),
+    /// The Rust type has no bencode representation (e.g. floating point numbers).
+    UnsupportedType(&'static str),
+    /// Dictionary keys must serialize to byte strings.
+    KeyMustBeAString,
+    /// Two entries of a dictionary had the same key, which bencode does not allow.
+    DuplicateKey(Vec<u8>),
+    /// The input ended in the middle of a value.
+    Eof,
+    /// A byte that cannot start or continue a value at this position.
+    UnexpectedByte(u8),
+    /// An integer, or a string length prefix, that is not a valid number.
+    InvalidInteger,
+    /// Lists and dictionaries nested more deeply than the deserializer allows.
+    DepthLimitExceeded { limit: usize },
+}
+
+/// Alias for a `Result` with the error type [`Error`].
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "io error: {error}"),
+            Error::Message(message) => f.write_str(message),
+            Error::UnsupportedType(name) => write!(f, "{name} cannot be represented in bencode"),
+            Error::KeyMustBeAString => f.write_str("dictionary key must be a string"),
+            Error::DuplicateKey(key) => {
+                write!(f, "duplicate key {:?}", String::from_utf8_lossy(key))
+            }
+            Error::Eof => f.write_str("unexpected end of input"),
+            Error::UnexpectedByte(byte) => write!(f, "unexpected byte 0x{byte:02x}"),
+            Error::InvalidInteger => f.write_str("invalid integer"),
+            Error::DepthLimitExceeded { limit } => {
+                write!(f, "lists and dictionaries nested more than {limit} deep")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
@@ -0,0 +1,154 @@
+//! Buffered access to the reader behind a [`Decoder`](crate::Decoder).
+
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+/// A reader, seen through a buffer that the decoder scans instead of reading byte by byte.
+///
+/// The buffer is the reader's own for a [`BufRead`], and otherwise one kept here.
+pub(crate) struct Input<R> {
+    reader: R,
+    /// The reader's [`BufRead`] methods, if it was given as one.
+    buf_read: Option<BufReadMethods<R>>,
+    buffer: Box<[u8]>,
+    /// How much of `buffer` has been consumed.
+    position: usize,
+    /// How much of `buffer` holds bytes read from the reader.
+    filled: usize,
+}
+
+/// Stands in for a `R: BufRead` bound that the decoder as a whole does not have.
+struct BufReadMethods<R> {
+    fill_buf: for<'a> fn(&'a mut R) -> io::Result<&'a [u8]>,
+    consume: fn(&mut R, usize),
+}
+
+impl<R: Read> Input<R> {
+    /// Reads from `reader` through a buffer of `capacity` bytes, at least one.
+    pub(crate) fn new(reader: R, capacity: usize) -> Self {
+        Input {
+            reader,
+            buf_read: None,
+            buffer: vec![0; capacity.max(1)].into_boxed_slice(),
+            position: 0,
+            filled: 0,
+        }
+    }
+
+    /// Reads from `reader` through its own buffer.
+    pub(crate) fn with_buf_read(reader: R) -> Self
+    where
+        R: BufRead,
+    {
+        Input {
+            reader,
+            buf_read: Some(BufReadMethods {
+                fill_buf: R::fill_buf,
+                consume: R::consume,
+            }),
+            buffer: Box::new([]),
+            position: 0,
+            filled: 0,
+        }
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// The bytes in the buffer kept here that have not been consumed.
+    pub(crate) fn buffer(&self) -> &[u8] {
+        &self.buffer[self.position..self.filled]
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Moves back `distance` bytes from what has been consumed, dropping whatever is
+    /// buffered.
+    pub(crate) fn seek_back(&mut self, distance: u64) -> io::Result<()>
+    where
+        R: Seek,
+    {
+        // A `BufRead` seeks relative to what has been consumed of it already.
+        let buffered = (self.filled - self.position) as u64;
+        self.position = 0;
+        self.filled = 0;
+        let distance = i64::try_from(distance + buffered)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "seeking back too far"))?;
+        self.reader.seek(SeekFrom::Current(-distance)).map(drop)
+    }
+
+    /// Returns the bytes buffered so far, reading more first if there are none. Only at
+    /// the end of the input is nothing returned.
+    pub(crate) fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if let Some(methods) = &self.buf_read {
+            loop {
+                match (methods.fill_buf)(&mut self.reader) {
+                    Ok(_) => break,
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(error) => return Err(error),
+                }
+            }
+            // The bytes stay buffered until consumed, so asking again hands back the same
+            // ones, this time borrowed for as long as the caller needs them.
+            return (methods.fill_buf)(&mut self.reader);
+        }
+        if self.position == self.filled {
+            self.position = 0;
+            self.filled = loop {
+                match self.reader.read(&mut self.buffer) {
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    result => break result?,
+                }
+            };
+        }
+        Ok(&self.buffer[self.position..self.filled])
+    }
+
+    /// Calls `scan` with the bytes [`fill_buf`](Input::fill_buf) returns.
+    pub(crate) fn fill<T>(&mut self, scan: impl FnOnce(&[u8]) -> T) -> io::Result<T> {
+        self.fill_buf().map(scan)
+    }
+
+    /// Marks `amount` of the bytes passed to [`fill`](Input::fill) as used.
+    pub(crate) fn consume(&mut self, amount: usize) {
+        match &self.buf_read {
+            Some(methods) => (methods.consume)(&mut self.reader, amount),
+            None => self.position += amount,
+        }
+    }
+
+    /// Copies up to `length` bytes into `writer`, fewer only if the input runs out, and
+    /// returns how many were copied.
+    pub(crate) fn copy_to<W: Write + ?Sized>(
+        &mut self,
+        length: u64,
+        writer: &mut W,
+    ) -> io::Result<u64> {
+        let mut copied = 0;
+        while copied < length {
+            if self.buf_read.is_none() && self.position == self.filled {
+                // Nothing is buffered, so skip the buffer rather than refill it only to
+                // copy out of it again.
+                copied += io::copy(&mut (&mut self.reader).take(length - copied), writer)?;
+                break;
+            }
+            let wanted = usize::try_from(length - copied).unwrap_or(usize::MAX);
+            let amount = self.fill(|available| {
+                let amount = available.len().min(wanted);
+                writer.write_all(&available[..amount]).map(|()| amount)
+            })??;
+            if amount == 0 {
+                break;
+            }
+            self.consume(amount);
+            copied += amount as u64;
+        }
+        Ok(copied)
+    }
+}
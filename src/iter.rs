@@ -0,0 +1,370 @@
+//! Iteration over the contents of lists and dictionaries.
+
+use std::collections::btree_map;
+use std::slice;
+use std::vec;
+
+use crate::path::{Path, Segment};
+use crate::value::Bencode;
+
+/// Something yielded when iterating over a [`Bencode`] value: an element of a list or an
+/// entry of a dictionary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item<K, V> {
+    Element(V),
+    Entry(K, V),
+}
+
+impl<K, V> Item<K, V> {
+    /// The dictionary key, if this is an entry.
+    pub fn key(&self) -> Option<&K> {
+        match self {
+            Item::Element(_) => None,
+            Item::Entry(key, _) => Some(key),
+        }
+    }
+
+    pub fn value(&self) -> &V {
+        match self {
+            Item::Element(value) | Item::Entry(_, value) => value,
+        }
+    }
+
+    pub fn into_value(self) -> V {
+        match self {
+            Item::Element(value) | Item::Entry(_, value) => value,
+        }
+    }
+}
+
+/// Borrowing iterator returned by [`Bencode::iter`].
+pub struct Iter<'a>(IterInner<slice::Iter<'a, Bencode>, btree_map::Iter<'a, Vec<u8>, Bencode>>);
+
+/// Mutably borrowing iterator returned by [`Bencode::iter_mut`].
+pub struct IterMut<'a>(
+    IterInner<slice::IterMut<'a, Bencode>, btree_map::IterMut<'a, Vec<u8>, Bencode>>,
+);
+
+/// Owning iterator returned by [`Bencode::into_iter`].
+pub struct IntoIter(IterInner<vec::IntoIter<Bencode>, btree_map::IntoIter<Vec<u8>, Bencode>>);
+
+enum IterInner<L, D> {
+    List(L),
+    Dictionary(D),
+    Empty,
+}
+
+impl<L, D, K, V> IterInner<L, D>
+where
+    L: Iterator<Item = V>,
+    D: Iterator<Item = (K, V)>,
+{
+    fn next(&mut self) -> Option<Item<K, V>> {
+        match self {
+            IterInner::List(list) => list.next().map(Item::Element),
+            IterInner::Dictionary(dictionary) => dictionary
+                .next()
+                .map(|(key, value)| Item::Entry(key, value)),
+            IterInner::Empty => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            IterInner::List(list) => list.size_hint(),
+            IterInner::Dictionary(dictionary) => dictionary.size_hint(),
+            IterInner::Empty => (0, Some(0)),
+        }
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Item<&'a Vec<u8>, &'a Bencode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = Item<&'a Vec<u8>, &'a mut Bencode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl Iterator for IntoIter {
+    type Item = Item<Vec<u8>, Bencode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// Depth-first iterator returned by [`Bencode::walk`].
+pub struct Walk<'a> {
+    stack: Vec<(Path, &'a Bencode)>,
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = (Path, &'a Bencode);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, value) = self.stack.pop()?;
+        // Children are pushed in reverse so that they come off the stack in order.
+        match value {
+            Bencode::List(list) => self.stack.extend(
+                list.iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(index, element)| (path.join(Segment::Index(index)), element)),
+            ),
+            Bencode::Dictionary(dictionary) => self.stack.extend(
+                dictionary
+                    .iter()
+                    .rev()
+                    .map(|(key, value)| (path.join(Segment::Key(key.clone())), value)),
+            ),
+            _ => {}
+        }
+        Some((path, value))
+    }
+}
+
+impl Bencode {
+    /// Visits this value and everything nested inside it, depth first, along with the path
+    /// to each.
+    ///
+    /// The value itself comes first, at the root path, and each container comes before its
+    /// contents.
+    ///
+    /// ```
+    /// use bencode::bencode;
+    ///
+    /// let torrent = bencode!({ "info": { "files": [{ "length": 5 }, { "length": 7 }] } });
+    /// let lengths: Vec<String> = torrent
+    ///     .walk()
+    ///     .filter(|(_, value)| value.as_integer().is_some())
+    ///     .map(|(path, _)| path.to_string())
+    ///     .collect();
+    /// assert_eq!(lengths, ["info.files[0].length", "info.files[1].length"]);
+    /// ```
+    pub fn walk(&self) -> Walk<'_> {
+        Walk {
+            stack: vec![(Path::root(), self)],
+        }
+    }
+
+    /// Iterates over the elements of a list or the entries of a dictionary.
+    ///
+    /// Integers and strings yield nothing.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(match self {
+            Bencode::List(list) => IterInner::List(list.iter()),
+            Bencode::Dictionary(dictionary) => IterInner::Dictionary(dictionary.iter()),
+            _ => IterInner::Empty,
+        })
+    }
+
+    /// Like [`iter`](Bencode::iter), but yields mutable references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut(match self {
+            Bencode::List(list) => IterInner::List(list.iter_mut()),
+            Bencode::Dictionary(dictionary) => IterInner::Dictionary(dictionary.iter_mut()),
+            _ => IterInner::Empty,
+        })
+    }
+}
+
+impl Bencode {
+    /// Keeps only the dictionary entries for which `f` returns `true`.
+    ///
+    /// Does nothing unless this is a dictionary.
+    pub fn retain_entries<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[u8], &mut Bencode) -> bool,
+    {
+        if let Bencode::Dictionary(dictionary) = self {
+            dictionary.retain(|key, value| f(key, value));
+        }
+    }
+
+    /// Keeps only the list elements for which `f` returns `true`.
+    ///
+    /// Does nothing unless this is a list.
+    pub fn retain_elements<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Bencode) -> bool,
+    {
+        if let Bencode::List(list) = self {
+            list.retain_mut(f);
+        }
+    }
+
+    /// Keeps only the list elements and dictionary entries for which `f` returns `true`, at
+    /// every level of nesting.
+    ///
+    /// Containers are filtered before their contents, so `f` never sees anything inside a
+    /// value it has already removed. Values are visited depth first, in order, without
+    /// recursing, so deeply nested input cannot overflow the stack.
+    ///
+    /// ```
+    /// use bencode::bencode;
+    /// use bencode::iter::Item;
+    ///
+    /// let mut torrent = bencode!({
+    ///     "announce": "http://a",
+    ///     "info": { "name": "x", "private": 1 },
+    /// });
+    /// torrent.retain_recursive(|item| {
+    ///     item.key().map_or(true, |key| key.as_slice() != b"private")
+    /// });
+    /// assert_eq!(torrent, bencode!({ "announce": "http://a", "info": { "name": "x" } }));
+    /// ```
+    pub fn retain_recursive<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Item<&Vec<u8>, &Bencode>) -> bool,
+    {
+        let mut pending = vec![self];
+        while let Some(value) = pending.pop() {
+            // Children are pushed in reverse so that they come off the stack in order.
+            match value {
+                Bencode::List(list) => {
+                    list.retain(|element| f(Item::Element(element)));
+                    pending.extend(list.iter_mut().rev());
+                }
+                Bencode::Dictionary(dictionary) => {
+                    dictionary.retain(|key, value| f(Item::Entry(key, value)));
+                    pending.extend(dictionary.values_mut().rev());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl IntoIterator for Bencode {
+    type Item = Item<Vec<u8>, Bencode>;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter(match self {
+            Bencode::List(list) => IterInner::List(list.into_iter()),
+            Bencode::Dictionary(dictionary) => IterInner::Dictionary(dictionary.into_iter()),
+            _ => IterInner::Empty,
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a Bencode {
+    type Item = Item<&'a Vec<u8>, &'a Bencode>;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Bencode {
+    type Item = Item<&'a Vec<u8>, &'a mut Bencode>;
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> IterMut<'a> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_visits_containers_before_their_contents_in_order() {
+        let value = bencode!({ "b": [1, { "c": 2 }], "a": "x" });
+        let walked: Vec<(String, Bencode)> = value
+            .walk()
+            .map(|(path, value)| (path.to_string(), value.clone()))
+            .collect();
+        assert_eq!(
+            walked,
+            [
+                (String::new(), value.clone()),
+                ("a".to_owned(), bencode!("x")),
+                ("b".to_owned(), bencode!([1, { "c": 2 }])),
+                ("b[0]".to_owned(), bencode!(1)),
+                ("b[1]".to_owned(), bencode!({ "c": 2 })),
+                ("b[1].c".to_owned(), bencode!(2)),
+            ]
+        );
+        assert_eq!(bencode!(1).walk().count(), 1);
+    }
+
+    #[test]
+    fn retain_entries_may_change_what_it_keeps() {
+        let mut value = bencode!({ "a": 1, "b": 2, "c": 3 });
+        value.retain_entries(|key, value| {
+            *value = bencode!(10);
+            key != b"b"
+        });
+        assert_eq!(value, bencode!({ "a": 10, "c": 10 }));
+
+        let mut list = bencode!([1]);
+        list.retain_entries(|_, _| false);
+        assert_eq!(list, bencode!([1]));
+    }
+
+    #[test]
+    fn retain_elements_may_change_what_it_keeps() {
+        let mut value = bencode!([1, "x", 2]);
+        value.retain_elements(|element| match element {
+            Bencode::Integer(integer) => {
+                *integer *= 10;
+                true
+            }
+            _ => false,
+        });
+        assert_eq!(value, bencode!([10, 20]));
+
+        let mut dictionary = bencode!({ "a": 1 });
+        dictionary.retain_elements(|_| false);
+        assert_eq!(dictionary, bencode!({ "a": 1 }));
+    }
+
+    #[test]
+    fn retain_recursive_filters_every_level_in_order() {
+        let mut value = bencode!({ "a": [1, "drop", [2, "drop"]], "drop": ["never seen"], "z": 3 });
+        let mut seen = Vec::new();
+        value.retain_recursive(|item| {
+            let value = *item.value();
+            seen.push(value.clone());
+            *value != bencode!("drop") && item.key().is_none_or(|key| key.as_slice() != b"drop")
+        });
+        assert_eq!(value, bencode!({ "a": [1, [2]], "z": 3 }));
+        assert_eq!(
+            seen,
+            [
+                bencode!([1, "drop", [2, "drop"]]),
+                bencode!(["never seen"]),
+                bencode!(3),
+                bencode!(1),
+                bencode!("drop"),
+                bencode!([2, "drop"]),
+                bencode!(2),
+                bencode!("drop"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Conversions to and from [`serde_json::Value`], behind the `json` feature.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Write};
+
+use serde_json::{Map, Number, Value};
+
+use crate::path::{Path, Segment};
+use crate::value::Bencode;
+
+/// Errors produced by [`Bencode::from_json`], for JSON values that bencode has nothing to
+/// stand for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FromJsonError {
+    Null {
+        path: Path,
+    },
+    /// A number with a fractional part or exponent, or too large for an `i64` or `u64`.
+    Float {
+        path: Path,
+    },
+}
+
+impl FromJsonError {
+    /// Where in the JSON value the error was found.
+    pub fn path(&self) -> &Path {
+        match self {
+            FromJsonError::Null { path } | FromJsonError::Float { path } => path,
+        }
+    }
+}
+
+impl Display for FromJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let found = match self {
+            FromJsonError::Null { .. } => "null",
+            FromJsonError::Float { .. } => "non-integer number",
+        };
+        write!(f, "JSON {found} has no bencode equivalent")?;
+        if !self.path().segments().is_empty() {
+            write!(f, " at {}", self.path())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FromJsonError {}
+
+/// How [`Bencode::to_json_with`] represents strings and keys that are not valid UTF-8,
+/// which JSON strings cannot hold as they are.
+///
+/// Strings that are valid UTF-8 are always converted as they are, so a binary string can
+/// end up the same as a text one. Where that happens to two keys of a dictionary, the
+/// later one in key order is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryStrings {
+    /// Lowercase hex, which suits hashes such as `pieces` in debugging dumps.
+    #[default]
+    Hex,
+    /// Standard base64 with padding, which is more compact for web frontends to decode.
+    Base64,
+    /// UTF-8 with invalid bytes replaced by U+FFFD, which loses the bytes but keeps
+    /// mostly-text strings readable.
+    Lossy,
+    /// Fail with [`ToJsonError`].
+    Error,
+}
+
+/// The error returned by [`Bencode::to_json_with`] under [`BinaryStrings::Error`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToJsonError {
+    /// Where the string that is not valid UTF-8 is, or the dictionary it is a key of.
+    pub path: Path,
+}
+
+impl Display for ToJsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("string is not valid UTF-8")?;
+        if !self.path.segments().is_empty() {
+            write!(f, " at {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ToJsonError {}
+
+impl Bencode {
+    /// Converts the value to JSON, with strings that are not valid UTF-8 as hex.
+    ///
+    /// - Integers become numbers, except those outside the range of an `i64` or `u64`,
+    ///   which JSON numbers do not reliably hold and so become strings of their digits.
+    /// - Strings and dictionary keys become JSON strings; see [`BinaryStrings`].
+    /// - Lists become arrays and dictionaries objects.
+    ///
+    /// ```
+    /// use bencode::bencode;
+    /// use serde_json::json;
+    ///
+    /// let value = bencode!({ "length": 5, "pieces": b"\x00\xff".as_slice() });
+    /// assert_eq!(value.to_json(), json!({ "length": 5, "pieces": "00ff" }));
+    /// ```
+    pub fn to_json(&self) -> Value {
+        self.to_json_with(BinaryStrings::Hex)
+            .expect("hex can represent any string")
+    }
+
+    /// Converts the value to JSON as [`to_json`](Bencode::to_json) does, with strings
+    /// that are not valid UTF-8 represented as `binary` says.
+    ///
+    /// ```
+    /// use bencode::bencode;
+    /// use bencode::json::BinaryStrings;
+    /// use serde_json::json;
+    ///
+    /// let value = bencode!({ "id": b"ab\xff".as_slice() });
+    /// assert_eq!(value.to_json_with(BinaryStrings::Base64), Ok(json!({ "id": "YWL/" })));
+    /// assert_eq!(value.to_json_with(BinaryStrings::Lossy), Ok(json!({ "id": "ab\u{fffd}" })));
+    /// let error = value.to_json_with(BinaryStrings::Error).unwrap_err();
+    /// assert_eq!(error.path.to_string(), "id");
+    /// ```
+    pub fn to_json_with(&self, binary: BinaryStrings) -> Result<Value, ToJsonError> {
+        to_json(self, binary, &mut Path::root())
+    }
+
+    /// Converts a JSON value to bencode.
+    ///
+    /// Strings become byte strings holding their UTF-8, booleans become `0` and `1` as
+    /// they do when serialized, arrays become lists and objects dictionaries. `null` and
+    /// numbers that are not integers have no equivalent and are errors.
+    ///
+    /// ```
+    /// use bencode::{bencode, Bencode};
+    /// use serde_json::json;
+    ///
+    /// let value = Bencode::from_json(&json!({ "y": "q", "ro": true, "n": [-1] })).unwrap();
+    /// assert_eq!(value, bencode!({ "n": [-1], "ro": 1, "y": "q" }));
+    /// assert!(Bencode::from_json(&json!({ "a": [0.5] })).is_err());
+    /// ```
+    pub fn from_json(json: &Value) -> Result<Bencode, FromJsonError> {
+        from_json(json, &mut Path::root())
+    }
+}
+
+fn from_json(json: &Value, path: &mut Path) -> Result<Bencode, FromJsonError> {
+    Ok(match json {
+        Value::Null => return Err(FromJsonError::Null { path: path.clone() }),
+        Value::Bool(boolean) => Bencode::Integer(i128::from(*boolean)),
+        Value::Number(number) => {
+            from_number(number).ok_or_else(|| FromJsonError::Float { path: path.clone() })?
+        }
+        Value::String(string) => Bencode::String(string.as_bytes().to_vec()),
+        Value::Array(array) => {
+            let mut list = Vec::with_capacity(array.len());
+            for (index, element) in array.iter().enumerate() {
+                path.push(Segment::Index(index));
+                list.push(from_json(element, path)?);
+                path.pop();
+            }
+            Bencode::List(list)
+        }
+        Value::Object(object) => {
+            let mut dictionary = BTreeMap::new();
+            for (key, value) in object {
+                path.push(Segment::Key(key.as_bytes().to_vec()));
+                dictionary.insert(key.as_bytes().to_vec(), from_json(value, path)?);
+                path.pop();
+            }
+            Bencode::Dictionary(dictionary)
+        }
+    })
+}
+
+fn from_number(number: &Number) -> Option<Bencode> {
+    match (number.as_i64(), number.as_u64()) {
+        (Some(integer), _) => Some(Bencode::Integer(integer.into())),
+        (_, Some(integer)) => Some(Bencode::Integer(integer.into())),
+        _ => None,
+    }
+}
+
+fn to_json(value: &Bencode, binary: BinaryStrings, path: &mut Path) -> Result<Value, ToJsonError> {
+    Ok(match value {
+        Bencode::Integer(integer) => match (i64::try_from(*integer), u64::try_from(*integer)) {
+            (Ok(integer), _) => Value::from(integer),
+            (_, Ok(integer)) => Value::from(integer),
+            _ => Value::String(integer.to_string()),
+        },
+        #[cfg(feature = "num-bigint")]
+        Bencode::BigInteger(integer) => Value::String(integer.to_string()),
+        Bencode::String(bytes) => Value::String(json_string(bytes, binary, path)?),
+        Bencode::List(list) => {
+            let mut array = Vec::with_capacity(list.len());
+            for (index, element) in list.iter().enumerate() {
+                path.push(Segment::Index(index));
+                array.push(to_json(element, binary, path)?);
+                path.pop();
+            }
+            Value::Array(array)
+        }
+        Bencode::Dictionary(dictionary) => {
+            let mut object = Map::new();
+            for (key, value) in dictionary {
+                let key_string = json_string(key, binary, path)?;
+                path.push(Segment::Key(key.clone()));
+                object.insert(key_string, to_json(value, binary, path)?);
+                path.pop();
+            }
+            Value::Object(object)
+        }
+    })
+}
+
+/// Turns a string or key into JSON text, as `binary` says unless it is valid UTF-8.
+fn json_string(bytes: &[u8], binary: BinaryStrings, path: &Path) -> Result<String, ToJsonError> {
+    if let Ok(string) = std::str::from_utf8(bytes) {
+        return Ok(string.to_owned());
+    }
+    Ok(match binary {
+        BinaryStrings::Hex => {
+            let mut hex = String::with_capacity(bytes.len() * 2);
+            for byte in bytes {
+                write!(hex, "{byte:02x}").expect("writing to a String");
+            }
+            hex
+        }
+        BinaryStrings::Base64 => base64(bytes),
+        BinaryStrings::Lossy => String::from_utf8_lossy(bytes).into_owned(),
+        BinaryStrings::Error => return Err(ToJsonError { path: path.clone() }),
+    })
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let bits = group
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (position, &byte)| {
+                bits | u32::from(byte) << (16 - 8 * position)
+            });
+        for position in 0..4 {
+            if position <= group.len() {
+                let index = (bits >> (18 - 6 * position)) & 0x3f;
+                encoded.push(char::from(ALPHABET[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn large_integers_become_strings() {
+        let value = Bencode::List(vec![
+            Bencode::integer(i64::MIN),
+            Bencode::integer(u64::MAX),
+            Bencode::integer(i128::MAX),
+        ]);
+        assert_eq!(
+            value.to_json(),
+            json!([i64::MIN, u64::MAX, i128::MAX.to_string()])
+        );
+    }
+
+    #[test]
+    fn base64_pads_partial_groups() {
+        for (bytes, encoded) in [
+            (&b""[..], ""),
+            (b"\xff", "/w=="),
+            (b"\xff\xfe", "//4="),
+            (b"\xff\xfe\xfd", "//79"),
+            (b"\x00\x80\xff\x10", "AID/EA=="),
+        ] {
+            assert_eq!(base64(bytes), encoded);
+        }
+    }
+
+    #[test]
+    fn errors_report_path() {
+        let error = Bencode::from_json(&json!({ "a": [1, null] })).unwrap_err();
+        assert_eq!(error.path().to_string(), "a[1]");
+        assert_eq!(
+            error.to_string(),
+            "JSON null has no bencode equivalent at a[1]"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+#[macro_use]
+mod macros;
+
+pub mod arc;
+#[cfg(any(feature = "futures", feature = "tokio"))]
+mod async_buffer;
+#[cfg(feature = "futures")]
+pub mod async_io;
+#[cfg(feature = "serde")]
+pub mod borrowed;
+pub mod builder;
+pub mod canonical;
+#[cfg(feature = "tokio")]
+pub mod codec;
+pub mod convert;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod decoder;
+pub mod diff;
+#[cfg(feature = "digest")]
+mod digest;
+mod display;
+pub mod encoder;
+#[cfg(feature = "serde")]
+pub mod error;
+mod input;
+pub mod iter;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod merge;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod ord;
+pub mod path;
+pub mod push;
+#[cfg(feature = "serde")]
+pub mod raw;
+#[cfg(feature = "serde")]
+pub mod read;
+#[cfg(feature = "serde")]
+pub mod ser;
+#[cfg(feature = "bytes")]
+pub mod shared;
+pub mod spanned;
+pub mod stats;
+pub mod text;
+pub mod tokenizer;
+pub mod transform;
+pub mod value;
+pub mod visit;
+
+pub use arc::ArcBencode;
+#[cfg(feature = "futures")]
+pub use async_io::{AsyncDecoder, AsyncEncoder};
+#[cfg(feature = "derive")]
+pub use bencode_derive::{FromBencode, ToBencode};
+#[cfg(feature = "serde")]
+pub use borrowed::BencodeRef;
+pub use builder::{DictBuilder, ListBuilder};
+pub use canonical::canonicalize_bytes;
+#[cfg(feature = "tokio")]
+pub use codec::BencodeCodec;
+pub use convert::{ConversionError, FromBencode, ToBencode};
+#[cfg(feature = "serde")]
+pub use de::{from_reader, from_slice, from_slice_complete, Deserializer};
+pub use decoder::{Decoder, DecoderError, DecoderOptions, DecoderResult, Recovered};
+#[cfg(feature = "digest")]
+pub use digest::HashingWriter;
+pub use encoder::{EncoderError, StreamEncoder};
+#[cfg(feature = "serde")]
+pub use error::{Error, Result};
+#[cfg(feature = "json")]
+pub use json::{BinaryStrings, FromJsonError, ToJsonError};
+pub use merge::{Conflict, MergeStrategy};
+#[cfg(feature = "mmap")]
+pub use mmap::decode_file;
+pub use path::{GetError, Path, Segment};
+pub use push::{Progress, PushDecoder};
+#[cfg(feature = "serde")]
+pub use raw::Raw;
+#[cfg(feature = "serde")]
+pub use ser::{to_vec, to_writer, EnumRepresentation, Serializer, SerializerOptions};
+#[cfg(feature = "bytes")]
+pub use shared::SharedBencode;
+pub use spanned::{Spanned, SpannedValue};
+pub use stats::Stats;
+pub use tokenizer::{validate, validate_with_options, Event, Tokenizer};
+pub use value::{Bencode, BencodeKind};
+pub use visit::BencodeVisitor;
@@ -0,0 +1,65 @@
+/// Builds a [`Bencode`](crate::Bencode) value from JSON-like literal syntax.
+///
+/// Lists are written `[...]` and dictionaries `{key: value, ...}`. Any other value is
+/// converted with `Bencode::from`, so integers, strings, byte slices and existing
+/// `Bencode` values can be interpolated.
+///
+/// ```
+/// use bencode::bencode;
+///
+/// let torrent = bencode!({
+///     "announce": "http://tracker.example/announce",
+///     "info": {"length": 1234, "name": "file.txt"},
+///     "url-list": ["http://a", "http://b"],
+/// });
+/// ```
+#[macro_export]
+macro_rules! bencode {
+    ([]) => {
+        $crate::Bencode::List(::std::vec::Vec::new())
+    };
+    ([ $($tt:tt)+ ]) => {
+        $crate::Bencode::List($crate::bencode!(@list [] $($tt)+))
+    };
+    ({}) => {
+        $crate::Bencode::Dictionary(::std::collections::BTreeMap::new())
+    };
+    ({ $($tt:tt)+ }) => {{
+        let mut dictionary = ::std::collections::BTreeMap::new();
+        $crate::bencode!(@dictionary dictionary $($tt)+);
+        $crate::Bencode::Dictionary(dictionary)
+    }};
+
+    // Collects list elements into `[$done,*]` until the input runs out.
+    (@list [$($done:expr,)*]) => {
+        ::std::vec![$($done),*]
+    };
+    (@list [$($done:expr,)*] [$($list:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@list [$($done,)* $crate::bencode!([$($list)*]),] $($($rest)*)?)
+    };
+    (@list [$($done:expr,)*] {$($dictionary:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@list [$($done,)* $crate::bencode!({$($dictionary)*}),] $($($rest)*)?)
+    };
+    (@list [$($done:expr,)*] $next:expr $(, $($rest:tt)*)?) => {
+        $crate::bencode!(@list [$($done,)* $crate::bencode!($next),] $($($rest)*)?)
+    };
+
+    // Inserts dictionary entries into `$dictionary` one at a time.
+    (@dictionary $dictionary:ident) => {};
+    (@dictionary $dictionary:ident $key:tt : [$($list:tt)*] $(, $($rest:tt)*)?) => {
+        $dictionary.insert(::std::vec::Vec::<u8>::from($key), $crate::bencode!([$($list)*]));
+        $crate::bencode!(@dictionary $dictionary $($($rest)*)?);
+    };
+    (@dictionary $dictionary:ident $key:tt : {$($inner:tt)*} $(, $($rest:tt)*)?) => {
+        $dictionary.insert(::std::vec::Vec::<u8>::from($key), $crate::bencode!({$($inner)*}));
+        $crate::bencode!(@dictionary $dictionary $($($rest)*)?);
+    };
+    (@dictionary $dictionary:ident $key:tt : $value:expr $(, $($rest:tt)*)?) => {
+        $dictionary.insert(::std::vec::Vec::<u8>::from($key), $crate::bencode!($value));
+        $crate::bencode!(@dictionary $dictionary $($($rest)*)?);
+    };
+
+    ($other:expr) => {
+        $crate::Bencode::from($other)
+    };
+}
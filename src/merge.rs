@@ -0,0 +1,101 @@
+//! Deep merging of [`Bencode`] values.
+
+use std::collections::btree_map::Entry;
+
+use crate::value::Bencode;
+
+/// Which side wins when both values have something different at the same place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Conflict {
+    /// The incoming value replaces the existing one.
+    #[default]
+    Overwrite,
+    /// The existing value is kept and the incoming one discarded.
+    KeepExisting,
+}
+
+/// Controls how [`Bencode::merge`] combines two values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergeStrategy {
+    pub conflict: Conflict,
+    /// Append incoming list elements to existing lists instead of treating two lists as a
+    /// conflict.
+    pub concatenate_lists: bool,
+}
+
+impl Bencode {
+    /// Merges `other` into `self`.
+    ///
+    /// Dictionaries are merged key by key, recursing into values present on both sides.
+    /// Any other pair of values is resolved according to `strategy`.
+    pub fn merge(&mut self, other: Bencode, strategy: MergeStrategy) {
+        match (self, other) {
+            (Bencode::Dictionary(dictionary), Bencode::Dictionary(other)) => {
+                for (key, value) in other {
+                    match dictionary.entry(key) {
+                        Entry::Occupied(mut entry) => entry.get_mut().merge(value, strategy),
+                        Entry::Vacant(entry) => {
+                            entry.insert(value);
+                        }
+                    }
+                }
+            }
+            (Bencode::List(list), Bencode::List(other)) if strategy.concatenate_lists => {
+                list.extend(other);
+            }
+            (this, other) => {
+                if strategy.conflict == Conflict::Overwrite {
+                    *this = other;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_dictionaries_are_merged_key_by_key() {
+        let mut value = bencode!({ "info": { "length": 1, "name": "a" }, "announce": "x" });
+        value.merge(
+            bencode!({ "info": { "private": 1 }, "comment": "c" }),
+            MergeStrategy::default(),
+        );
+        assert_eq!(
+            value,
+            bencode!({
+                "announce": "x",
+                "comment": "c",
+                "info": { "length": 1, "name": "a", "private": 1 },
+            })
+        );
+    }
+
+    #[test]
+    fn conflicting_values_follow_the_strategy() {
+        let existing = bencode!({ "info": { "length": 1 }, "list": [1] });
+        let incoming = bencode!({ "info": { "length": "2" }, "list": [2] });
+
+        let mut value = existing.clone();
+        value.merge(incoming.clone(), MergeStrategy::default());
+        assert_eq!(value, incoming);
+
+        let mut value = existing.clone();
+        let keep = MergeStrategy {
+            conflict: Conflict::KeepExisting,
+            ..MergeStrategy::default()
+        };
+        value.merge(incoming.clone(), keep);
+        assert_eq!(value, existing);
+
+        let mut value = existing;
+        let concatenate = MergeStrategy {
+            concatenate_lists: true,
+            ..keep
+        };
+        value.merge(incoming, concatenate);
+        assert_eq!(value, bencode!({ "info": { "length": 1 }, "list": [1, 2] }));
+    }
+}
@@ -0,0 +1,93 @@
+//! Decoding files by mapping them into memory, behind the `mmap` feature.
+
+use std::fs::File;
+use std::path::Path;
+
+use memmap2::Mmap;
+use serde::de::DeserializeOwned;
+
+use crate::de::from_slice_complete;
+use crate::error::Result;
+
+/// Deserializes the single value in the file at `path`, which is mapped into memory
+/// rather than read.
+///
+/// Decoding then runs over the file's contents as a slice, as [`from_slice`] does, so
+/// nothing is read a byte at a time or copied before it is decoded, and only the pages
+/// the value touches are ever loaded. Lists and dictionaries nested more deeply than
+/// [`DecoderOptions::max_depth`] allows by default are rejected with
+/// [`Error::DepthLimitExceeded`].
+///
+/// The file must not be changed while it is being decoded. If another process writes
+/// to it at the time, the value may come out inconsistent, and if it shrinks the
+/// process may be killed.
+///
+/// [`from_slice`]: crate::from_slice
+/// [`DecoderOptions::max_depth`]: crate::DecoderOptions::max_depth
+/// [`Error::DepthLimitExceeded`]: crate::Error::DepthLimitExceeded
+///
+/// ```
+/// use bencode::Bencode;
+///
+/// let name = format!("decode_file-{}.torrent", std::process::id());
+/// let path = std::env::temp_dir().join(name);
+/// std::fs::write(&path, b"d4:infod6:lengthi5e4:name5:a.txtee").unwrap();
+/// let torrent: Bencode = bencode::decode_file(&path).unwrap();
+/// assert_eq!(torrent.get_str("info.name").unwrap(), "a.txt");
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn decode_file<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T> {
+    let file = File::open(path)?;
+    // SAFETY: the caller is told not to change the file while it is mapped.
+    let map = unsafe { Mmap::map(&file)? };
+    from_slice_complete(&map)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::error::Error;
+    use crate::value::Bencode;
+
+    /// A file in the temporary directory, removed again when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let name = format!("bencode-{}-{name}", std::process::id());
+            let path = std::env::temp_dir().join(name);
+            std::fs::write(&path, contents).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn files_hold_exactly_one_value() {
+        let file = TempFile::new("one.torrent", b"d4:infod6:lengthi5eee");
+        let value: Bencode = decode_file(&file.0).unwrap();
+        assert_eq!(value.get_integer("info.length"), Ok(5));
+
+        let file = TempFile::new("two.torrent", b"i1ei2e");
+        assert!(matches!(
+            decode_file::<Bencode>(&file.0),
+            Err(Error::UnexpectedByte(b'i'))
+        ));
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let file = TempFile::new("deep.torrent", &vec![b'l'; 1_000_000]);
+        assert!(matches!(
+            decode_file::<Bencode>(&file.0),
+            Err(Error::DepthLimitExceeded { .. })
+        ));
+    }
+}
@@ -0,0 +1,153 @@
+//! Ordering of [`Bencode`] values by their canonical encoding.
+
+use std::cmp::Ordering;
+
+use crate::canonical::{encode_integer, encode_length, INTEGER_LEN, LENGTH_LEN};
+use crate::value::Bencode;
+
+/// Values are ordered as their canonical encodings compare byte by byte.
+///
+/// Since every string encoding starts with a digit, and `d` < `i` < `l`, strings sort
+/// first, followed by dictionaries, integers and lists. Integers compare by their decimal
+/// text and closing `e`, so `i-1e` < `i10e` < `i1e` < `i9e`, and strings compare by their
+/// length prefix before their contents. An encoded value is never a prefix of another, so
+/// lists and dictionaries compare element by element, and running out of elements compares
+/// the closing `e` against whatever the other container has next.
+///
+/// With the `num-bigint` feature, an integer held as a [`Bencode::BigInteger`] sorts just
+/// after the same integer held as a [`Bencode::Integer`], since the two are not equal.
+impl Ord for Bencode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Bencode::Integer(a), Bencode::Integer(b)) => {
+                let (mut a_buffer, mut b_buffer) = ([0; INTEGER_LEN], [0; INTEGER_LEN]);
+                let a = encode_integer(&mut a_buffer, *a < 0, a.unsigned_abs());
+                let b = encode_integer(&mut b_buffer, *b < 0, b.unsigned_abs());
+                a.cmp(b)
+            }
+            // Big integers are rare enough that formatting them is not worth avoiding.
+            #[cfg(feature = "num-bigint")]
+            (
+                Bencode::Integer(_) | Bencode::BigInteger(_),
+                Bencode::Integer(_) | Bencode::BigInteger(_),
+            ) => self
+                .canonicalize()
+                .cmp(&other.canonicalize())
+                .then_with(|| {
+                    let is_big = |value: &Bencode| matches!(value, Bencode::BigInteger(_));
+                    is_big(self).cmp(&is_big(other))
+                }),
+            (Bencode::String(a), Bencode::String(b)) => compare_strings(a, b),
+            (Bencode::List(a), Bencode::List(b)) => compare_sequences(
+                a.iter(),
+                b.iter(),
+                |a, b| a.cmp(b),
+                |element| element.first_byte(),
+            ),
+            (Bencode::Dictionary(a), Bencode::Dictionary(b)) => compare_sequences(
+                a.iter(),
+                b.iter(),
+                |(a_key, a_value), (b_key, b_value)| {
+                    compare_strings(a_key, b_key).then_with(|| a_value.cmp(b_value))
+                },
+                |_| b'0',
+            ),
+            _ => self.first_byte().cmp(&other.first_byte()),
+        }
+    }
+}
+
+impl PartialOrd for Bencode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Bencode {
+    /// The first byte of the encoding, or one that compares the same way.
+    fn first_byte(&self) -> u8 {
+        match self {
+            Bencode::Integer(_) => b'i',
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(_) => b'i',
+            // Any digit will do: strings of different types never compare by it.
+            Bencode::String(_) => b'0',
+            Bencode::List(_) => b'l',
+            Bencode::Dictionary(_) => b'd',
+        }
+    }
+}
+
+/// Compares `<length>:<bytes>` encodings.
+fn compare_strings(a: &[u8], b: &[u8]) -> Ordering {
+    if a.len() == b.len() {
+        return a.cmp(b);
+    }
+    let (mut a_buffer, mut b_buffer) = ([0; LENGTH_LEN], [0; LENGTH_LEN]);
+    let a_prefix = encode_length(&mut a_buffer, a.len() as u64);
+    let b_prefix = encode_length(&mut b_buffer, b.len() as u64);
+    a_prefix.cmp(b_prefix)
+}
+
+/// Compares the contents of two containers, each of which is followed by a closing `e`.
+fn compare_sequences<T>(
+    mut a: impl Iterator<Item = T>,
+    mut b: impl Iterator<Item = T>,
+    compare: impl Fn(&T, &T) -> Ordering,
+    first_byte: impl Fn(&T) -> u8,
+) -> Ordering {
+    loop {
+        match (a.next(), b.next()) {
+            (Some(a), Some(b)) => match compare(&a, &b) {
+                Ordering::Equal => continue,
+                unequal => return unequal,
+            },
+            (Some(a), None) => return first_byte(&a).cmp(&b'e'),
+            (None, Some(b)) => return b'e'.cmp(&first_byte(&b)),
+            (None, None) => return Ordering::Equal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_sort_as_their_encodings_do() {
+        let mut values = vec![
+            bencode!(9),
+            bencode!([]),
+            bencode!(-1),
+            bencode!({}),
+            bencode!(10),
+            bencode!("ab"),
+            bencode!(1),
+            bencode!("b"),
+            Bencode::String(vec![b'a'; 10]),
+            bencode!([1]),
+            bencode!({ "a": 1 }),
+        ];
+        values.sort();
+        let encodings: Vec<_> = values.iter().map(Bencode::canonicalize).collect();
+        let mut sorted = encodings.clone();
+        sorted.sort();
+        assert_eq!(encodings, sorted);
+    }
+
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn big_integers_equal_to_small_ones_still_differ() {
+        let small = Bencode::Integer(5);
+        let big = Bencode::BigInteger(5.into());
+        assert_ne!(small, big);
+        assert_eq!(small.cmp(&big), Ordering::Less);
+        assert_eq!(big.cmp(&small), Ordering::Greater);
+        assert_eq!(
+            Bencode::BigInteger(10.into()).cmp(&Bencode::Integer(9)),
+            Ordering::Less
+        );
+        let set: std::collections::BTreeSet<_> = [small, big].into();
+        assert_eq!(set.len(), 2);
+    }
+}
@@ -0,0 +1,397 @@
+//! Locations of values inside nested lists and dictionaries.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use crate::convert::type_name;
+use crate::value::Bencode;
+
+/// One step into a container: a dictionary key or a list index.
+///
+/// Paths are displayed with keys decoded as UTF-8, replacing any invalid bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Segment {
+    Key(Vec<u8>),
+    Index(usize),
+}
+
+/// The location of a value relative to the root of a document.
+///
+/// Displayed as e.g. `info.files[3].path`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Path(Vec<Segment>);
+
+impl Path {
+    /// The empty path, pointing at the root value.
+    pub fn root() -> Self {
+        Path::default()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.0
+    }
+
+    pub fn push(&mut self, segment: Segment) {
+        self.0.push(segment);
+    }
+
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.0.pop()
+    }
+
+    /// Returns a new path extended by `segment`.
+    pub fn join(&self, segment: Segment) -> Path {
+        let mut path = self.clone();
+        path.push(segment);
+        path
+    }
+
+    /// Splits off the last segment, returning the parent path along with it.
+    pub fn split_last(&self) -> Option<(&[Segment], &Segment)> {
+        self.0.split_last().map(|(last, parent)| (parent, last))
+    }
+}
+
+impl From<Vec<Segment>> for Path {
+    fn from(segments: Vec<Segment>) -> Self {
+        Path(segments)
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (position, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) => {
+                    if position > 0 {
+                        f.write_str(".")?;
+                    }
+                    f.write_str(&String::from_utf8_lossy(key))?;
+                }
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Bencode {
+    /// Looks up a value by a slash-separated pointer such as `/info/files/0/path`.
+    ///
+    /// Each part is a dictionary key or, inside a list, an element index. As in JSON
+    /// pointers, `~1` stands for `/` and `~0` for `~` within a key, and the empty string
+    /// points at the value itself.
+    pub fn pointer(&self, pointer: &str) -> Option<&Bencode> {
+        let mut value = self;
+        for token in pointer_tokens(pointer)? {
+            value = match value {
+                Bencode::Dictionary(dictionary) => dictionary.get(token.as_bytes())?,
+                Bencode::List(list) => list.get(parse_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Like [`pointer`](Bencode::pointer), but returns a mutable reference.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Bencode> {
+        let mut value = self;
+        for token in pointer_tokens(pointer)? {
+            value = match value {
+                Bencode::Dictionary(dictionary) => dictionary.get_mut(token.as_bytes())?,
+                Bencode::List(list) => list.get_mut(parse_index(&token)?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+}
+
+/// The reasons a typed getter such as [`Bencode::get_i64`] can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GetError {
+    Missing {
+        path: String,
+    },
+    UnexpectedType {
+        path: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    InvalidUtf8 {
+        path: String,
+    },
+    /// An integer too large for the type asked for.
+    OutOfRange {
+        path: String,
+    },
+}
+
+impl Display for GetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GetError::Missing { path } => write!(f, "no value at `{path}`"),
+            GetError::UnexpectedType {
+                path,
+                expected,
+                found,
+            } => write!(f, "expected {expected} at `{path}`, found {found}"),
+            GetError::InvalidUtf8 { path } => write!(f, "string at `{path}` is not valid UTF-8"),
+            GetError::OutOfRange { path } => write!(f, "integer at `{path}` is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for GetError {}
+
+impl Bencode {
+    /// Looks up a value by a dot-separated path such as `info.piece length`.
+    ///
+    /// Each part is a dictionary key or, inside a list, an element index, so
+    /// `info.files.0.length` is the length of the first file. The empty string is the
+    /// value itself.
+    pub fn get_path(&self, path: &str) -> Option<&Bencode> {
+        let mut value = self;
+        for token in path_tokens(path) {
+            value = match value {
+                Bencode::Dictionary(dictionary) => dictionary.get(token.as_bytes())?,
+                Bencode::List(list) => list.get(parse_index(token)?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Looks up an integer by a path as described in [`get_path`](Bencode::get_path).
+    ///
+    /// ```
+    /// use bencode::bencode;
+    ///
+    /// let torrent = bencode!({ "info": { "piece length": 262144 } });
+    /// assert_eq!(torrent.get_i64("info.piece length"), Ok(262144));
+    /// assert_eq!(
+    ///     torrent.get_i64("info").unwrap_err().to_string(),
+    ///     "expected integer at `info`, found dictionary",
+    /// );
+    /// ```
+    pub fn get_i64(&self, path: &str) -> Result<i64, GetError> {
+        i64::try_from(self.get_integer(path)?).map_err(|_| GetError::OutOfRange {
+            path: path.to_owned(),
+        })
+    }
+
+    /// Looks up an integer by path, at its full width; a big integer is out of range.
+    pub fn get_integer(&self, path: &str) -> Result<i128, GetError> {
+        match self.lookup(path)? {
+            Bencode::Integer(integer) => Ok(*integer),
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(_) => Err(GetError::OutOfRange {
+                path: path.to_owned(),
+            }),
+            other => Err(unexpected_at(path, "integer", other)),
+        }
+    }
+
+    /// Looks up a string by path and checks that it is valid UTF-8.
+    pub fn get_str(&self, path: &str) -> Result<&str, GetError> {
+        let bytes = self.get_bytes(path)?;
+        std::str::from_utf8(bytes).map_err(|_| GetError::InvalidUtf8 {
+            path: path.to_owned(),
+        })
+    }
+
+    /// Looks up a string by path as raw bytes.
+    pub fn get_bytes(&self, path: &str) -> Result<&[u8], GetError> {
+        match self.lookup(path)? {
+            Bencode::String(bytes) => Ok(bytes),
+            other => Err(unexpected_at(path, "string", other)),
+        }
+    }
+
+    /// Looks up a list by path.
+    pub fn get_list(&self, path: &str) -> Result<&[Bencode], GetError> {
+        match self.lookup(path)? {
+            Bencode::List(list) => Ok(list),
+            other => Err(unexpected_at(path, "list", other)),
+        }
+    }
+
+    /// Looks up a dictionary by path.
+    pub fn get_dictionary(&self, path: &str) -> Result<&BTreeMap<Vec<u8>, Bencode>, GetError> {
+        match self.lookup(path)? {
+            Bencode::Dictionary(dictionary) => Ok(dictionary),
+            other => Err(unexpected_at(path, "dictionary", other)),
+        }
+    }
+
+    fn lookup(&self, path: &str) -> Result<&Bencode, GetError> {
+        self.get_path(path).ok_or_else(|| GetError::Missing {
+            path: path.to_owned(),
+        })
+    }
+}
+
+fn path_tokens(path: &str) -> impl Iterator<Item = &str> {
+    (!path.is_empty())
+        .then(|| path.split('.'))
+        .into_iter()
+        .flatten()
+}
+
+fn unexpected_at(path: &str, expected: &'static str, found: &Bencode) -> GetError {
+    GetError::UnexpectedType {
+        path: path.to_owned(),
+        expected,
+        found: type_name(found),
+    }
+}
+
+fn pointer_tokens(pointer: &str) -> Option<impl Iterator<Item = String> + '_> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return None;
+    }
+    Some(
+        pointer
+            .split('/')
+            .skip(1)
+            .map(|token| token.replace("~1", "/").replace("~0", "~")),
+    )
+}
+
+/// Parses a list index, rejecting forms like `+1` or `01` that `usize::from_str` accepts.
+fn parse_index(token: &str) -> Option<usize> {
+    if token.starts_with('+') || (token.starts_with('0') && token.len() > 1) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrent() -> Bencode {
+        bencode!({
+            "announce": "http://tracker",
+            "info": {
+                "files": [
+                    { "length": 5, "path": ["a", "b.txt"] },
+                    { "length": 7, "path": [b"\xff".as_slice()] },
+                ],
+                "piece length": 262144,
+            },
+        })
+    }
+
+    #[test]
+    fn paths_go_through_keys_and_indices() {
+        let torrent = torrent();
+        assert_eq!(torrent.get_path(""), Some(&torrent));
+        assert_eq!(torrent.get_str("announce"), Ok("http://tracker"));
+        assert_eq!(torrent.get_i64("info.piece length"), Ok(262144));
+        assert_eq!(torrent.get_integer("info.files.1.length"), Ok(7));
+        assert_eq!(torrent.get_str("info.files.0.path.1"), Ok("b.txt"));
+        assert_eq!(torrent.get_bytes("info.files.1.path.0"), Ok(&b"\xff"[..]));
+        assert_eq!(torrent.get_list("info.files").map(<[_]>::len), Ok(2));
+        assert!(torrent.get_dictionary("info.files.0").is_ok());
+    }
+
+    #[test]
+    fn missing_values_name_their_path() {
+        let torrent = torrent();
+        for path in [
+            "comment",
+            "info.files.2",
+            "info.files.01",
+            "info.files.+1",
+            "announce.x",
+        ] {
+            assert_eq!(
+                torrent.get_integer(path),
+                Err(GetError::Missing {
+                    path: path.to_owned()
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn values_of_the_wrong_type_are_rejected() {
+        let torrent = torrent();
+        assert_eq!(
+            torrent.get_list("info"),
+            Err(GetError::UnexpectedType {
+                path: "info".to_owned(),
+                expected: "list",
+                found: "dictionary",
+            })
+        );
+        assert!(matches!(
+            torrent.get_dictionary("announce"),
+            Err(GetError::UnexpectedType {
+                found: "string",
+                ..
+            })
+        ));
+        assert_eq!(
+            torrent.get_str("info.files.1.path.0"),
+            Err(GetError::InvalidUtf8 {
+                path: "info.files.1.path.0".to_owned()
+            })
+        );
+        let value = bencode!({ "big": Bencode::Integer(i128::MAX) });
+        assert_eq!(
+            value.get_i64("big"),
+            Err(GetError::OutOfRange {
+                path: "big".to_owned()
+            })
+        );
+    }
+
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn big_integers_are_out_of_range() {
+        let value = bencode!({ "big": Bencode::BigInteger(num_bigint::BigInt::from(u128::MAX)) });
+        assert_eq!(
+            value.get_integer("big"),
+            Err(GetError::OutOfRange {
+                path: "big".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn pointers_unescape_their_keys() {
+        let value = bencode!({ "a/b": 1, "m~n": 2, "~1": 3, "": 4 });
+        assert_eq!(value.pointer("/a~1b"), Some(&bencode!(1)));
+        assert_eq!(value.pointer("/m~0n"), Some(&bencode!(2)));
+        // `~01` is an escaped `~` followed by `1`, not an escaped `/`.
+        assert_eq!(value.pointer("/~01"), Some(&bencode!(3)));
+        assert_eq!(value.pointer("/"), Some(&bencode!(4)));
+        assert_eq!(value.pointer("/a/b"), None);
+    }
+
+    #[test]
+    fn pointer_segments_are_keys_or_indices_by_container() {
+        let value = bencode!({ "0": ["x", "y"], "list": [{ "1": "z" }] });
+        assert_eq!(value.pointer("/0/1"), Some(&bencode!("y")));
+        assert_eq!(value.pointer("/list/0/1"), Some(&bencode!("z")));
+        assert_eq!(value.pointer("/0/2"), None);
+        assert_eq!(value.pointer("/0/01"), None);
+        assert_eq!(value.pointer("/0/-1"), None);
+        assert_eq!(value.pointer("/0/99999999999999999999999"), None);
+        assert_eq!(value.pointer("/0/0/0"), None);
+    }
+
+    #[test]
+    fn pointers_start_with_a_slash_or_are_empty() {
+        let mut value = bencode!({ "a": [1] });
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("a"), None);
+        assert_eq!(value.pointer_mut("a/0"), None);
+
+        *value.pointer_mut("/a/0").unwrap() = bencode!(2);
+        assert_eq!(value, bencode!({ "a": [2] }));
+        assert!(value.pointer_mut("/a/1").is_none());
+    }
+}
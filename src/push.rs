@@ -0,0 +1,404 @@
+//! Decoding input handed over in pieces, for code that cannot block waiting for more.
+
+use crate::decoder::{Decoder, DecoderOptions, DecoderResult};
+use crate::value::Bencode;
+
+/// What [`PushDecoder::feed`] and [`PushDecoder::poll`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress {
+    /// The input so far ends partway through a value.
+    NeedMoreData,
+    /// A complete value.
+    Value(Bencode),
+}
+
+/// Decodes values from input as it arrives, such as from a non-blocking socket.
+///
+/// Each value is decoded once all of it has been fed in, exactly as a [`Decoder`] would
+/// decode it, and errors report offsets counted from the start of everything fed in.
+/// The bytes of an incomplete value are only scanned as they arrive, never decoded
+/// again, and a string longer than [`DecoderOptions::max_string_length`], nesting deeper
+/// than [`DecoderOptions::max_depth`] or more elements than
+/// [`DecoderOptions::max_elements`] is rejected without waiting for the rest.
+///
+/// After an error, the decoder keeps returning it from every [`feed`](PushDecoder::feed)
+/// and [`poll`](PushDecoder::poll), since there is no telling where the next value would
+/// start.
+///
+/// ```
+/// use bencode::push::{Progress, PushDecoder};
+/// use bencode::bencode;
+///
+/// let mut decoder = PushDecoder::new();
+/// assert_eq!(decoder.feed(b"d1:y1:").unwrap(), Progress::NeedMoreData);
+/// assert_eq!(decoder.feed(b"qed1:y").unwrap(), Progress::Value(bencode!({ "y": "q" })));
+/// assert_eq!(decoder.poll().unwrap(), Progress::NeedMoreData);
+/// assert_eq!(decoder.feed(b"1:re").unwrap(), Progress::Value(bencode!({ "y": "r" })));
+/// ```
+#[derive(Debug, Default)]
+pub struct PushDecoder {
+    /// Input fed in but not yet decoded.
+    buffer: Vec<u8>,
+    framer: Framer,
+}
+
+impl PushDecoder {
+    pub fn new() -> Self {
+        PushDecoder::default()
+    }
+
+    /// Creates a push decoder with the given options, except that it never decodes
+    /// leniently: a value cut short is taken to be still arriving.
+    pub fn with_options(options: DecoderOptions) -> Self {
+        PushDecoder {
+            buffer: Vec::new(),
+            framer: Framer::new(options),
+        }
+    }
+
+    /// Adds `bytes` to the input and returns the next value, if all of it is here.
+    ///
+    /// A single piece of input may hold more than one value; call
+    /// [`poll`](PushDecoder::poll) to get the rest.
+    pub fn feed(&mut self, bytes: &[u8]) -> DecoderResult<Progress> {
+        // Nothing more will be decoded after an error, so there is no need to keep it.
+        if !self.framer.has_failed() {
+            self.buffer.extend_from_slice(bytes);
+        }
+        self.poll()
+    }
+
+    /// Returns the next value, if all of it has been fed in.
+    pub fn poll(&mut self) -> DecoderResult<Progress> {
+        match self.framer.next(&self.buffer)? {
+            Some((value, length)) => {
+                self.buffer.drain(..length);
+                Ok(Progress::Value(value))
+            }
+            None => Ok(Progress::NeedMoreData),
+        }
+    }
+
+    /// How many bytes have been fed in altogether.
+    #[cfg(feature = "futures")]
+    pub(crate) fn bytes_fed(&self) -> usize {
+        self.framer.consumed + self.buffer.len()
+    }
+
+    /// The error for input that ends here, partway through a value or before one.
+    #[cfg(feature = "futures")]
+    pub(crate) fn end_of_input(&self) -> crate::DecoderError {
+        self.framer.end_of_input(&self.buffer)
+    }
+
+    /// The input fed in that has not been returned as part of a value.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+/// Splits values off the front of a buffer that input is added to, for a
+/// [`PushDecoder`] or anything else that keeps its own buffer.
+#[derive(Debug, Default)]
+pub(crate) struct Framer {
+    options: DecoderOptions,
+    /// How many bytes of input came before the buffer.
+    consumed: usize,
+    scanner: Scanner,
+    /// How much of the buffer had been fed in when decoding it failed, if it has.
+    /// A [`DecoderError`](crate::DecoderError) cannot be cloned, so the same input is
+    /// decoded again to return the error once more.
+    failed_at: Option<usize>,
+}
+
+impl Framer {
+    pub(crate) fn new(options: DecoderOptions) -> Self {
+        Framer {
+            options: DecoderOptions {
+                lenient: false,
+                ..options
+            },
+            ..Framer::default()
+        }
+    }
+
+    /// Decodes the value at the start of `buffer`, if all of it is there, returning it
+    /// along with its length. The caller must then remove that many bytes from the
+    /// front of the buffer, and otherwise only add to its end.
+    pub(crate) fn next(&mut self, buffer: &[u8]) -> DecoderResult<Option<(Bencode, usize)>> {
+        let end = match self.failed_at {
+            Some(end) => end,
+            None => match self.scanner.scan(buffer, &self.options) {
+                Scan::Incomplete => return Ok(None),
+                Scan::Complete(end) => end,
+                // Leave it to the decoder to say what is wrong.
+                Scan::Malformed => buffer.len(),
+            },
+        };
+        let mut decoder = Decoder::with_options(&buffer[..end], self.options);
+        let value = decoder.decode().map_err(|error| {
+            self.failed_at = Some(end);
+            error.offset_by(self.consumed)
+        })?;
+        let length = decoder.bytes_consumed();
+        self.consumed += length;
+        self.scanner = Scanner::default();
+        Ok(Some((value, length)))
+    }
+
+    /// Whether decoding has failed, after which every call to [`next`](Framer::next)
+    /// returns the same error.
+    pub(crate) fn has_failed(&self) -> bool {
+        self.failed_at.is_some()
+    }
+
+    /// The error for input that ends with `buffer`, partway through a value or before
+    /// one.
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    pub(crate) fn end_of_input(&self, buffer: &[u8]) -> crate::DecoderError {
+        let error = Decoder::with_options(buffer, self.options)
+            .decode()
+            .expect_err("a whole value would have been returned by `next`");
+        error.offset_by(self.consumed)
+    }
+}
+
+/// What [`Scanner::scan`] found.
+enum Scan {
+    Incomplete,
+    /// A whole value, ending at the given offset into the input.
+    Complete(usize),
+    /// Input that fails to decode, for whatever reason the decoder will give.
+    Malformed,
+}
+
+/// Finds where a value ends, picking up where it left off as more input arrives.
+#[derive(Debug, Default)]
+struct Scanner {
+    /// How much of the input has been scanned.
+    position: usize,
+    /// The lists and dictionaries open at `position`, and what comes next in each.
+    open: Vec<Open>,
+    token: Token,
+    /// How many bytes of strings the value holds so far.
+    string_bytes: u64,
+    /// How many list elements and dictionary entries the value holds so far.
+    elements: usize,
+}
+
+#[derive(Debug)]
+enum Open {
+    List,
+    Key,
+    Value,
+}
+
+/// Where `position` is within the smallest piece of the value.
+#[derive(Debug, Default)]
+enum Token {
+    #[default]
+    Start,
+    Integer,
+    Length(u64),
+    Bytes(u64),
+}
+
+impl Scanner {
+    fn scan(&mut self, input: &[u8], options: &DecoderOptions) -> Scan {
+        loop {
+            if let Token::Bytes(remaining) = self.token {
+                let available = (input.len() - self.position) as u64;
+                if available < remaining {
+                    self.position = input.len();
+                    self.token = Token::Bytes(remaining - available);
+                    return Scan::Incomplete;
+                }
+                self.position += remaining as usize;
+                self.token = Token::Start;
+                if self.finish_value() {
+                    return Scan::Complete(self.position);
+                }
+                continue;
+            }
+            let Some(&byte) = input.get(self.position) else {
+                return Scan::Incomplete;
+            };
+            self.position += 1;
+            if matches!(self.token, Token::Start)
+                && byte != b'e'
+                && matches!(self.open.last(), Some(Open::List | Open::Key))
+            {
+                self.elements += 1;
+                if options
+                    .max_elements
+                    .is_some_and(|limit| self.elements > limit)
+                {
+                    return Scan::Malformed;
+                }
+            }
+            let finished = match self.token {
+                Token::Start => match byte {
+                    b'0'..=b'9' => {
+                        self.token = Token::Length(u64::from(byte - b'0'));
+                        false
+                    }
+                    b'e' if matches!(self.open.last(), Some(Open::List | Open::Key)) => {
+                        self.open.pop();
+                        true
+                    }
+                    _ if matches!(self.open.last(), Some(Open::Key)) => return Scan::Malformed,
+                    b'i' => {
+                        self.token = Token::Integer;
+                        false
+                    }
+                    b'l' | b'd' if self.open.len() < options.max_depth => {
+                        self.open
+                            .push(if byte == b'l' { Open::List } else { Open::Key });
+                        false
+                    }
+                    _ => return Scan::Malformed,
+                },
+                Token::Integer => match byte {
+                    b'0'..=b'9' | b'-' => false,
+                    b'e' => {
+                        self.token = Token::Start;
+                        true
+                    }
+                    _ => return Scan::Malformed,
+                },
+                Token::Length(length) => match byte {
+                    b'0'..=b'9' => {
+                        let digit = u64::from(byte - b'0');
+                        match length.checked_mul(10).and_then(|l| l.checked_add(digit)) {
+                            Some(length) => self.token = Token::Length(length),
+                            None => return Scan::Malformed,
+                        }
+                        false
+                    }
+                    b':' => {
+                        self.string_bytes = self.string_bytes.saturating_add(length);
+                        let too_long = length > i64::MAX as u64
+                            || options
+                                .max_string_length
+                                .is_some_and(|limit| length > limit)
+                            || options
+                                .max_total_bytes
+                                .is_some_and(|limit| self.string_bytes > limit);
+                        if too_long {
+                            return Scan::Malformed;
+                        }
+                        self.token = Token::Bytes(length);
+                        false
+                    }
+                    _ => return Scan::Malformed,
+                },
+                Token::Bytes(_) => unreachable!("string contents are skipped above"),
+            };
+            if finished && self.finish_value() {
+                return Scan::Complete(self.position);
+            }
+        }
+    }
+
+    /// Moves on from a value that has just ended, returning whether it was the whole one.
+    fn finish_value(&mut self) -> bool {
+        match self.open.last_mut() {
+            None => return true,
+            Some(Open::List) => {}
+            Some(top @ Open::Key) => *top = Open::Value,
+            Some(top @ Open::Value) => *top = Open::Key,
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::DecoderError;
+
+    #[test]
+    fn byte_by_byte_matches_decoder() {
+        let input = b"d4:infod6:lengthi5e4:name5:a.txtee0:li-1el0:eei7e";
+        let expected = Decoder::new(&input[..]).decode_all().unwrap();
+        let mut decoder = PushDecoder::new();
+        let mut values = Vec::new();
+        for &byte in input {
+            let mut progress = decoder.feed(&[byte]).unwrap();
+            while let Progress::Value(value) = progress {
+                values.push(value);
+                progress = decoder.poll().unwrap();
+            }
+        }
+        assert_eq!(values, expected);
+        assert!(decoder.buffered().is_empty());
+    }
+
+    #[test]
+    fn errors_count_offsets_from_the_start() {
+        let mut decoder = PushDecoder::new();
+        assert!(matches!(decoder.feed(b"i1ei2"), Ok(Progress::Value(_))));
+        assert!(matches!(
+            decoder.feed(b"xe"),
+            Err(DecoderError::UnexpectedByte {
+                byte: b'x',
+                offset: 5,
+                ..
+            })
+        ));
+
+        let options = DecoderOptions {
+            max_string_length: Some(10),
+            ..DecoderOptions::default()
+        };
+        let mut decoder = PushDecoder::with_options(options);
+        assert!(matches!(
+            decoder.feed(b"l1000000:"),
+            Err(DecoderError::StringTooLong { offset: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn errors_are_returned_again() {
+        let mut decoder = PushDecoder::new();
+        assert!(matches!(decoder.feed(b"i1e"), Ok(Progress::Value(_))));
+        for _ in 0..2 {
+            assert!(matches!(
+                decoder.feed(b"x"),
+                Err(DecoderError::UnexpectedByte { offset: 3, .. })
+            ));
+        }
+        assert!(matches!(
+            decoder.poll(),
+            Err(DecoderError::UnexpectedByte { offset: 3, .. })
+        ));
+        assert!(matches!(
+            decoder.feed(b"i1e"),
+            Err(DecoderError::UnexpectedByte { offset: 3, .. })
+        ));
+        assert_eq!(decoder.buffered(), b"x");
+    }
+
+    #[test]
+    fn too_many_elements_are_rejected_as_they_arrive() {
+        let options = DecoderOptions {
+            max_elements: Some(3),
+            ..DecoderOptions::default()
+        };
+        let mut decoder = PushDecoder::with_options(options);
+        assert_eq!(
+            decoder.feed(b"ld1:ai1ee0:").unwrap(),
+            Progress::NeedMoreData
+        );
+        assert!(matches!(
+            decoder.feed(b"i3e"),
+            Err(DecoderError::TooManyElements { limit: 3, .. })
+        ));
+
+        let mut decoder = PushDecoder::with_options(options);
+        assert!(matches!(
+            decoder.feed(b"li1ei2ei3ee"),
+            Ok(Progress::Value(_))
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+//! Pre-encoded bencode that is passed through untouched.
+
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::error::Error;
+use crate::tokenizer;
+
+/// The name the serializer and deserializer recognize a [`Raw`] by.
+pub(crate) const TOKEN: &str = "$bencode::private::Raw";
+
+/// A single bencode value kept exactly as it was encoded.
+///
+/// When deserialized from bencode, a `Raw` captures the bytes of the value in its place
+/// instead of parsing it, and when serialized to bencode it writes them back out verbatim.
+/// This keeps data such as a torrent's `info` dictionary byte-for-byte identical, and its
+/// infohash unchanged, while the rest of the document is rebuilt.
+///
+/// ```
+/// use bencode::raw::Raw;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct Torrent {
+///     announce: String,
+///     info: Raw,
+/// }
+///
+/// let mut torrent: Torrent = bencode::from_slice(b"d8:announce3:old4:infod1:xi1eee").unwrap();
+/// assert_eq!(torrent.info.as_bytes(), b"d1:xi1ee");
+///
+/// torrent.announce = "new".to_owned();
+/// assert_eq!(bencode::to_vec(&torrent).unwrap(), b"d8:announce3:new4:infod1:xi1eee");
+/// ```
+///
+/// Other serde formats see a `Raw` as a plain byte string, which must still hold exactly one
+/// well-formed bencode value to be deserialized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Raw(Vec<u8>);
+
+impl Raw {
+    /// Wraps `bytes` after checking that they hold exactly one well-formed value.
+    pub fn new(bytes: Vec<u8>) -> Result<Self, Error> {
+        tokenizer::validate(bytes.as_slice()).map_err(<Error as de::Error>::custom)?;
+        Ok(Raw(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Serialize for Raw {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(TOKEN, &Bytes(&self.0))
+    }
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Raw {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(TOKEN, RawVisitor)
+    }
+}
+
+struct RawVisitor;
+
+impl<'de> Visitor<'de> for RawVisitor {
+    type Value = Raw;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an encoded bencode value")
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Raw, D::Error> {
+        deserializer.deserialize_byte_buf(self)
+    }
+
+    // Bytes from other formats could be anything, so they are checked before being kept.
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Raw, E> {
+        tokenizer::validate(v).map_err(E::custom)?;
+        Ok(Raw(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Raw, E> {
+        tokenizer::validate(v.as_slice()).map_err(E::custom)?;
+        Ok(Raw(v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::value::{BytesDeserializer, Error as ValueError};
+
+    use super::*;
+
+    #[test]
+    fn bytes_from_other_formats_must_be_bencode() {
+        let raw = Raw::deserialize(BytesDeserializer::<ValueError>::new(b"li1ee"));
+        assert_eq!(raw.unwrap().as_bytes(), b"li1ee");
+
+        for bytes in [&b""[..], b"li1e", b"i1ei2e", b"hello"] {
+            assert!(Raw::deserialize(BytesDeserializer::<ValueError>::new(bytes)).is_err());
+        }
+    }
+
+    #[test]
+    fn new_accepts_exactly_one_value() {
+        assert_eq!(
+            Raw::new(b"d1:xi1ee".to_vec()).unwrap().as_bytes(),
+            b"d1:xi1ee"
+        );
+        for bytes in [&b""[..], b"li1e", b"i1ei2e", b"hello"] {
+            assert!(Raw::new(bytes.to_vec()).is_err());
+        }
+        assert!(Raw::new(vec![b'l'; 1_000_000]).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Input sources for the serde [`Deserializer`](crate::de::Deserializer).
+
+use std::io::{self, Read as _};
+
+use crate::error::{Error, Result};
+
+/// Bytes of a string payload, either borrowed from the input or copied into scratch space.
+pub enum Reference<'b, 'c> {
+    Borrowed(&'b [u8]),
+    Copied(&'c [u8]),
+}
+
+impl std::ops::Deref for Reference<'_, '_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Reference::Borrowed(bytes) => bytes,
+            Reference::Copied(bytes) => bytes,
+        }
+    }
+}
+
+/// A source of bencode input.
+///
+/// This trait is sealed; it is implemented by [`SliceRead`] and [`IoRead`] only.
+pub trait Read<'de>: private::Sealed {
+    #[doc(hidden)]
+    fn next(&mut self) -> Result<Option<u8>>;
+
+    #[doc(hidden)]
+    fn peek(&mut self) -> Result<Option<u8>>;
+
+    /// Reads exactly `length` bytes, borrowing them from the input when possible.
+    #[doc(hidden)]
+    fn read_bytes<'s>(
+        &'s mut self,
+        length: u64,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's>>;
+
+    /// Starts recording the bytes that are read, for capturing a value verbatim.
+    #[doc(hidden)]
+    fn begin_raw(&mut self);
+
+    /// Stops recording and returns everything read since [`begin_raw`](Read::begin_raw).
+    #[doc(hidden)]
+    fn end_raw(&mut self) -> Reference<'de, '_>;
+
+    /// How many bytes have been read, not counting one that has only been peeked at.
+    #[doc(hidden)]
+    fn offset(&self) -> usize;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Bencode input held entirely in memory; strings are borrowed from it.
+pub struct SliceRead<'a> {
+    slice: &'a [u8],
+    index: usize,
+    raw_start: usize,
+}
+
+impl<'a> SliceRead<'a> {
+    pub fn new(slice: &'a [u8]) -> Self {
+        SliceRead {
+            slice,
+            index: 0,
+            raw_start: 0,
+        }
+    }
+}
+
+impl private::Sealed for SliceRead<'_> {}
+
+impl<'a> Read<'a> for SliceRead<'a> {
+    fn next(&mut self) -> Result<Option<u8>> {
+        let byte = self.slice.get(self.index).copied();
+        if byte.is_some() {
+            self.index += 1;
+        }
+        Ok(byte)
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.slice.get(self.index).copied())
+    }
+
+    fn read_bytes<'s>(
+        &'s mut self,
+        length: u64,
+        _scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'a, 's>> {
+        let remaining = &self.slice[self.index..];
+        match usize::try_from(length) {
+            Ok(length) if length <= remaining.len() => {
+                self.index += length;
+                Ok(Reference::Borrowed(&remaining[..length]))
+            }
+            _ => {
+                self.index = self.slice.len();
+                Err(Error::Eof)
+            }
+        }
+    }
+
+    fn begin_raw(&mut self) {
+        self.raw_start = self.index;
+    }
+
+    fn end_raw(&mut self) -> Reference<'a, '_> {
+        Reference::Borrowed(&self.slice[self.raw_start..self.index])
+    }
+
+    fn offset(&self) -> usize {
+        self.index
+    }
+}
+
+/// Bencode input pulled from an [`io::Read`]; strings are copied out of it.
+///
+/// Everything but the contents of strings is read one byte at a time, so that nothing is
+/// taken from the reader beyond the end of the value. Each of those is a separate call to
+/// [`io::Read::read`], so a reader for which that is costly, such as a `File` or
+/// `TcpStream`, should be wrapped in an [`io::BufReader`] first.
+pub struct IoRead<R> {
+    reader: R,
+    peeked: Option<u8>,
+    recording: bool,
+    raw: Vec<u8>,
+    offset: usize,
+}
+
+impl<R: io::Read> IoRead<R> {
+    pub fn new(reader: R) -> Self {
+        IoRead {
+            reader,
+            peeked: None,
+            recording: false,
+            raw: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        let mut byte = 0;
+        loop {
+            return match self.reader.read(std::slice::from_mut(&mut byte)) {
+                Ok(0) => Ok(None),
+                Ok(_) => Ok(Some(byte)),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => Err(error.into()),
+            };
+        }
+    }
+}
+
+impl<R> private::Sealed for IoRead<R> {}
+
+impl<'de, R: io::Read> Read<'de> for IoRead<R> {
+    fn next(&mut self) -> Result<Option<u8>> {
+        let byte = match self.peeked.take() {
+            Some(byte) => Some(byte),
+            None => self.read_byte()?,
+        };
+        if byte.is_some() {
+            self.offset += 1;
+        }
+        if let (true, Some(byte)) = (self.recording, byte) {
+            self.raw.push(byte);
+        }
+        Ok(byte)
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        if self.peeked.is_none() {
+            self.peeked = self.read_byte()?;
+        }
+        Ok(self.peeked)
+    }
+
+    fn read_bytes<'s>(
+        &'s mut self,
+        length: u64,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's>> {
+        scratch.clear();
+        // `read_to_end` grows `scratch` as bytes arrive, so a huge declared length backed
+        // by a few bytes of input costs no more than those bytes.
+        if length > 0 {
+            if let Some(byte) = self.peeked.take() {
+                scratch.push(byte);
+            }
+        }
+        let result = (&mut self.reader)
+            .take(length - scratch.len() as u64)
+            .read_to_end(scratch);
+        self.offset += scratch.len();
+        result?;
+        if (scratch.len() as u64) < length {
+            return Err(Error::Eof);
+        }
+        if self.recording {
+            self.raw.extend_from_slice(scratch);
+        }
+        Ok(Reference::Copied(scratch))
+    }
+
+    fn begin_raw(&mut self) {
+        self.raw.clear();
+        self.recording = true;
+    }
+
+    fn end_raw(&mut self) -> Reference<'de, '_> {
+        self.recording = false;
+        Reference::Copied(&self.raw)
+    }
+
+    fn offset(&self) -> usize {
+        self.offset
+    }
+}
@@ -0,0 +1,1050 @@
+//! Serialize Rust data structures into bencode through serde.
+
+use std::io::Write;
+use std::mem;
+use std::ops::Range;
+
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::canonical;
+use crate::error::{Error, Result};
+use crate::raw;
+use crate::value::BIG_INTEGER_TOKEN;
+
+/// How Rust enums are laid out, since bencode has no enum type of its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EnumRepresentation {
+    /// Unit variants are the variant name as a string, e.g. `4:Left`; every other variant
+    /// is a dictionary keyed by its name, e.g. `d4:Movei3ee`.
+    #[default]
+    UnitAsString,
+    /// Every variant is a dictionary keyed by its name. Unit variants map to an empty
+    /// list, e.g. `d4:Leftlee`.
+    ExternallyTagged,
+    /// Every variant is a dictionary holding the name under `type` and the contents, if
+    /// any, under `value`, e.g. `d4:type4:Move5:valuei3ee`.
+    AdjacentlyTagged,
+}
+
+/// Options controlling how values are mapped to bencode.
+///
+/// The same options must be used to deserialize the data again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SerializerOptions {
+    pub enum_representation: EnumRepresentation,
+}
+
+/// A serde serializer writing bencode to an [`io::Write`](std::io::Write).
+///
+/// Dictionary entries are buffered and emitted in sorted key order, as required by the
+/// bencode specification, whatever order the map being serialized keeps them in. Two
+/// entries with the same key are an [`Error::DuplicateKey`].
+///
+/// Bencode has no null, so struct fields and map entries whose value is `None` or unit
+/// are left out, though their keys still count as duplicates. Anywhere else, such as in
+/// a sequence or as the whole value, they are an [`Error::UnsupportedType`].
+pub struct Serializer<W> {
+    writer: W,
+    options: SerializerOptions,
+    /// Set while serializing a [`Raw`](crate::raw::Raw), whose bytes are written as they are.
+    raw: bool,
+    /// Set while serializing the digits of an integer too large for serde's integer types.
+    big_integer: bool,
+    /// Set while serializing the value of a dictionary entry, until anything inside it
+    /// begins, so that a `None` or unit there leaves the entry out.
+    omit_unit: bool,
+    /// Set when a `None` or unit is left out, so the entry holding it can be dropped.
+    omitted: bool,
+    /// The entries of the dictionaries being serialized, which can only be written out
+    /// once the outermost one ends and each has been sorted.
+    buffer: Vec<u8>,
+    /// How many dictionaries are being serialized, one inside another.
+    open_dictionaries: usize,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Serializer {
+            writer,
+            options: SerializerOptions::default(),
+            raw: false,
+            big_integer: false,
+            omit_unit: false,
+            omitted: false,
+            buffer: Vec::new(),
+            open_dictionaries: 0,
+        }
+    }
+
+    pub fn with_options(mut self, options: SerializerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Where output goes: the buffer while inside a dictionary, otherwise the writer.
+    fn output(&mut self) -> &mut dyn Write {
+        if self.open_dictionaries > 0 {
+            &mut self.buffer
+        } else {
+            &mut self.writer
+        }
+    }
+
+    fn write_signed(&mut self, value: i128) -> Result<()> {
+        canonical::write_integer(self.output(), value < 0, value.unsigned_abs())?;
+        Ok(())
+    }
+
+    fn write_unsigned(&mut self, value: u128) -> Result<()> {
+        canonical::write_integer(self.output(), false, value)?;
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, value: &[u8]) -> Result<()> {
+        canonical::write_string(self.output(), value)?;
+        Ok(())
+    }
+
+    /// Opens the dictionary wrapping an enum variant, up to where its contents go.
+    fn begin_variant(&mut self, variant: &str) -> Result<()> {
+        self.omit_unit = false;
+        self.output().write_all(b"d")?;
+        match self.options.enum_representation {
+            EnumRepresentation::UnitAsString | EnumRepresentation::ExternallyTagged => {
+                self.write_bytes(variant.as_bytes())
+            }
+            EnumRepresentation::AdjacentlyTagged => {
+                self.write_bytes(b"type")?;
+                self.write_bytes(variant.as_bytes())?;
+                self.write_bytes(b"value")
+            }
+        }
+    }
+}
+
+/// Serialize `value` as bencode into `writer`.
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<()>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    value.serialize(&mut Serializer::new(writer))
+}
+
+/// Serialize `value` as a bencode byte vector.
+///
+/// A [`Bencode`](crate::Bencode) serializes to its canonical encoding, so this is also the
+/// one-line way to encode a value built by hand:
+///
+/// ```
+/// use bencode::bencode;
+///
+/// let bytes = bencode::to_vec(&bencode!({ "y": "q", "t": "aa" })).unwrap();
+/// assert_eq!(bytes, b"d1:t2:aa1:y1:qe");
+/// ```
+///
+/// This goes through serde, so it needs the `serde` feature, which is on by default.
+/// Without it, [`Bencode::canonicalize`](crate::Bencode::canonicalize) encodes a `Bencode`
+/// just as well.
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    let mut writer = Vec::new();
+    to_writer(&mut writer, value)?;
+    Ok(writer)
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = List<'a, W>;
+    type SerializeTuple = List<'a, W>;
+    type SerializeTupleStruct = List<'a, W>;
+    type SerializeTupleVariant = List<'a, W>;
+    type SerializeMap = Dictionary<'a, W>;
+    type SerializeStruct = Dictionary<'a, W>;
+    type SerializeStructVariant = Dictionary<'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.write_unsigned(v.into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.write_signed(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.write_signed(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.write_signed(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.write_signed(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.write_signed(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.write_unsigned(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.write_unsigned(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.write_unsigned(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.write_unsigned(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.write_unsigned(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<()> {
+        Err(Error::UnsupportedType("f32"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<()> {
+        Err(Error::UnsupportedType("f64"))
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.write_bytes(v.encode_utf8(&mut [0; 4]).as_bytes())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        if self.big_integer {
+            self.output().write_all(b"i")?;
+            self.output().write_all(v.as_bytes())?;
+            self.output().write_all(b"e")?;
+            return Ok(());
+        }
+        self.write_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        if self.raw {
+            if v.is_empty() {
+                return Err(ser::Error::custom("an empty Raw is not a bencode value"));
+            }
+            self.output().write_all(v)?;
+            return Ok(());
+        }
+        self.write_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        if self.omit_unit {
+            self.omitted = true;
+            return Ok(());
+        }
+        Err(Error::UnsupportedType("None"))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        // Bencode has no null, so `()` (and e.g. a JSON `null` being transcoded) is left
+        // out of dictionaries just like `None`.
+        if self.omit_unit {
+            self.omitted = true;
+            return Ok(());
+        }
+        Err(Error::UnsupportedType("()"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        match self.options.enum_representation {
+            EnumRepresentation::UnitAsString => self.write_bytes(variant.as_bytes()),
+            EnumRepresentation::ExternallyTagged => {
+                self.begin_variant(variant)?;
+                self.output().write_all(b"lee")?;
+                Ok(())
+            }
+            EnumRepresentation::AdjacentlyTagged => {
+                self.output().write_all(b"d")?;
+                self.write_bytes(b"type")?;
+                self.write_bytes(variant.as_bytes())?;
+                self.output().write_all(b"e")?;
+                Ok(())
+            }
+        }
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if name == raw::TOKEN {
+            self.raw = true;
+            let result = value.serialize(&mut *self);
+            self.raw = false;
+            return result;
+        }
+        if name == BIG_INTEGER_TOKEN {
+            self.big_integer = true;
+            let result = value.serialize(&mut *self);
+            self.big_integer = false;
+            return result;
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.begin_variant(variant)?;
+        value.serialize(&mut *self)?;
+        self.output().write_all(b"e")?;
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.omit_unit = false;
+        self.output().write_all(b"l")?;
+        Ok(List::new(self, b"e"))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.begin_variant(variant)?;
+        self.output().write_all(b"l")?;
+        Ok(List::new(self, b"ee"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.output().write_all(b"d")?;
+        Ok(Dictionary::new(self, b"e"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.begin_variant(variant)?;
+        self.output().write_all(b"d")?;
+        Ok(Dictionary::new(self, b"ee"))
+    }
+}
+
+/// Serializes sequences, tuples and tuple variants as bencode lists.
+pub struct List<'a, W> {
+    ser: &'a mut Serializer<W>,
+    end: &'static [u8],
+}
+
+impl<'a, W: Write> List<'a, W> {
+    fn new(ser: &'a mut Serializer<W>, end: &'static [u8]) -> Self {
+        List { ser, end }
+    }
+
+    fn element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn finish(self) -> Result<()> {
+        self.ser.output().write_all(self.end)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeSeq for List<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTuple for List<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleStruct for List<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleVariant for List<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+/// Serializes maps, structs and struct variants as bencode dictionaries.
+///
+/// Each entry is encoded, key first, onto the end of a buffer shared by every dictionary
+/// being serialized, and the dictionary records where. When it ends, after checking that
+/// no key appears twice, entries that arrived in key order stay where they are; otherwise
+/// they are copied into order once, so a value nested inside several dictionaries whose
+/// entries arrive out of order is copied once for each. Nothing reaches the writer until
+/// the outermost dictionary ends.
+pub struct Dictionary<'a, W> {
+    ser: &'a mut Serializer<W>,
+    end: &'static [u8],
+    /// Where this dictionary's entries begin in the buffer.
+    start: usize,
+    entries: Vec<(Vec<u8>, Range<usize>)>,
+    key: Option<Vec<u8>>,
+}
+
+impl<'a, W: Write> Dictionary<'a, W> {
+    fn new(ser: &'a mut Serializer<W>, end: &'static [u8]) -> Self {
+        ser.omit_unit = false;
+        ser.open_dictionaries += 1;
+        Dictionary {
+            start: ser.buffer.len(),
+            ser,
+            end,
+            entries: Vec::new(),
+            key: None,
+        }
+    }
+
+    fn entry<T>(&mut self, key: Vec<u8>, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let start = self.ser.buffer.len();
+        self.ser.write_bytes(&key)?;
+        self.ser.omit_unit = true;
+        let result = value.serialize(&mut *self.ser);
+        self.ser.omit_unit = false;
+        result?;
+        // An entry left out still takes up its key, so a duplicate of it is caught too.
+        if mem::take(&mut self.ser.omitted) {
+            self.ser.buffer.truncate(start);
+        }
+        self.entries.push((key, start..self.ser.buffer.len()));
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        let in_order = self.entries.windows(2).all(|pair| pair[0].0 <= pair[1].0);
+        if !in_order {
+            self.entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        if let Some(pair) = self.entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(Error::DuplicateKey(pair[0].0.clone()));
+        }
+        let buffer = &mut self.ser.buffer;
+        if !in_order {
+            let unsorted_end = buffer.len();
+            for (_, range) in &self.entries {
+                buffer.extend_from_within(range.clone());
+            }
+            buffer.drain(self.start..unsorted_end);
+        }
+        self.ser.open_dictionaries -= 1;
+        if self.ser.open_dictionaries == 0 {
+            self.ser.writer.write_all(&self.ser.buffer)?;
+            self.ser.buffer.clear();
+        }
+        self.ser.output().write_all(self.end)?;
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeMap for Dictionary<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .expect("serialize_value called before serialize_key");
+        self.entry(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeStruct for Dictionary<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.entry(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<W: Write> ser::SerializeStructVariant for Dictionary<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.entry(key.as_bytes().to_vec(), value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+/// Turns a map key into the raw bytes of a bencode string.
+struct KeySerializer;
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<Vec<u8>, Error>;
+    type SerializeTuple = Impossible<Vec<u8>, Error>;
+    type SerializeTupleStruct = Impossible<Vec<u8>, Error>;
+    type SerializeTupleVariant = Impossible<Vec<u8>, Error>;
+    type SerializeMap = Impossible<Vec<u8>, Error>;
+    type SerializeStruct = Impossible<Vec<u8>, Error>;
+    type SerializeStructVariant = Impossible<Vec<u8>, Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Vec<u8>> {
+        Ok(v.as_bytes().to_vec())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<u8>> {
+        Ok(v.to_vec())
+    }
+
+    fn serialize_none(self) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_unit(self) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Vec<u8>> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Vec<u8>> {
+        Ok(variant.as_bytes().to_vec())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(Error::KeyMustBeAString)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use super::*;
+
+    /// A map kept in insertion order, as an order-preserving map type would serialize.
+    struct Entries(Vec<(&'static str, i64)>);
+
+    impl Serialize for Entries {
+        fn serialize<S: ser::Serializer>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+        }
+    }
+
+    /// A byte string, which serializes through `serialize_bytes` rather than as a list.
+    struct Bytes(&'static [u8]);
+
+    impl Serialize for Bytes {
+        fn serialize<S: ser::Serializer>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    #[derive(Serialize)]
+    enum Shape {
+        Unit,
+        Newtype(u8),
+        Tuple(u8, &'static str),
+        Struct { y: u8, x: u8 },
+    }
+
+    fn to_vec_with(value: &impl Serialize, enum_representation: EnumRepresentation) -> Vec<u8> {
+        let options = SerializerOptions {
+            enum_representation,
+        };
+        let mut serializer = Serializer::new(Vec::new()).with_options(options);
+        value.serialize(&mut serializer).unwrap();
+        serializer.into_inner()
+    }
+
+    #[test]
+    fn integers_are_written_in_full() {
+        assert_eq!(to_vec(&0u8).unwrap(), b"i0e");
+        assert_eq!(to_vec(&-42i32).unwrap(), b"i-42e");
+        assert_eq!(to_vec(&true).unwrap(), b"i1e");
+        assert_eq!(to_vec(&i64::MIN).unwrap(), b"i-9223372036854775808e");
+        assert_eq!(to_vec(&u64::MAX).unwrap(), b"i18446744073709551615e");
+        assert_eq!(
+            to_vec(&u128::MAX).unwrap(),
+            b"i340282366920938463463374607431768211455e"
+        );
+        assert!(matches!(to_vec(&1.5), Err(Error::UnsupportedType("f64"))));
+    }
+
+    #[test]
+    fn strings_and_bytes_are_length_prefixed() {
+        assert_eq!(to_vec("spam").unwrap(), b"4:spam");
+        assert_eq!(to_vec("").unwrap(), b"0:");
+        assert_eq!(to_vec(&'\u{e9}').unwrap(), "2:\u{e9}".as_bytes());
+        assert_eq!(to_vec(&Bytes(b"\x00\xff")).unwrap(), b"2:\x00\xff");
+    }
+
+    #[test]
+    fn sequences_become_lists() {
+        assert_eq!(to_vec(&[1, 2]).unwrap(), b"li1ei2ee");
+        assert_eq!(to_vec(&Vec::<u8>::new()).unwrap(), b"le");
+        assert_eq!(to_vec(&(1, "a", [[3]])).unwrap(), b"li1e1:alli3eeee");
+    }
+
+    #[test]
+    fn maps_need_string_keys() {
+        let map = std::collections::HashMap::from([("b", 1), ("a", 2)]);
+        assert_eq!(to_vec(&map).unwrap(), b"d1:ai2e1:bi1ee");
+        let map = std::collections::BTreeMap::from([(1, 2)]);
+        assert!(matches!(to_vec(&map), Err(Error::KeyMustBeAString)));
+    }
+
+    #[test]
+    fn struct_fields_are_sorted_by_name() {
+        #[derive(Serialize)]
+        struct File {
+            name: &'static str,
+            length: u64,
+            path: Vec<&'static str>,
+        }
+
+        let file = File {
+            name: "a.txt",
+            length: 5,
+            path: vec!["dir", "a.txt"],
+        };
+        assert_eq!(
+            to_vec(&file).unwrap(),
+            b"d6:lengthi5e4:name5:a.txt4:pathl3:dir5:a.txtee"
+        );
+    }
+
+    #[test]
+    fn enums_in_each_representation() {
+        let shapes = [
+            Shape::Unit,
+            Shape::Newtype(1),
+            Shape::Tuple(1, "a"),
+            Shape::Struct { y: 2, x: 1 },
+        ];
+        let expected: [(_, [&[u8]; 4]); 3] = [
+            (
+                EnumRepresentation::UnitAsString,
+                [
+                    b"4:Unit",
+                    b"d7:Newtypei1ee",
+                    b"d5:Tupleli1e1:aee",
+                    b"d6:Structd1:xi1e1:yi2eee",
+                ],
+            ),
+            (
+                EnumRepresentation::ExternallyTagged,
+                [
+                    b"d4:Unitlee",
+                    b"d7:Newtypei1ee",
+                    b"d5:Tupleli1e1:aee",
+                    b"d6:Structd1:xi1e1:yi2eee",
+                ],
+            ),
+            (
+                EnumRepresentation::AdjacentlyTagged,
+                [
+                    b"d4:type4:Unite",
+                    b"d4:type7:Newtype5:valuei1ee",
+                    b"d4:type5:Tuple5:valueli1e1:aee",
+                    b"d4:type6:Struct5:valued1:xi1e1:yi2eee",
+                ],
+            ),
+        ];
+        for (representation, encodings) in expected {
+            for (shape, encoding) in shapes.iter().zip(encodings) {
+                assert_eq!(to_vec_with(shape, representation), encoding);
+            }
+        }
+    }
+
+    #[test]
+    fn map_keys_are_sorted_and_unique() {
+        let entries = Entries(vec![("b", 1), ("a", 2), ("c", 3)]);
+        assert_eq!(to_vec(&entries).unwrap(), b"d1:ai2e1:bi1e1:ci3ee");
+        let entries = Entries(vec![("b", 1), ("a", 2), ("b", 3)]);
+        assert!(matches!(to_vec(&entries), Err(Error::DuplicateKey(key)) if key == b"b"));
+    }
+
+    #[test]
+    fn keys_left_out_are_still_unique() {
+        let mut serializer = Serializer::new(Vec::new());
+        let entries = [("a", Some(1)), ("b", None), ("a", None)];
+        let result = ser::Serializer::collect_map(&mut serializer, entries);
+        assert!(matches!(result, Err(Error::DuplicateKey(key)) if key == b"a"));
+
+        let entries = [("a", None), ("a", Some(1))];
+        let mut serializer = Serializer::new(Vec::new());
+        let result = ser::Serializer::collect_map(&mut serializer, entries);
+        assert!(matches!(result, Err(Error::DuplicateKey(key)) if key == b"a"));
+    }
+
+    #[test]
+    fn nested_dictionaries_are_sorted_at_every_level() {
+        let inner = Entries(vec![("y", 1), ("x", 2)]);
+        let value = std::collections::BTreeMap::from([
+            ("b", vec![Entries(vec![("d", 3), ("c", 4)])]),
+            ("a", vec![inner]),
+        ]);
+        assert_eq!(
+            to_vec(&value).unwrap(),
+            b"d1:ald1:xi2e1:yi1eee1:bld1:ci4e1:di3eeee"
+        );
+
+        let outer = Entries(vec![("b", 1), ("a", 2)]);
+        let mut serializer = Serializer::new(Vec::new());
+        (&outer, [&outer]).serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), b"ld1:ai2e1:bi1eeld1:ai2e1:bi1eeee");
+    }
+
+    /// Serializes as a `Raw` holding no bytes at all, which `Raw` itself never does.
+    struct EmptyRaw;
+
+    impl Serialize for EmptyRaw {
+        fn serialize<S: ser::Serializer>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error> {
+            serializer.serialize_newtype_struct(raw::TOKEN, &Bytes(b""))
+        }
+    }
+
+    #[test]
+    fn empty_raw_values_are_rejected_rather_than_left_out() {
+        let map = std::collections::BTreeMap::from([("a", EmptyRaw)]);
+        assert!(matches!(to_vec(&map), Err(Error::Message(_))));
+        assert!(matches!(to_vec(&[EmptyRaw]), Err(Error::Message(_))));
+    }
+
+    #[derive(Serialize)]
+    enum Command {
+        Move(Option<u8>),
+        Stop(()),
+    }
+
+    #[derive(Serialize)]
+    struct Optional {
+        present: Option<u8>,
+        absent: Option<u8>,
+        unit: (),
+        nested: Vec<Option<u8>>,
+    }
+
+    #[test]
+    fn none_is_only_left_out_of_dictionaries() {
+        let value = Optional {
+            present: Some(1),
+            absent: None,
+            unit: (),
+            nested: Vec::new(),
+        };
+        assert_eq!(to_vec(&value).unwrap(), b"d6:nestedle7:presenti1ee");
+        let map: std::collections::BTreeMap<&str, Option<u8>> = [("a", None)].into();
+        assert_eq!(to_vec(&map).unwrap(), b"de");
+
+        let value = Optional {
+            nested: vec![Some(1), None],
+            ..value
+        };
+        assert!(matches!(
+            to_vec(&value),
+            Err(Error::UnsupportedType("None"))
+        ));
+        assert!(matches!(
+            to_vec(&vec![Some(1), None, Some(2)]),
+            Err(Error::UnsupportedType("None"))
+        ));
+        assert!(matches!(
+            to_vec(&None::<u8>),
+            Err(Error::UnsupportedType("None"))
+        ));
+        assert!(matches!(
+            to_vec(&Command::Move(None)),
+            Err(Error::UnsupportedType("None"))
+        ));
+        assert!(matches!(
+            to_vec(&Command::Stop(())),
+            Err(Error::UnsupportedType("()"))
+        ));
+        let map: std::collections::BTreeMap<&str, u8> = [("a", 1)].into();
+        assert!(matches!(
+            to_vec(&(map, None::<u8>)),
+            Err(Error::UnsupportedType("None"))
+        ));
+        assert_eq!(
+            to_vec(&Some(Command::Move(Some(3)))).unwrap(),
+            b"d4:Movei3ee"
+        );
+    }
+}
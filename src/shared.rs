@@ -0,0 +1,313 @@
+//! A [`Bencode`] counterpart whose strings are reference-counted [`Bytes`].
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bytes::Bytes;
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::de::Deserializer;
+use crate::error::Result;
+use crate::value::{serialize_integer, Bencode, ByteString};
+
+/// Any bencode value, with strings stored as [`Bytes`].
+///
+/// Decoding from a [`Bytes`] buffer with [`decode`](SharedBencode::decode) makes every
+/// string a slice of that buffer rather than a copy, and cloning a value only bumps
+/// reference counts. This suits large fields like a torrent's `pieces` that arrive in
+/// network buffers and are passed around without being modified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SharedBencode {
+    Integer(i128),
+    #[cfg(feature = "num-bigint")]
+    BigInteger(num_bigint::BigInt),
+    String(Bytes),
+    List(Vec<SharedBencode>),
+    Dictionary(BTreeMap<Vec<u8>, SharedBencode>),
+}
+
+impl SharedBencode {
+    /// Decodes a single value, sharing `input`'s storage for every string.
+    ///
+    /// ```
+    /// use bencode::shared::SharedBencode;
+    /// use bytes::Bytes;
+    ///
+    /// let input = Bytes::from_static(b"d6:pieces4:abcde");
+    /// let value = SharedBencode::decode(input.clone()).unwrap();
+    /// let pieces = value.get("pieces").and_then(SharedBencode::as_string).unwrap();
+    /// assert_eq!(pieces.as_ptr(), input[11..].as_ptr());
+    /// ```
+    pub fn decode(input: Bytes) -> Result<Self> {
+        let mut deserializer = Deserializer::from_slice(&input);
+        let value = Seed(&input).deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(value)
+    }
+
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            SharedBencode::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    pub fn as_string(&self) -> Option<&Bytes> {
+        match self {
+            SharedBencode::String(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[SharedBencode]> {
+        match self {
+            SharedBencode::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&BTreeMap<Vec<u8>, SharedBencode>> {
+        match self {
+            SharedBencode::Dictionary(dictionary) => Some(dictionary),
+            _ => None,
+        }
+    }
+
+    /// Looks up a dictionary entry, returning `None` if this is not a dictionary.
+    pub fn get(&self, key: &str) -> Option<&SharedBencode> {
+        self.as_dictionary()?.get(key.as_bytes())
+    }
+}
+
+/// Moves the strings into [`Bytes`] without copying them.
+impl From<Bencode> for SharedBencode {
+    fn from(value: Bencode) -> Self {
+        match value {
+            Bencode::Integer(integer) => SharedBencode::Integer(integer),
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(integer) => SharedBencode::BigInteger(integer),
+            Bencode::String(bytes) => SharedBencode::String(Bytes::from(bytes)),
+            Bencode::List(list) => SharedBencode::List(list.into_iter().map(Into::into).collect()),
+            Bencode::Dictionary(dictionary) => SharedBencode::Dictionary(
+                dictionary
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Copies the strings out of any buffers they share.
+impl From<SharedBencode> for Bencode {
+    fn from(value: SharedBencode) -> Self {
+        match value {
+            SharedBencode::Integer(integer) => Bencode::Integer(integer),
+            #[cfg(feature = "num-bigint")]
+            SharedBencode::BigInteger(integer) => Bencode::BigInteger(integer),
+            SharedBencode::String(bytes) => Bencode::String(bytes.into()),
+            SharedBencode::List(list) => Bencode::List(list.into_iter().map(Into::into).collect()),
+            SharedBencode::Dictionary(dictionary) => Bencode::Dictionary(
+                dictionary
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for SharedBencode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            SharedBencode::Integer(integer) => serialize_integer(*integer, serializer),
+            #[cfg(feature = "num-bigint")]
+            SharedBencode::BigInteger(integer) => {
+                crate::value::serialize_big_integer(integer, serializer)
+            }
+            SharedBencode::String(bytes) => ByteString(bytes).serialize(serializer),
+            SharedBencode::List(list) => serializer.collect_seq(list),
+            SharedBencode::Dictionary(dictionary) => serializer.collect_map(
+                dictionary
+                    .iter()
+                    .map(|(key, value)| (ByteString(key), value)),
+            ),
+        }
+    }
+}
+
+/// Builds a [`SharedBencode`] whose borrowed strings are sliced out of the input buffer.
+#[derive(Clone, Copy)]
+struct Seed<'a>(&'a Bytes);
+
+impl Seed<'_> {
+    fn borrowed(self, bytes: &[u8]) -> SharedBencode {
+        SharedBencode::String(self.0.slice_ref(bytes))
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for Seed<'_> {
+    type Value = SharedBencode;
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<SharedBencode, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Seed<'_> {
+    type Value = SharedBencode;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an integer, string, list or dictionary")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<SharedBencode, E> {
+        Ok(SharedBencode::Integer(v.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<SharedBencode, E> {
+        Ok(SharedBencode::Integer(v.into()))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> std::result::Result<SharedBencode, E> {
+        Ok(SharedBencode::Integer(v))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> std::result::Result<SharedBencode, E> {
+        i128::try_from(v)
+            .map(SharedBencode::Integer)
+            .map_err(|_| E::custom(format_args!("integer {v} is out of range")))
+    }
+
+    fn visit_borrowed_str<E: de::Error>(
+        self,
+        v: &'de str,
+    ) -> std::result::Result<SharedBencode, E> {
+        Ok(self.borrowed(v.as_bytes()))
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(
+        self,
+        v: &'de [u8],
+    ) -> std::result::Result<SharedBencode, E> {
+        Ok(self.borrowed(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<SharedBencode, E> {
+        Ok(SharedBencode::String(Bytes::copy_from_slice(v.as_bytes())))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<SharedBencode, E> {
+        Ok(SharedBencode::String(Bytes::copy_from_slice(v)))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<SharedBencode, A::Error> {
+        let mut list = Vec::new();
+        while let Some(element) = seq.next_element_seed(self)? {
+            list.push(element);
+        }
+        Ok(SharedBencode::List(list))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(
+        self,
+        mut map: A,
+    ) -> std::result::Result<SharedBencode, A::Error> {
+        let mut dictionary = BTreeMap::new();
+        while let Some(key) = map.next_key_seed(self)? {
+            let SharedBencode::String(key) = key else {
+                return Err(de::Error::invalid_type(
+                    de::Unexpected::Other("non-string key"),
+                    &"a string",
+                ));
+            };
+            let value = map.next_value_seed(self)?;
+            dictionary.insert(key.to_vec(), value);
+        }
+        Ok(SharedBencode::Dictionary(dictionary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    /// Whether `slice` lies within `input`'s storage.
+    fn is_within(slice: &Bytes, input: &Bytes) -> bool {
+        input.as_ptr_range().contains(&slice.as_ptr())
+    }
+
+    #[test]
+    fn nested_strings_are_slices_of_the_input() {
+        let input = Bytes::from_static(b"d4:infod5:filesl4:abcde4:name1:xee");
+        let value = SharedBencode::decode(input.clone()).unwrap();
+        let info = value.get("info").unwrap();
+        let file = &info.get("files").and_then(SharedBencode::as_list).unwrap()[0];
+        let name = info.get("name").and_then(SharedBencode::as_string).unwrap();
+        assert_eq!(file.as_string().unwrap(), "abcd");
+        assert!(is_within(file.as_string().unwrap(), &input));
+        assert_eq!(name, "x");
+        assert!(is_within(name, &input));
+
+        let keys: Vec<&[u8]> = info
+            .as_dictionary()
+            .unwrap()
+            .keys()
+            .map(Vec::as_slice)
+            .collect();
+        assert_eq!(keys, [b"files".as_slice(), b"name"]);
+    }
+
+    #[test]
+    fn conversions_keep_every_value() {
+        let value = bencode!({ "a": [1, -2, "x"], "b": { "c": b"\xff".as_slice() } });
+        let shared = SharedBencode::from(value.clone());
+        assert_eq!(
+            shared.get("a").and_then(SharedBencode::as_list).unwrap()[1].as_integer(),
+            Some(-2)
+        );
+        assert_eq!(Bencode::from(shared.clone()), value);
+        let input = Bytes::from(value.canonicalize());
+        assert_eq!(SharedBencode::decode(input).unwrap(), shared);
+    }
+
+    #[test]
+    fn serializing_writes_the_canonical_encoding() {
+        let input = Bytes::from_static(b"d1:bli1e2:xye1:a0:e");
+        let value = SharedBencode::decode(input).unwrap();
+        assert_eq!(crate::to_vec(&value).unwrap(), b"d1:a0:1:bli1e2:xyee");
+    }
+
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn big_integers_are_serialized_in_full() {
+        let big = num_bigint::BigInt::from(u128::MAX) * 10u8;
+        let value = SharedBencode::from(Bencode::List(vec![Bencode::BigInteger(big.clone())]));
+        assert_eq!(
+            crate::to_vec(&value).unwrap(),
+            format!("li{big}ee").into_bytes()
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let input = Bytes::from("l".repeat(1_000_000));
+        assert!(matches!(
+            SharedBencode::decode(input),
+            Err(Error::DepthLimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        assert!(SharedBencode::decode(Bytes::from_static(b"i1ei2e")).is_err());
+    }
+}
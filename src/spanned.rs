@@ -0,0 +1,98 @@
+//! [`Bencode`] values annotated with where in the input they were decoded from.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::decoder::Node;
+use crate::value::Bencode;
+
+/// A value returned by [`Decoder::decode_spanned`](crate::Decoder::decode_spanned),
+/// together with the range of input bytes it was decoded from.
+///
+/// The span of a list or dictionary runs from its opening `l` or `d` up to and including
+/// the closing `e`; that of a dictionary entry covers only its value, not its key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Spanned {
+    pub value: SpannedValue,
+    pub span: Range<usize>,
+}
+
+/// The contents of a [`Spanned`] value, whose elements carry their own spans.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SpannedValue {
+    Integer(i128),
+    #[cfg(feature = "num-bigint")]
+    BigInteger(num_bigint::BigInt),
+    String(Vec<u8>),
+    List(Vec<Spanned>),
+    Dictionary(BTreeMap<Vec<u8>, Spanned>),
+}
+
+impl Spanned {
+    pub fn as_list(&self) -> Option<&[Spanned]> {
+        match &self.value {
+            SpannedValue::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&BTreeMap<Vec<u8>, Spanned>> {
+        match &self.value {
+            SpannedValue::Dictionary(dictionary) => Some(dictionary),
+            _ => None,
+        }
+    }
+
+    /// Looks up a dictionary entry, returning `None` if this is not a dictionary.
+    pub fn get(&self, key: &str) -> Option<&Spanned> {
+        self.as_dictionary()?.get(key.as_bytes())
+    }
+}
+
+/// Drops the spans.
+impl From<Spanned> for Bencode {
+    fn from(spanned: Spanned) -> Self {
+        match spanned.value {
+            SpannedValue::Integer(integer) => Bencode::Integer(integer),
+            #[cfg(feature = "num-bigint")]
+            SpannedValue::BigInteger(integer) => Bencode::BigInteger(integer),
+            SpannedValue::String(bytes) => Bencode::String(bytes),
+            SpannedValue::List(list) => Bencode::List(list.into_iter().map(Into::into).collect()),
+            SpannedValue::Dictionary(dictionary) => Bencode::Dictionary(
+                dictionary
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Node for Spanned {
+    fn scalar(value: Bencode, span: Range<usize>) -> Self {
+        let value = match value {
+            Bencode::Integer(integer) => SpannedValue::Integer(integer),
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(integer) => SpannedValue::BigInteger(integer),
+            Bencode::String(bytes) => SpannedValue::String(bytes),
+            Bencode::List(_) | Bencode::Dictionary(_) => {
+                unreachable!("containers are not decoded as scalars")
+            }
+        };
+        Spanned { value, span }
+    }
+
+    fn list(list: Vec<Self>, span: Range<usize>) -> Self {
+        Spanned {
+            value: SpannedValue::List(list),
+            span,
+        }
+    }
+
+    fn dictionary(dictionary: BTreeMap<Vec<u8>, Self>, span: Range<usize>) -> Self {
+        Spanned {
+            value: SpannedValue::Dictionary(dictionary),
+            span,
+        }
+    }
+}
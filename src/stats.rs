@@ -0,0 +1,184 @@
+//! Size and shape metrics for [`Bencode`] values.
+
+use std::collections::BTreeMap;
+use std::mem::size_of;
+
+use crate::canonical;
+use crate::value::Bencode;
+use crate::visit::BencodeVisitor;
+
+/// Metrics describing a [`Bencode`] value, returned by [`Bencode::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// How deeply lists and dictionaries are nested: 0 for an integer or string, 1 for a
+    /// list of integers, and so on.
+    pub max_depth: usize,
+    pub integers: usize,
+    pub strings: usize,
+    pub lists: usize,
+    pub dictionaries: usize,
+    /// The total length of all strings, not counting dictionary keys.
+    pub string_bytes: usize,
+    /// The number of bytes the value takes up when encoded.
+    pub encoded_len: usize,
+}
+
+impl Stats {
+    /// The total number of values, counting containers as well as their contents.
+    pub fn nodes(&self) -> usize {
+        self.integers + self.strings + self.lists + self.dictionaries
+    }
+}
+
+impl Bencode {
+    /// Measures this value and everything nested inside it.
+    ///
+    /// ```
+    /// use bencode::bencode;
+    ///
+    /// let stats = bencode!({ "files": [{ "length": 5 }] }).stats();
+    /// assert_eq!(stats.max_depth, 3);
+    /// assert_eq!(stats.nodes(), 4);
+    /// assert_eq!(stats.encoded_len, "d5:filesld6:lengthi5eeee".len());
+    /// ```
+    pub fn stats(&self) -> Stats {
+        let mut collector = Collector::default();
+        self.accept(&mut collector);
+        collector.stats
+    }
+
+    /// Estimates the heap memory owned by this value, in bytes.
+    ///
+    /// This counts the capacity of every string and list and the entries of every
+    /// dictionary, but not the `Bencode` itself, which may live on the stack or inside
+    /// another value. Dictionary nodes are assumed to be full, so the estimate errs on the
+    /// high side for small dictionaries.
+    pub fn deep_size_of(&self) -> usize {
+        match self {
+            Bencode::Integer(_) => 0,
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(integer) => integer.bits().div_ceil(64) as usize * 8,
+            Bencode::String(bytes) => bytes.capacity(),
+            Bencode::List(list) => {
+                list.capacity() * size_of::<Bencode>()
+                    + list.iter().map(Bencode::deep_size_of).sum::<usize>()
+            }
+            Bencode::Dictionary(dictionary) => {
+                let slots = dictionary.len().div_ceil(BTREE_NODE_CAPACITY) * BTREE_NODE_CAPACITY;
+                slots * (size_of::<Vec<u8>>() + size_of::<Bencode>())
+                    + dictionary
+                        .iter()
+                        .map(|(key, value)| key.capacity() + value.deep_size_of())
+                        .sum::<usize>()
+            }
+        }
+    }
+}
+
+/// How many entries fit in one node of the standard library's `BTreeMap`.
+const BTREE_NODE_CAPACITY: usize = 11;
+
+#[derive(Default)]
+struct Collector {
+    stats: Stats,
+    depth: usize,
+}
+
+impl Collector {
+    fn enter(&mut self) {
+        self.depth += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+        // The opening `l` or `d` and the closing `e`.
+        self.stats.encoded_len += 2;
+    }
+}
+
+impl BencodeVisitor for Collector {
+    fn visit_integer(&mut self, integer: i128) {
+        self.stats.integers += 1;
+        self.stats.encoded_len += canonical::integer_len(integer);
+    }
+
+    #[cfg(feature = "num-bigint")]
+    fn visit_big_integer(&mut self, integer: &num_bigint::BigInt) {
+        self.stats.integers += 1;
+        self.stats.encoded_len += canonical::big_integer_len(integer);
+    }
+
+    fn visit_string(&mut self, bytes: &[u8]) {
+        self.stats.strings += 1;
+        self.stats.string_bytes += bytes.len();
+        self.stats.encoded_len += canonical::string_len(bytes.len());
+    }
+
+    fn enter_list(&mut self, _list: &[Bencode]) {
+        self.stats.lists += 1;
+        self.enter();
+    }
+
+    fn leave_list(&mut self, _list: &[Bencode]) {
+        self.depth -= 1;
+    }
+
+    fn enter_dictionary(&mut self, _dictionary: &BTreeMap<Vec<u8>, Bencode>) {
+        self.stats.dictionaries += 1;
+        self.enter();
+    }
+
+    fn leave_dictionary(&mut self, _dictionary: &BTreeMap<Vec<u8>, Bencode>) {
+        self.depth -= 1;
+    }
+
+    fn visit_key(&mut self, key: &[u8]) {
+        self.stats.encoded_len += canonical::string_len(key.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_len_matches_the_encoding() {
+        for value in [
+            bencode!(0),
+            bencode!(-10),
+            bencode!(""),
+            bencode!([]),
+            bencode!({}),
+            Bencode::Integer(i128::MIN),
+            Bencode::string(vec![b'x'; 1000]),
+            bencode!({ "a": [1, -99, "bc"], "dd": { "e": [[]] } }),
+        ] {
+            assert_eq!(value.stats().encoded_len, value.canonicalize().len());
+        }
+    }
+
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn big_integers_are_measured_like_others() {
+        let integer = -num_bigint::BigInt::from(u128::MAX) * 1000;
+        let value = bencode!([Bencode::BigInteger(integer)]);
+        assert_eq!(value.stats().encoded_len, value.canonicalize().len());
+        assert_eq!(value.stats().integers, 1);
+    }
+
+    #[test]
+    fn nested_values_are_counted_by_kind() {
+        let value = bencode!({ "a": [1, -99, "bc"], "dd": { "e": [[], "f"] } });
+        assert_eq!(
+            value.stats(),
+            Stats {
+                max_depth: 4,
+                integers: 2,
+                strings: 2,
+                lists: 3,
+                dictionaries: 2,
+                string_bytes: 3,
+                encoded_len: value.canonicalize().len(),
+            }
+        );
+        assert_eq!(value.stats().nodes(), 9);
+        assert_eq!(bencode!("abc").stats().max_depth, 0);
+    }
+}
@@ -0,0 +1,479 @@
+//! A text format for bencode values, for fixtures and golden files that people write and
+//! review by hand.
+//!
+//! Unlike the [`Display`] form, the text form holds every byte of a
+//! value, so it can be parsed back into exactly the value it was printed from:
+//!
+//! - Integers are written in decimal.
+//! - Strings are quoted. Text is written as it is, with `\"`, `\\`, `\n`, `\r` and `\t`
+//!   escaped. Other control characters are written as `\u{..}`, and bytes that are not
+//!   part of valid UTF-8 as `\x..`, so binary strings such as `pieces` are written in hex.
+//! - Lists are written as `[...]` and dictionaries as `{"key": value, ...}`, with one
+//!   element or entry to a line, indented by two spaces for each level.
+//!
+//! The parser also accepts the whole value on one line, a comma after the last element
+//! or entry, dictionary keys in any order, and comments from `#` to the end of a line.
+//!
+//! ```
+//! use bencode::bencode;
+//! use bencode::text::{from_text, to_text};
+//!
+//! let value = bencode!({ "info": { "length": 5, "pieces": b"\x00\xff".as_slice() } });
+//! let text = to_text(&value);
+//! assert_eq!(
+//!     text,
+//!     "{\n  \"info\": {\n    \"length\": 5,\n    \"pieces\": \"\\x00\\xff\"\n  }\n}"
+//! );
+//! assert_eq!(from_text(&text).unwrap(), value);
+//! ```
+
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::fmt::{self, Display, Write};
+use std::mem;
+
+use crate::value::Bencode;
+
+/// Prints `value` in the text form.
+pub fn to_text(value: &Bencode) -> String {
+    let mut text = String::new();
+    write_value(&mut text, value, 0);
+    text
+}
+
+fn write_value(text: &mut String, value: &Bencode, depth: usize) {
+    match value {
+        Bencode::Integer(integer) => write!(text, "{integer}").expect("writing to a String"),
+        #[cfg(feature = "num-bigint")]
+        Bencode::BigInteger(integer) => write!(text, "{integer}").expect("writing to a String"),
+        Bencode::String(bytes) => write_string(text, bytes),
+        Bencode::List(list) if list.is_empty() => text.push_str("[]"),
+        Bencode::List(list) => {
+            text.push('[');
+            for (position, element) in list.iter().enumerate() {
+                begin_line(text, position, depth + 1);
+                write_value(text, element, depth + 1);
+            }
+            begin_line(text, 0, depth);
+            text.push(']');
+        }
+        Bencode::Dictionary(dictionary) if dictionary.is_empty() => text.push_str("{}"),
+        Bencode::Dictionary(dictionary) => {
+            text.push('{');
+            for (position, (key, value)) in dictionary.iter().enumerate() {
+                begin_line(text, position, depth + 1);
+                write_string(text, key);
+                text.push_str(": ");
+                write_value(text, value, depth + 1);
+            }
+            begin_line(text, 0, depth);
+            text.push('}');
+        }
+    }
+}
+
+/// Starts the line for the element at `position`, after a comma unless it is the first.
+fn begin_line(text: &mut String, position: usize, depth: usize) {
+    if position > 0 {
+        text.push(',');
+    }
+    text.push('\n');
+    for _ in 0..depth {
+        text.push_str("  ");
+    }
+}
+
+fn write_string(text: &mut String, bytes: &[u8]) {
+    text.push('"');
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' => text.push_str("\\\""),
+                '\\' => text.push_str("\\\\"),
+                '\n' => text.push_str("\\n"),
+                '\r' => text.push_str("\\r"),
+                '\t' => text.push_str("\\t"),
+                c if c.is_ascii_control() => {
+                    write!(text, "\\x{:02x}", c as u32).expect("writing to a String")
+                }
+                c if c.is_control() => {
+                    write!(text, "\\u{{{:x}}}", c as u32).expect("writing to a String")
+                }
+                c => text.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            write!(text, "\\x{byte:02x}").expect("writing to a String");
+        }
+    }
+    text.push('"');
+}
+
+/// Errors produced by [`from_text`], with the line and column where they were found, both
+/// counted from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextError {
+    Eof,
+    UnexpectedChar {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    /// A `\` in a string not followed by one of the escapes the text form uses.
+    InvalidEscape {
+        line: usize,
+        column: usize,
+    },
+    /// An integer too large to hold, without the `num-bigint` feature.
+    IntegerOutOfRange {
+        line: usize,
+        column: usize,
+    },
+    DuplicateKey {
+        line: usize,
+        column: usize,
+    },
+    TrailingCharacters {
+        line: usize,
+        column: usize,
+    },
+}
+
+impl Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextError::Eof => f.write_str("unexpected end of input"),
+            TextError::UnexpectedChar {
+                line,
+                column,
+                found,
+            } => write!(f, "unexpected {found:?} at line {line}, column {column}"),
+            TextError::InvalidEscape { line, column } => {
+                write!(f, "invalid escape at line {line}, column {column}")
+            }
+            TextError::IntegerOutOfRange { line, column } => {
+                write!(f, "integer at line {line}, column {column} is out of range")
+            }
+            TextError::DuplicateKey { line, column } => {
+                write!(f, "duplicate key at line {line}, column {column}")
+            }
+            TextError::TrailingCharacters { line, column } => {
+                write!(f, "trailing characters at line {line}, column {column}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// Parses a value written in the text form.
+pub fn from_text(text: &str) -> Result<Bencode, TextError> {
+    let mut parser = TextParser { text, index: 0 };
+    let value = parser.parse_value()?;
+    if parser.peek().is_ok() {
+        let (line, column) = parser.location(parser.index);
+        return Err(TextError::TrailingCharacters { line, column });
+    }
+    Ok(value)
+}
+
+struct TextParser<'a> {
+    text: &'a str,
+    /// The offset in bytes of the next character.
+    index: usize,
+}
+
+/// A list or dictionary that [`TextParser::parse_value`] is partway through.
+enum Container {
+    List(Vec<Bencode>),
+    Dictionary {
+        entries: BTreeMap<Vec<u8>, Bencode>,
+        /// The key of the entry whose value is being parsed.
+        key: Vec<u8>,
+        /// Where that key begins, to point at if it turns out to be a duplicate.
+        key_start: usize,
+    },
+}
+
+impl TextParser<'_> {
+    /// Parses one value, keeping the lists and dictionaries it is inside of on a stack of
+    /// their own rather than recursing.
+    fn parse_value(&mut self) -> Result<Bencode, TextError> {
+        let mut open: Vec<Container> = Vec::new();
+        loop {
+            let mut finished = match self.peek()? {
+                '"' => Some(Bencode::String(self.parse_string()?)),
+                '-' | '0'..='9' => Some(self.parse_integer()?),
+                c @ ('[' | '{') => {
+                    self.index += 1;
+                    open.push(if c == '[' {
+                        Container::List(Vec::new())
+                    } else {
+                        Container::Dictionary {
+                            entries: BTreeMap::new(),
+                            key: Vec::new(),
+                            key_start: 0,
+                        }
+                    });
+                    None
+                }
+                _ => return Err(self.unexpected()),
+            };
+            // Hand the finished value to the container it is in, then either close that
+            // container, which finishes it in turn, or move on to its next element.
+            loop {
+                let Some(container) = open.last_mut() else {
+                    return Ok(finished.expect("a value is finished once nothing is open"));
+                };
+                if let Some(value) = finished.take() {
+                    self.add(container, value)?;
+                }
+                let (end, first) = match container {
+                    Container::List(list) => (']', list.is_empty()),
+                    Container::Dictionary { entries, .. } => ('}', entries.is_empty()),
+                };
+                if !self.end_of_container(end, first)? {
+                    self.begin_element(container)?;
+                    break;
+                }
+                finished = Some(match open.pop().expect("a container is open") {
+                    Container::List(list) => Bencode::List(list),
+                    Container::Dictionary { entries, .. } => Bencode::Dictionary(entries),
+                });
+            }
+        }
+    }
+
+    /// Reads up to the value of the next element of `container`: nothing for a list, and
+    /// the key and colon for a dictionary.
+    fn begin_element(&mut self, container: &mut Container) -> Result<(), TextError> {
+        if let Container::Dictionary { key, key_start, .. } = container {
+            *key_start = self.index;
+            if self.peek()? != '"' {
+                return Err(self.unexpected());
+            }
+            *key = self.parse_string()?;
+            self.expect(':')?;
+        }
+        Ok(())
+    }
+
+    fn add(&self, container: &mut Container, value: Bencode) -> Result<(), TextError> {
+        match container {
+            Container::List(list) => list.push(value),
+            Container::Dictionary {
+                entries,
+                key,
+                key_start,
+            } => match entries.entry(mem::take(key)) {
+                Entry::Vacant(entry) => {
+                    entry.insert(value);
+                }
+                Entry::Occupied(_) => {
+                    let (line, column) = self.location(*key_start);
+                    return Err(TextError::DuplicateKey { line, column });
+                }
+            },
+        }
+        Ok(())
+    }
+
+    /// Skips the comma before the next element of a list or dictionary, unless it would
+    /// be the `first`, returning whether there are no more elements instead.
+    fn end_of_container(&mut self, end: char, first: bool) -> Result<bool, TextError> {
+        if self.peek()? == end {
+            self.index += 1;
+            return Ok(true);
+        }
+        if !first {
+            self.expect(',')?;
+            if self.peek()? == end {
+                self.index += 1;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn parse_integer(&mut self) -> Result<Bencode, TextError> {
+        let start = self.index;
+        let rest = &self.text[start..];
+        let sign = usize::from(rest.starts_with('-'));
+        let digits = rest[sign..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len() - sign);
+        if digits == 0 {
+            self.index += sign;
+            return Err(self.unexpected());
+        }
+        self.index += sign + digits;
+        let number = &rest[..sign + digits];
+        if let Ok(integer) = number.parse() {
+            return Ok(Bencode::Integer(integer));
+        }
+        #[cfg(feature = "num-bigint")]
+        if let Ok(integer) = number.parse() {
+            return Ok(Bencode::BigInteger(integer));
+        }
+        let (line, column) = self.location(start);
+        Err(TextError::IntegerOutOfRange { line, column })
+    }
+
+    fn parse_string(&mut self) -> Result<Vec<u8>, TextError> {
+        self.index += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let rest = &self.text[self.index..];
+            let Some(special) = rest.find(['"', '\\']) else {
+                return Err(TextError::Eof);
+            };
+            bytes.extend_from_slice(&rest.as_bytes()[..special]);
+            self.index += special + 1;
+            if rest.as_bytes()[special] == b'"' {
+                return Ok(bytes);
+            }
+            let escape = self.index - 1;
+            let invalid = |parser: &Self| {
+                let (line, column) = parser.location(escape);
+                TextError::InvalidEscape { line, column }
+            };
+            let rest = &self.text[self.index..];
+            let (byte, length) = match rest.as_bytes().first() {
+                None => return Err(TextError::Eof),
+                Some(b'"') => (b'"', 1),
+                Some(b'\\') => (b'\\', 1),
+                Some(b'n') => (b'\n', 1),
+                Some(b'r') => (b'\r', 1),
+                Some(b't') => (b'\t', 1),
+                Some(b'x') => match rest
+                    .get(1..3)
+                    .filter(|hex| !hex.starts_with('+'))
+                    .map(|hex| u8::from_str_radix(hex, 16))
+                {
+                    Some(Ok(byte)) => (byte, 3),
+                    _ => return Err(invalid(self)),
+                },
+                Some(b'u') => {
+                    let c = rest
+                        .strip_prefix("u{")
+                        .and_then(|rest| rest.split_once('}'))
+                        .filter(|(hex, _)| !hex.starts_with('+'))
+                        .and_then(|(hex, _)| u32::from_str_radix(hex, 16).ok())
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| invalid(self))?;
+                    let length = rest.find('}').expect("the escape was just parsed") + 1;
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    self.index += length;
+                    continue;
+                }
+                Some(_) => return Err(invalid(self)),
+            };
+            bytes.push(byte);
+            self.index += length;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), TextError> {
+        if self.peek()? != expected {
+            return Err(self.unexpected());
+        }
+        self.index += expected.len_utf8();
+        Ok(())
+    }
+
+    /// Skips whitespace and comments, then returns the next character.
+    fn peek(&mut self) -> Result<char, TextError> {
+        loop {
+            let rest = &self.text[self.index..];
+            let trimmed = rest.trim_start();
+            self.index += rest.len() - trimmed.len();
+            match trimmed.strip_prefix('#') {
+                Some(comment) => {
+                    self.index += 1 + comment.find('\n').unwrap_or(comment.len());
+                }
+                None => return trimmed.chars().next().ok_or(TextError::Eof),
+            }
+        }
+    }
+
+    /// The error for the character at `index`, which is not what was expected.
+    fn unexpected(&self) -> TextError {
+        let (line, column) = self.location(self.index);
+        match self.text[self.index..].chars().next() {
+            Some(found) => TextError::UnexpectedChar {
+                line,
+                column,
+                found,
+            },
+            None => TextError::Eof,
+        }
+    }
+
+    /// The line and column of the character at `index`.
+    fn location(&self, index: usize) -> (usize, usize) {
+        let before = &self.text[..index];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let line = before.matches('\n').count() + 1;
+        (line, before[line_start..].chars().count() + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printing_and_parsing_round_trip() {
+        let value = bencode!({
+            "empty": [],
+            "nested": [{}, [1, -2]],
+            "text": "tab\t \"quoted\" \\ é \u{85}",
+            "binary": b"\x00\x7f\x80\xff".as_slice(),
+        });
+        let text = to_text(&value);
+        assert!(text.contains(r#""binary": "\x00\x7f\x80\xff""#));
+        assert!(text.contains(r#""text": "tab\t \"quoted\" \\ é \u{85}""#));
+        assert_eq!(from_text(&text), Ok(value));
+    }
+
+    #[test]
+    fn parsing_is_forgiving_but_exact() {
+        let text = "# a fixture\n{\"b\": [1, 2,], \"a\": \"x\"  # trailing comment\n}";
+        assert_eq!(from_text(text), Ok(bencode!({ "a": "x", "b": [1, 2] })));
+        assert_eq!(
+            from_text("[1,\n  2 3]"),
+            Err(TextError::UnexpectedChar {
+                line: 2,
+                column: 5,
+                found: '3'
+            })
+        );
+        assert_eq!(
+            from_text("{\"a\": 1, \"a\": 2}"),
+            Err(TextError::DuplicateKey {
+                line: 1,
+                column: 10
+            })
+        );
+        assert_eq!(
+            from_text("\"\\q\""),
+            Err(TextError::InvalidEscape { line: 1, column: 2 })
+        );
+        assert_eq!(
+            from_text("\"\\x+f\""),
+            Err(TextError::InvalidEscape { line: 1, column: 2 })
+        );
+        assert_eq!(from_text("[1,"), Err(TextError::Eof));
+        assert_eq!(from_text("[,]").map_err(|_| ()), Err(()));
+        assert_eq!(
+            from_text("1 2"),
+            Err(TextError::TrailingCharacters { line: 1, column: 3 })
+        );
+    }
+
+    #[test]
+    fn deep_nesting_does_not_overflow_the_stack() {
+        assert_eq!(from_text(&"[".repeat(1_000_000)), Err(TextError::Eof));
+        let text = format!("{}1{}", "[{\"a\": ".repeat(1000), "}]".repeat(1000));
+        assert_eq!(from_text(&text).unwrap().walk().count(), 2001);
+    }
+}
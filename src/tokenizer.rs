@@ -0,0 +1,388 @@
+//! Reading bencode as a stream of events, without building values out of it.
+
+use std::io::Read;
+use std::mem;
+
+use crate::decoder::{Decoder, DecoderOptions, DecoderResult, Expected};
+use crate::path::Segment;
+
+/// One step through the input, as returned by [`Tokenizer::next_event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    Integer(i128),
+    /// An integer too large for an `i128`, with the `num-bigint` feature.
+    #[cfg(feature = "num-bigint")]
+    BigInteger(&'a num_bigint::BigInt),
+    /// The start of a string of the given length, whose contents follow as
+    /// [`StringChunk`](Event::StringChunk)s. A dictionary key is a string like any other.
+    StringStart(u64),
+    /// The next piece of a string's contents, as much of it as is buffered.
+    StringChunk(&'a [u8]),
+    ListStart,
+    DictStart,
+    /// The end of the innermost list or dictionary.
+    End,
+}
+
+/// Walks through the input a piece at a time, so that documents of any size can be
+/// processed in as much memory as the reader's buffer takes.
+///
+/// The same limits are checked as by a [`Decoder`] with the same [`DecoderOptions`],
+/// including key order when decoding strictly, but malformed input is always an error:
+/// [`DecoderOptions::lenient`] is ignored. Integers that do not fit in an `i128` are
+/// returned as [`Event::BigInteger`] with the `num-bigint` feature, and otherwise
+/// reported as [`DecoderError::IntegerOverflow`](crate::DecoderError::IntegerOverflow),
+/// just as a [`Decoder`] decodes them.
+///
+/// Dictionary keys are kept while their value is tokenized, so that errors can name
+/// where they happened, but string contents otherwise never are.
+///
+/// ```
+/// use bencode::tokenizer::{Event, Tokenizer};
+///
+/// // Add up the file lengths in a torrent without decoding it.
+/// let torrent = b"d4:infod5:filesld6:lengthi5eed6:lengthi7eeeee";
+/// let mut tokenizer = Tokenizer::new(&torrent[..]);
+/// let mut total = 0;
+/// while let Some(event) = tokenizer.next_event().unwrap() {
+///     if let Event::Integer(length) = event {
+///         total += length;
+///     }
+/// }
+/// assert_eq!(total, 12);
+/// ```
+pub struct Tokenizer<R> {
+    decoder: Decoder<R>,
+    /// The lists and dictionaries open at this point in the input.
+    open: Vec<Open>,
+    /// The string whose contents are being returned, if any.
+    string: Option<StringState>,
+    /// How much of the last chunk returned is still to be consumed.
+    unconsumed: usize,
+    /// The last integer returned as an [`Event::BigInteger`].
+    #[cfg(feature = "num-bigint")]
+    big_integer: num_bigint::BigInt,
+}
+
+enum Open {
+    List {
+        /// How many elements have started so far.
+        length: usize,
+    },
+    Dictionary {
+        previous_key: Option<Vec<u8>>,
+        /// Whether a key has been read and its value comes next.
+        in_value: bool,
+    },
+}
+
+struct StringState {
+    /// Where the string started.
+    start: usize,
+    remaining: u64,
+    /// The contents so far, if the string is a dictionary key.
+    key: Option<Vec<u8>>,
+}
+
+impl<R: Read> Tokenizer<R> {
+    pub fn new(reader: R) -> Self {
+        Tokenizer::with_options(reader, DecoderOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: DecoderOptions) -> Self {
+        Tokenizer {
+            decoder: Decoder::with_options(
+                reader,
+                DecoderOptions {
+                    lenient: false,
+                    ..options
+                },
+            ),
+            open: Vec::new(),
+            string: None,
+            unconsumed: 0,
+            #[cfg(feature = "num-bigint")]
+            big_integer: num_bigint::BigInt::default(),
+        }
+    }
+
+    /// How many bytes of the input have been tokenized so far.
+    pub fn bytes_consumed(&self) -> usize {
+        self.decoder.bytes_consumed() + self.unconsumed
+    }
+
+    /// Returns the next event, or `None` once the input has run out between two values.
+    pub fn next_event(&mut self) -> DecoderResult<Option<Event<'_>>> {
+        self.decoder.consume(mem::take(&mut self.unconsumed));
+        if let Some(string) = &self.string {
+            if string.remaining > 0 {
+                return self.next_chunk().map(Some);
+            }
+            let string = self.string.take().expect("the string was just looked at");
+            match string.key {
+                Some(key) => self.finish_key(key, string.start)?,
+                None => self.finish_value(),
+            }
+        }
+
+        let expected = match self.open.last() {
+            None | Some(Open::Dictionary { in_value: true, .. }) => Expected::Value,
+            Some(Open::List { .. }) => Expected::ValueOrEnd,
+            Some(Open::Dictionary {
+                in_value: false, ..
+            }) => Expected::KeyOrEnd,
+        };
+        if self.open.is_empty() {
+            if self.decoder.at_end()? {
+                return Ok(None);
+            }
+            self.decoder.begin();
+        }
+        let byte = self.decoder.peek(expected)?;
+        if byte == b'e' && expected != Expected::Value {
+            self.decoder.advance(expected)?;
+            self.open.pop();
+            self.finish_value();
+            return Ok(Some(Event::End));
+        }
+        let in_key = expected == Expected::KeyOrEnd;
+        match self.open.last_mut() {
+            Some(Open::List { length }) => {
+                self.decoder.path_mut().push(Segment::Index(*length));
+                *length += 1;
+                self.decoder.count_element()?;
+            }
+            Some(Open::Dictionary { .. }) if in_key => self.decoder.count_element()?,
+            _ => {}
+        }
+
+        let start = self.decoder.bytes_consumed();
+        let event = match byte {
+            b'0'..=b'9' => {
+                let length = self.decoder.read_length()?;
+                self.decoder.check_string_length(length, start)?;
+                self.string = Some(StringState {
+                    start,
+                    remaining: length,
+                    key: in_key.then(Vec::new),
+                });
+                return Ok(Some(Event::StringStart(length)));
+            }
+            byte if in_key => return Err(self.decoder.unexpected_byte(byte, expected)),
+            b'i' => {
+                self.decoder.advance(expected)?;
+                let start = self.decoder.bytes_consumed();
+                let digits = self.decoder.read_integer_digits()?;
+                #[cfg(feature = "num-bigint")]
+                if digits.parse::<i128>().is_err() {
+                    self.big_integer = self.decoder.parse_number(&digits, start)?;
+                    self.finish_value();
+                    return Ok(Some(Event::BigInteger(&self.big_integer)));
+                }
+                let integer = self.decoder.parse_number(&digits, start)?;
+                self.finish_value();
+                Event::Integer(integer)
+            }
+            b'l' => {
+                self.decoder.check_depth()?;
+                self.decoder.advance(expected)?;
+                self.open.push(Open::List { length: 0 });
+                Event::ListStart
+            }
+            b'd' => {
+                self.decoder.check_depth()?;
+                self.decoder.advance(expected)?;
+                self.open.push(Open::Dictionary {
+                    previous_key: None,
+                    in_value: false,
+                });
+                Event::DictStart
+            }
+            byte => return Err(self.decoder.unexpected_byte(byte, expected)),
+        };
+        Ok(Some(event))
+    }
+
+    /// Returns as much of the current string's remaining contents as is buffered.
+    fn next_chunk(&mut self) -> DecoderResult<Event<'_>> {
+        let string = self.string.as_mut().expect("a string is being tokenized");
+        let available = self.decoder.buffered(Expected::StringBytes {
+            remaining: string.remaining,
+        })?;
+        let wanted = usize::try_from(string.remaining).unwrap_or(usize::MAX);
+        let chunk = &available[..available.len().min(wanted)];
+        string.remaining -= chunk.len() as u64;
+        if let Some(key) = &mut string.key {
+            key.extend_from_slice(chunk);
+        }
+        self.unconsumed = chunk.len();
+        Ok(Event::StringChunk(chunk))
+    }
+
+    /// Moves on to the value of the dictionary entry whose key has just been read.
+    fn finish_key(&mut self, key: Vec<u8>, offset: usize) -> DecoderResult<()> {
+        let Some(Open::Dictionary {
+            previous_key,
+            in_value,
+        }) = self.open.last_mut()
+        else {
+            unreachable!("keys are only read inside dictionaries");
+        };
+        if self.decoder.options().strict {
+            self.decoder
+                .check_key_order(previous_key.as_deref(), &key, offset)?;
+            *previous_key = Some(key.clone());
+        }
+        *in_value = true;
+        self.decoder.path_mut().push(Segment::Key(key));
+        Ok(())
+    }
+
+    /// Whether the events so far make up a whole value.
+    fn value_finished(&self) -> bool {
+        self.open.is_empty() && self.string.as_ref().is_none_or(|s| s.remaining == 0)
+    }
+
+    /// Moves on from a value that has just ended.
+    fn finish_value(&mut self) {
+        match self.open.last_mut() {
+            None => return,
+            Some(Open::List { .. }) => {}
+            Some(Open::Dictionary { in_value, .. }) => *in_value = false,
+        }
+        self.decoder.path_mut().pop();
+    }
+}
+
+/// Checks that `reader` holds exactly one well-formed value, without building it.
+///
+/// See [`validate_with_options`].
+pub fn validate<R: Read>(reader: R) -> DecoderResult<()> {
+    validate_with_options(reader, DecoderOptions::default())
+}
+
+/// Checks that `reader` holds exactly one value that a [`Decoder`] with `options` would
+/// decode, without building it.
+///
+/// Nothing is kept of the value but the keys of the dictionaries it is inside, so this
+/// is a cheap way to accept or reject a payload before handing it on. With
+/// [`DecoderOptions::strict`] set, the value must also be in canonical form.
+///
+/// ```
+/// use bencode::{validate, validate_with_options, DecoderError, DecoderOptions};
+///
+/// assert!(validate(&b"d1:ai03ee"[..]).is_ok());
+/// assert!(matches!(
+///     validate(&b"d1:ai1ee1:x"[..]),
+///     Err(DecoderError::TrailingBytes { offset: 8, .. })
+/// ));
+///
+/// let strict = DecoderOptions {
+///     strict: true,
+///     ..DecoderOptions::default()
+/// };
+/// assert!(matches!(
+///     validate_with_options(&b"d1:ai03ee"[..], strict),
+///     Err(DecoderError::NonCanonicalInteger { offset: 5, .. })
+/// ));
+/// ```
+pub fn validate_with_options<R: Read>(reader: R, options: DecoderOptions) -> DecoderResult<()> {
+    let mut tokenizer = Tokenizer::with_options(reader, options);
+    // Empty input is malformed, not merely out of values.
+    tokenizer.decoder.peek(Expected::Value)?;
+    while tokenizer.next_event()?.is_some() && !tokenizer.value_finished() {}
+    tokenizer
+        .decoder
+        .consume(mem::take(&mut tokenizer.unconsumed));
+    tokenizer.decoder.expect_end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder::DecoderError;
+
+    #[test]
+    fn chunks_cover_strings_in_small_buffers() {
+        let input = b"l5:hello0:d3:keyi-3eee";
+        for capacity in [1, 2, 64] {
+            let options = DecoderOptions {
+                buffer_size: capacity,
+                ..DecoderOptions::default()
+            };
+            let mut tokenizer = Tokenizer::with_options(&input[..], options);
+            let mut strings: Vec<Vec<u8>> = Vec::new();
+            let mut ends = 0;
+            while let Some(event) = tokenizer.next_event().unwrap() {
+                match event {
+                    Event::StringStart(_) => strings.push(Vec::new()),
+                    Event::StringChunk(chunk) => strings.last_mut().unwrap().extend(chunk),
+                    Event::End => ends += 1,
+                    _ => {}
+                }
+            }
+            assert_eq!(strings, [&b"hello"[..], b"", b"key"]);
+            assert_eq!(ends, 2);
+            assert_eq!(tokenizer.bytes_consumed(), input.len());
+        }
+    }
+
+    #[test]
+    fn errors_report_path() {
+        let mut tokenizer = Tokenizer::new(&b"d1:ali1ei2x"[..]);
+        let error = loop {
+            match tokenizer.next_event() {
+                Ok(Some(_)) => {}
+                Ok(None) => panic!("the input is malformed"),
+                Err(error) => break error,
+            }
+        };
+        assert!(matches!(
+            error,
+            DecoderError::UnexpectedByte { offset: 10, .. }
+        ));
+        assert_eq!(error.path().to_string(), "a[1]");
+    }
+
+    #[test]
+    fn integers_are_as_wide_as_decoded_ones() {
+        let mut tokenizer = Tokenizer::new(&b"li18446744073709551615ei-9223372036854775809ee"[..]);
+        let mut integers = Vec::new();
+        while let Some(event) = tokenizer.next_event().unwrap() {
+            if let Event::Integer(integer) = event {
+                integers.push(integer);
+            }
+        }
+        assert_eq!(integers, [i128::from(u64::MAX), i128::from(i64::MIN) - 1]);
+    }
+
+    #[test]
+    fn validation_accepts_what_a_decoder_decodes() {
+        assert!(validate(&b"i18446744073709551615e"[..]).is_ok());
+        for input in [
+            &b"i18446744073709551615e"[..],
+            b"d6:lengthi170141183460469231731687303715884105728ee",
+            b"i-170141183460469231731687303715884105729e",
+            b"i1x",
+        ] {
+            let decoded = Decoder::new(input).decode_complete();
+            assert_eq!(validate(input).is_ok(), decoded.is_ok());
+        }
+    }
+
+    #[test]
+    fn validation_needs_one_whole_value() {
+        assert!(validate(&b"0:"[..]).is_ok());
+        assert!(validate(&b"l5:helloe"[..]).is_ok());
+        for input in [&b""[..], b"l", b"5:hel", b"d1:a"] {
+            assert!(matches!(
+                validate(input),
+                Err(DecoderError::UnexpectedEof { .. })
+            ));
+        }
+        assert!(matches!(
+            validate(&b"i1ei2e"[..]),
+            Err(DecoderError::TrailingBytes { offset: 3, .. })
+        ));
+    }
+}
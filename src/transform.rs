@@ -0,0 +1,61 @@
+//! Bottom-up rewriting of [`Bencode`] trees.
+
+use std::convert::Infallible;
+
+use crate::value::Bencode;
+
+impl Bencode {
+    /// Rebuilds the tree bottom-up, replacing every value with the result of `f`.
+    ///
+    /// The contents of a list or dictionary are transformed before the container itself is
+    /// passed to `f`, and the root value is passed last.
+    ///
+    /// ```
+    /// use bencode::{bencode, Bencode};
+    ///
+    /// let value = bencode!({ "a": 1, "b": [2, "c"] });
+    /// let doubled = value.transform(|value| match value {
+    ///     Bencode::Integer(integer) => Bencode::Integer(integer * 2),
+    ///     other => other,
+    /// });
+    /// assert_eq!(doubled, bencode!({ "a": 2, "b": [4, "c"] }));
+    /// ```
+    pub fn transform<F>(self, mut f: F) -> Bencode
+    where
+        F: FnMut(Bencode) -> Bencode,
+    {
+        match self.transform_with::<Infallible>(&mut |value| Ok(f(value))) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Like [`transform`](Bencode::transform), but stops at the first error returned by `f`.
+    pub fn try_transform<F, E>(self, mut f: F) -> Result<Bencode, E>
+    where
+        F: FnMut(Bencode) -> Result<Bencode, E>,
+    {
+        self.transform_with(&mut f)
+    }
+
+    fn transform_with<E>(
+        self,
+        f: &mut dyn FnMut(Bencode) -> Result<Bencode, E>,
+    ) -> Result<Bencode, E> {
+        let value = match self {
+            Bencode::List(list) => Bencode::List(
+                list.into_iter()
+                    .map(|element| element.transform_with(f))
+                    .collect::<Result<_, E>>()?,
+            ),
+            Bencode::Dictionary(dictionary) => Bencode::Dictionary(
+                dictionary
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, value.transform_with(f)?)))
+                    .collect::<Result<_, E>>()?,
+            ),
+            other => other,
+        };
+        f(value)
+    }
+}
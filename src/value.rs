@@ -0,0 +1,507 @@
+//! The dynamically typed bencode value.
+
+use std::borrow::Cow;
+use std::collections::btree_map::{self, BTreeMap};
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+/// Any bencode value.
+///
+/// Values are ordered as their canonical encodings would be; see the [`Ord`] impl.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Bencode {
+    /// An integer, wide enough for any `i64` or `u64`.
+    Integer(i128),
+    /// An integer too large for [`Integer`](Bencode::Integer).
+    ///
+    /// Decoding produces this only for values outside the range of an `i128`, so that
+    /// numbers of any size survive a round trip. Smaller values in this variant are
+    /// encoded the same way but do not compare equal to the corresponding `Integer`.
+    #[cfg(feature = "num-bigint")]
+    BigInteger(num_bigint::BigInt),
+    String(Vec<u8>),
+    List(Vec<Bencode>),
+    /// A dictionary, whose keys are byte strings like any other bencode string.
+    ///
+    /// Keys are usually text, but nothing requires them to be valid UTF-8.
+    Dictionary(BTreeMap<Vec<u8>, Bencode>),
+}
+
+/// The four kinds of bencode value, without their contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BencodeKind {
+    Integer,
+    String,
+    List,
+    Dictionary,
+}
+
+impl fmt::Display for BencodeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BencodeKind::Integer => "integer",
+            BencodeKind::String => "string",
+            BencodeKind::List => "list",
+            BencodeKind::Dictionary => "dictionary",
+        })
+    }
+}
+
+impl Bencode {
+    /// Which kind of value this is; big integers are integers like any other.
+    pub fn kind(&self) -> BencodeKind {
+        match self {
+            Bencode::Integer(_) => BencodeKind::Integer,
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(_) => BencodeKind::Integer,
+            Bencode::String(_) => BencodeKind::String,
+            Bencode::List(_) => BencodeKind::List,
+            Bencode::Dictionary(_) => BencodeKind::Dictionary,
+        }
+    }
+
+    pub fn integer(integer: impl Into<i128>) -> Self {
+        Bencode::Integer(integer.into())
+    }
+
+    pub fn string(bytes: impl Into<Vec<u8>>) -> Self {
+        Bencode::String(bytes.into())
+    }
+
+    /// Creates a string holding the UTF-8 encoding of `string`.
+    pub fn from_utf8(string: &str) -> Self {
+        Bencode::String(string.as_bytes().to_vec())
+    }
+
+    /// Creates an empty list.
+    pub fn list() -> Self {
+        Bencode::List(Vec::new())
+    }
+
+    /// Creates an empty dictionary.
+    pub fn dictionary() -> Self {
+        Bencode::Dictionary(BTreeMap::new())
+    }
+
+    /// Gets the entry for `key` for in-place manipulation, if this is a dictionary.
+    ///
+    /// ```
+    /// use bencode::Bencode;
+    ///
+    /// let mut torrent = Bencode::dictionary();
+    /// if let Some(entry) = torrent.entry("comment") {
+    ///     entry.or_insert_with(|| Bencode::from("patched"));
+    /// }
+    /// ```
+    pub fn entry(
+        &mut self,
+        key: impl Into<Vec<u8>>,
+    ) -> Option<btree_map::Entry<'_, Vec<u8>, Bencode>> {
+        match self {
+            Bencode::Dictionary(dictionary) => Some(dictionary.entry(key.into())),
+            _ => None,
+        }
+    }
+
+    /// Takes the value out, leaving an empty string in its place.
+    ///
+    /// An empty string needs no allocation, so this is a cheap way to move a subtree out
+    /// of a larger document.
+    ///
+    /// ```
+    /// use bencode::{bencode, Bencode};
+    ///
+    /// let mut torrent = bencode!({ "info": { "name": "a.txt" } });
+    /// let info = torrent["info"].take();
+    /// assert_eq!(info, bencode!({ "name": "a.txt" }));
+    /// assert_eq!(torrent["info"], Bencode::default());
+    /// ```
+    pub fn take(&mut self) -> Bencode {
+        std::mem::take(self)
+    }
+
+    /// Puts `value` in place of this one, returning the old value.
+    pub fn replace(&mut self, value: Bencode) -> Bencode {
+        std::mem::replace(self, value)
+    }
+}
+
+/// The empty string.
+impl Default for Bencode {
+    fn default() -> Self {
+        Bencode::String(Vec::new())
+    }
+}
+
+macro_rules! accessors {
+    ($variant:ident: $ty:ty, $as_ref:ident -> $ref_ty:ty, $as_mut:ident, $into:ident) => {
+        pub fn $as_ref(&self) -> Option<$ref_ty> {
+            match self {
+                Bencode::$variant(value) => Some(value),
+                _ => None,
+            }
+        }
+
+        pub fn $as_mut(&mut self) -> Option<&mut $ty> {
+            match self {
+                Bencode::$variant(value) => Some(value),
+                _ => None,
+            }
+        }
+
+        pub fn $into(self) -> Option<$ty> {
+            match self {
+                Bencode::$variant(value) => Some(value),
+                _ => None,
+            }
+        }
+    };
+}
+
+/// Accessors returning the contents of one variant, or `None` for any other.
+impl Bencode {
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            Bencode::Integer(integer) => Some(*integer),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer_mut(&mut self) -> Option<&mut i128> {
+        match self {
+            Bencode::Integer(integer) => Some(integer),
+            _ => None,
+        }
+    }
+
+    pub fn into_integer(self) -> Option<i128> {
+        self.as_integer()
+    }
+
+    /// The integer, if it fits in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_integer()?.try_into().ok()
+    }
+
+    /// The integer, if it fits in a `u64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_integer()?.try_into().ok()
+    }
+
+    accessors!(String: Vec<u8>, as_string -> &[u8], as_string_mut, into_string);
+
+    /// The contents of a string, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(self.as_string()?).ok()
+    }
+
+    /// The contents of a string as text, with invalid UTF-8 replaced by `U+FFFD`.
+    pub fn as_str_lossy(&self) -> Option<Cow<'_, str>> {
+        self.as_string().map(String::from_utf8_lossy)
+    }
+    accessors!(List: Vec<Bencode>, as_list -> &[Bencode], as_list_mut, into_list);
+    accessors!(
+        Dictionary: BTreeMap<Vec<u8>, Bencode>,
+        as_dictionary -> &BTreeMap<Vec<u8>, Bencode>,
+        as_dictionary_mut,
+        into_dictionary
+    );
+}
+
+/// Looks up a dictionary entry.
+///
+/// # Panics
+///
+/// Panics if the value is not a dictionary or has no entry for the key. Use
+/// [`as_dictionary`](Bencode::as_dictionary) to look up entries that may be missing.
+impl Index<&str> for Bencode {
+    type Output = Bencode;
+
+    fn index(&self, key: &str) -> &Bencode {
+        match self {
+            Bencode::Dictionary(dictionary) => dictionary
+                .get(key.as_bytes())
+                .unwrap_or_else(|| panic!("no entry found for key {key:?}")),
+            _ => panic!("cannot index into a non-dictionary with key {key:?}"),
+        }
+    }
+}
+
+impl IndexMut<&str> for Bencode {
+    fn index_mut(&mut self, key: &str) -> &mut Bencode {
+        match self {
+            Bencode::Dictionary(dictionary) => dictionary
+                .get_mut(key.as_bytes())
+                .unwrap_or_else(|| panic!("no entry found for key {key:?}")),
+            _ => panic!("cannot index into a non-dictionary with key {key:?}"),
+        }
+    }
+}
+
+/// Looks up a list element.
+///
+/// # Panics
+///
+/// Panics if the value is not a list or the index is out of bounds.
+impl Index<usize> for Bencode {
+    type Output = Bencode;
+
+    fn index(&self, index: usize) -> &Bencode {
+        match self {
+            Bencode::List(list) => &list[index],
+            _ => panic!("cannot index into a non-list with index {index}"),
+        }
+    }
+}
+
+impl IndexMut<usize> for Bencode {
+    fn index_mut(&mut self, index: usize) -> &mut Bencode {
+        match self {
+            Bencode::List(list) => &mut list[index],
+            _ => panic!("cannot index into a non-list with index {index}"),
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", feature = "num-bigint"))]
+pub(crate) use serde_impls::serialize_big_integer;
+#[cfg(feature = "serde")]
+pub(crate) use serde_impls::{serialize_integer, ByteString, BIG_INTEGER_TOKEN};
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use std::collections::BTreeMap;
+    use std::fmt;
+
+    use serde::de::{
+        self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor,
+    };
+    use serde::ser::{Serialize, Serializer};
+
+    use super::Bencode;
+
+    impl Serialize for Bencode {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Bencode::Integer(integer) => serialize_integer(*integer, serializer),
+                #[cfg(feature = "num-bigint")]
+                Bencode::BigInteger(integer) => serialize_big_integer(integer, serializer),
+                Bencode::String(bytes) => ByteString(bytes).serialize(serializer),
+                Bencode::List(list) => serializer.collect_seq(list),
+                Bencode::Dictionary(dictionary) => serializer.collect_map(
+                    dictionary
+                        .iter()
+                        .map(|(key, value)| (ByteString(key), value)),
+                ),
+            }
+        }
+    }
+
+    /// Serializes an integer as the narrowest of `i64`, `u64` and `i128` that holds it, since
+    /// many formats support no more than 64 bits.
+    pub(crate) fn serialize_integer<S: Serializer>(
+        integer: i128,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if let Ok(integer) = i64::try_from(integer) {
+            serializer.serialize_i64(integer)
+        } else if let Ok(integer) = u64::try_from(integer) {
+            serializer.serialize_u64(integer)
+        } else {
+            serializer.serialize_i128(integer)
+        }
+    }
+
+    /// Serializes an integer too large for serde's integer types as its decimal digits.
+    ///
+    /// [`Serializer`](crate::ser::Serializer) recognizes the wrapper and writes an integer;
+    /// other formats see a string.
+    #[cfg(feature = "num-bigint")]
+    pub(crate) fn serialize_big_integer<S: Serializer>(
+        integer: &num_bigint::BigInt,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(BIG_INTEGER_TOKEN, &integer.to_string())
+    }
+
+    /// Marks the digits of an integer serialized by [`serialize_big_integer`].
+    pub(crate) const BIG_INTEGER_TOKEN: &str = "$bencode::private::BigInteger";
+
+    /// A string or dictionary key, serialized as text when it is valid UTF-8.
+    pub(crate) struct ByteString<'a>(pub(crate) &'a [u8]);
+
+    impl Serialize for ByteString<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            // Text is kept as a string so that formats like JSON stay readable.
+            match std::str::from_utf8(self.0) {
+                Ok(string) => serializer.serialize_str(string),
+                Err(_) => serializer.serialize_bytes(self.0),
+            }
+        }
+    }
+
+    /// Accepts a dictionary key given either as text or as bytes.
+    struct KeyVisitor;
+
+    impl<'de> Visitor<'de> for KeyVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a string")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+            Ok(v.as_bytes().to_vec())
+        }
+
+        fn visit_string<E: de::Error>(self, v: String) -> Result<Vec<u8>, E> {
+            Ok(v.into_bytes())
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+            Ok(v)
+        }
+    }
+
+    impl<'de> DeserializeSeed<'de> for KeyVisitor {
+        type Value = Vec<u8>;
+
+        fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<u8>, D::Error> {
+            deserializer.deserialize_any(self)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Bencode {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_any(BencodeVisitor)
+        }
+    }
+
+    /// The most list elements room is made for up front.
+    const MAX_PREALLOCATED: usize = 4096;
+
+    struct BencodeVisitor;
+
+    impl<'de> Visitor<'de> for BencodeVisitor {
+        type Value = Bencode;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an integer, string, list or dictionary")
+        }
+
+        fn visit_bool<E: de::Error>(self, v: bool) -> Result<Bencode, E> {
+            Ok(Bencode::Integer(v.into()))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Bencode, E> {
+            Ok(Bencode::Integer(v.into()))
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Bencode, E> {
+            Ok(Bencode::Integer(v.into()))
+        }
+
+        fn visit_i128<E: de::Error>(self, v: i128) -> Result<Bencode, E> {
+            Ok(Bencode::Integer(v))
+        }
+
+        fn visit_u128<E: de::Error>(self, v: u128) -> Result<Bencode, E> {
+            i128::try_from(v)
+                .map(Bencode::Integer)
+                .map_err(|_| E::custom(format_args!("integer {v} is out of range")))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Bencode, E> {
+            Ok(Bencode::String(v.as_bytes().to_vec()))
+        }
+
+        fn visit_string<E: de::Error>(self, v: String) -> Result<Bencode, E> {
+            Ok(Bencode::String(v.into_bytes()))
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Bencode, E> {
+            Ok(Bencode::String(v.to_vec()))
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Bencode, E> {
+            Ok(Bencode::String(v))
+        }
+
+        // Bencode has no null. Dictionary entries that are null are left out, as the
+        // serializer leaves out `None`, but anywhere else there is nothing to turn it into.
+        fn visit_unit<E: de::Error>(self) -> Result<Bencode, E> {
+            Err(E::custom("null cannot be represented in bencode"))
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Bencode, E> {
+            self.visit_unit()
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Bencode, A::Error> {
+            // The hint comes from the input, so it is only trusted so far.
+            let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATED));
+            while let Some(element) = seq.next_element()? {
+                list.push(element);
+            }
+            Ok(Bencode::List(list))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Bencode, A::Error> {
+            let mut dictionary = BTreeMap::new();
+            while let Some(key) = map.next_key_seed(KeyVisitor)? {
+                if let Some(value) = map.next_value::<Option<Bencode>>()? {
+                    dictionary.insert(key, value);
+                }
+            }
+            Ok(Bencode::Dictionary(dictionary))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use serde::de::value::{Error as ValueError, SeqDeserializer, UnitDeserializer};
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[test]
+    fn null_has_no_bencode_value() {
+        let error = Bencode::deserialize(UnitDeserializer::<ValueError>::new()).unwrap_err();
+        assert_eq!(error.to_string(), "null cannot be represented in bencode");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn null_dictionary_entries_are_left_out() {
+        let value: Bencode = serde_json::from_str(r#"{ "a": null, "b": [1, "x"] }"#).unwrap();
+        assert_eq!(value, bencode!({ "b": [1, "x"] }));
+        let error = serde_json::from_str::<Bencode>("[1, null]").unwrap_err();
+        assert!(error.to_string().contains("null cannot be represented"));
+    }
+
+    #[test]
+    fn size_hints_are_not_trusted() {
+        /// One element, claiming to be as many as there can be.
+        struct Boastful(Option<i64>);
+
+        impl Iterator for Boastful {
+            type Item = i64;
+
+            fn next(&mut self) -> Option<i64> {
+                self.0.take()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (usize::MAX, Some(usize::MAX))
+            }
+        }
+
+        let elements = SeqDeserializer::<_, ValueError>::new(Boastful(Some(1)));
+        assert_eq!(Bencode::deserialize(elements).unwrap(), bencode!([1]));
+    }
+}
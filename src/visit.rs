@@ -0,0 +1,77 @@
+//! Callback-based traversal of [`Bencode`] trees.
+
+use std::collections::BTreeMap;
+
+use crate::value::Bencode;
+
+/// Callbacks invoked by [`Bencode::accept`] for each part of a value, in document order.
+///
+/// Every method does nothing by default, so an implementation only overrides the ones it
+/// is interested in.
+///
+/// ```
+/// use bencode::{bencode, BencodeVisitor};
+///
+/// #[derive(Default)]
+/// struct StringBytes(usize);
+///
+/// impl BencodeVisitor for StringBytes {
+///     fn visit_string(&mut self, bytes: &[u8]) {
+///         self.0 += bytes.len();
+///     }
+/// }
+///
+/// let mut visitor = StringBytes::default();
+/// bencode!({ "announce": "http://tracker", "info": { "name": "a.txt" } }).accept(&mut visitor);
+/// assert_eq!(visitor.0, 19);
+/// ```
+pub trait BencodeVisitor {
+    fn visit_integer(&mut self, _integer: i128) {}
+
+    #[cfg(feature = "num-bigint")]
+    fn visit_big_integer(&mut self, _integer: &num_bigint::BigInt) {}
+
+    fn visit_string(&mut self, _bytes: &[u8]) {}
+
+    /// Called before the elements of a list are visited.
+    fn enter_list(&mut self, _list: &[Bencode]) {}
+
+    /// Called after the elements of a list have been visited.
+    fn leave_list(&mut self, _list: &[Bencode]) {}
+
+    /// Called before the entries of a dictionary are visited.
+    fn enter_dictionary(&mut self, _dictionary: &BTreeMap<Vec<u8>, Bencode>) {}
+
+    /// Called after the entries of a dictionary have been visited.
+    fn leave_dictionary(&mut self, _dictionary: &BTreeMap<Vec<u8>, Bencode>) {}
+
+    /// Called with the key of each dictionary entry, right before its value is visited.
+    fn visit_key(&mut self, _key: &[u8]) {}
+}
+
+impl Bencode {
+    /// Walks this value depth first, calling `visitor` for everything inside it.
+    pub fn accept<V: BencodeVisitor + ?Sized>(&self, visitor: &mut V) {
+        match self {
+            Bencode::Integer(integer) => visitor.visit_integer(*integer),
+            #[cfg(feature = "num-bigint")]
+            Bencode::BigInteger(integer) => visitor.visit_big_integer(integer),
+            Bencode::String(bytes) => visitor.visit_string(bytes),
+            Bencode::List(list) => {
+                visitor.enter_list(list);
+                for element in list {
+                    element.accept(visitor);
+                }
+                visitor.leave_list(list);
+            }
+            Bencode::Dictionary(dictionary) => {
+                visitor.enter_dictionary(dictionary);
+                for (key, value) in dictionary {
+                    visitor.visit_key(key);
+                    value.accept(visitor);
+                }
+                visitor.leave_dictionary(dictionary);
+            }
+        }
+    }
+}
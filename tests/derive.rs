@@ -0,0 +1,272 @@
+#![cfg(feature = "derive")]
+
+use std::collections::BTreeMap;
+
+use bencode::{Bencode, ConversionError, FromBencode, ToBencode};
+
+#[derive(Debug, PartialEq, ToBencode, FromBencode)]
+struct Torrent {
+    announce: String,
+    info: Info,
+    comment: Option<String>,
+}
+
+#[derive(Debug, PartialEq, ToBencode, FromBencode)]
+struct Info {
+    length: u64,
+    name: String,
+}
+
+#[derive(Debug, PartialEq, ToBencode, FromBencode)]
+enum Event {
+    Started,
+    #[bencode(rename = "stopped")]
+    Stopped,
+    Progress(u64),
+    Peer(Info),
+}
+
+fn dictionary<const N: usize>(entries: [(&str, Bencode); N]) -> Bencode {
+    Bencode::Dictionary(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.as_bytes().to_vec(), value))
+            .collect(),
+    )
+}
+
+#[test]
+fn structs_round_trip_as_dictionaries() {
+    let torrent = Torrent {
+        announce: "http://tracker".to_string(),
+        info: Info {
+            length: 42,
+            name: "a.txt".to_string(),
+        },
+        comment: None,
+    };
+    let value = torrent.to_bencode().unwrap();
+    assert_eq!(
+        value,
+        dictionary([
+            ("announce", Bencode::from_utf8("http://tracker")),
+            (
+                "info",
+                dictionary([
+                    ("length", Bencode::integer(42)),
+                    ("name", Bencode::from_utf8("a.txt")),
+                ])
+            ),
+        ])
+    );
+    assert_eq!(Torrent::from_bencode(value), Ok(torrent));
+}
+
+#[test]
+fn missing_fields_are_reported_by_key() {
+    let value = dictionary([("announce", Bencode::from_utf8("http://tracker"))]);
+    assert_eq!(
+        Torrent::from_bencode(value),
+        Err(ConversionError::MissingField("info"))
+    );
+}
+
+#[test]
+fn enums_round_trip_as_names_or_single_entries() {
+    let events = [
+        (Event::Started, Bencode::from_utf8("Started")),
+        (Event::Stopped, Bencode::from_utf8("stopped")),
+        (
+            Event::Progress(7),
+            dictionary([("Progress", Bencode::integer(7))]),
+        ),
+        (
+            Event::Peer(Info {
+                length: 1,
+                name: "b".to_string(),
+            }),
+            dictionary([(
+                "Peer",
+                dictionary([
+                    ("length", Bencode::integer(1)),
+                    ("name", Bencode::from_utf8("b")),
+                ]),
+            )]),
+        ),
+    ];
+    for (event, value) in events {
+        assert_eq!(event.to_bencode().unwrap(), value);
+        assert_eq!(Event::from_bencode(value), Ok(event));
+    }
+}
+
+#[test]
+fn unknown_variants_are_rejected() {
+    assert_eq!(
+        Event::from_bencode(Bencode::from_utf8("Progress")),
+        Err(ConversionError::UnknownVariant("Progress".to_string()))
+    );
+    assert_eq!(
+        Event::from_bencode(dictionary([("Started", Bencode::integer(1))])),
+        Err(ConversionError::UnknownVariant("Started".to_string()))
+    );
+    assert_eq!(
+        Event::from_bencode(dictionary([])),
+        Err(ConversionError::UnexpectedType {
+            expected: "dictionary with one entry",
+            found: "dictionary",
+        })
+    );
+}
+
+#[derive(Debug, PartialEq, ToBencode, FromBencode)]
+struct Attributes {
+    #[bencode(rename = "creation date")]
+    creation_date: i64,
+    #[bencode(skip)]
+    cached: u32,
+    #[bencode(default)]
+    private: u16,
+}
+
+#[test]
+fn renamed_fields_use_their_key() {
+    let value = Attributes {
+        creation_date: 1,
+        cached: 0,
+        private: 1,
+    }
+    .to_bencode()
+    .unwrap();
+    assert_eq!(
+        value,
+        dictionary([
+            ("creation date", Bencode::integer(1)),
+            ("private", Bencode::integer(1)),
+        ])
+    );
+    assert_eq!(
+        Attributes::from_bencode(dictionary([("creation_date", Bencode::integer(1))])),
+        Err(ConversionError::MissingField("creation date"))
+    );
+}
+
+#[test]
+fn skipped_fields_are_neither_written_nor_read() {
+    let attributes = Attributes {
+        creation_date: 1,
+        cached: 9,
+        private: 0,
+    };
+    let value = attributes.to_bencode().unwrap();
+    let Bencode::Dictionary(entries) = &value else {
+        panic!("expected a dictionary");
+    };
+    assert!(!entries.contains_key(b"cached".as_slice()));
+
+    let value = dictionary([
+        ("cached", Bencode::integer(9)),
+        ("creation date", Bencode::integer(1)),
+    ]);
+    assert_eq!(Attributes::from_bencode(value).unwrap().cached, 0);
+}
+
+#[test]
+fn default_fields_may_be_missing() {
+    let value = dictionary([("creation date", Bencode::integer(1))]);
+    assert_eq!(
+        Attributes::from_bencode(value),
+        Ok(Attributes {
+            creation_date: 1,
+            cached: 0,
+            private: 0,
+        })
+    );
+}
+
+#[derive(Debug, PartialEq, ToBencode, FromBencode)]
+#[bencode(deny_unknown_fields)]
+struct Strict {
+    length: u64,
+}
+
+#[derive(Debug, PartialEq, ToBencode, FromBencode)]
+#[bencode(extra = "rest")]
+struct Lenient {
+    length: u64,
+    rest: BTreeMap<Vec<u8>, Bencode>,
+}
+
+#[derive(Debug, PartialEq, ToBencode, FromBencode)]
+#[bencode(extra = "rest")]
+struct LenientText {
+    length: u64,
+    rest: BTreeMap<String, Bencode>,
+}
+
+#[test]
+fn unknown_fields_are_ignored_by_default() {
+    let value = dictionary([
+        ("length", Bencode::integer(3)),
+        ("name", Bencode::from_utf8("a.txt")),
+    ]);
+    assert_eq!(
+        Info::from_bencode(value.clone()).map(|info| info.length),
+        Ok(3)
+    );
+    assert_eq!(
+        Strict::from_bencode(value),
+        Err(ConversionError::UnknownField("name".to_string()))
+    );
+    assert_eq!(
+        Strict::from_bencode(dictionary([("length", Bencode::integer(3))])),
+        Ok(Strict { length: 3 })
+    );
+}
+
+#[test]
+fn extra_fields_are_kept_and_written_back() {
+    let value = dictionary([
+        ("length", Bencode::integer(3)),
+        ("name", Bencode::from_utf8("a.txt")),
+    ]);
+    let lenient = Lenient::from_bencode(value.clone()).unwrap();
+    assert_eq!(lenient.length, 3);
+    assert_eq!(
+        lenient.rest,
+        BTreeMap::from([(b"name".to_vec(), Bencode::from_utf8("a.txt"))])
+    );
+    assert_eq!(lenient.to_bencode().unwrap(), value);
+
+    let text = LenientText::from_bencode(value.clone()).unwrap();
+    assert_eq!(
+        text.rest,
+        BTreeMap::from([("name".to_string(), Bencode::from_utf8("a.txt"))])
+    );
+    assert_eq!(text.to_bencode().unwrap(), value);
+}
+
+#[test]
+fn extra_fields_never_override_real_ones() {
+    let lenient = Lenient {
+        length: 3,
+        rest: BTreeMap::from([(b"length".to_vec(), Bencode::integer(4))]),
+    };
+    assert_eq!(
+        lenient.to_bencode().unwrap(),
+        dictionary([("length", Bencode::integer(3))])
+    );
+}
+
+#[test]
+fn extra_text_fields_reject_keys_that_are_not_utf8() {
+    let value = Bencode::Dictionary(BTreeMap::from([
+        (b"length".to_vec(), Bencode::integer(3)),
+        (b"\xff".to_vec(), Bencode::integer(1)),
+    ]));
+    assert!(Lenient::from_bencode(value.clone()).is_ok());
+    assert_eq!(
+        LenientText::from_bencode(value),
+        Err(ConversionError::InvalidUtf8)
+    );
+}